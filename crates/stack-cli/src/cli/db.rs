@@ -0,0 +1,790 @@
+use crate::cli::{
+    manifest, migrations, new, secrets, DbBackupArgs, DbCloneArgs, DbDumpArgs, DbForwardArgs,
+    DbManifestArgs, DbMigrateNewArgs, DbPsqlArgs, DbRestoreArgs, DbRotatePasswordsArgs, DbTopArgs,
+};
+use crate::services::database::{self, Cluster, DATABASE_URLS_SECRET_NAME, DEFAULT_DB_PORT};
+use crate::services::database_backup;
+use crate::services::database_clone::{self, CloneState};
+use crate::services::database_migrations;
+use crate::services::database_monitoring;
+use crate::services::database_rotation::{self, Progress};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use k8s_openapi::api::core::v1::{Pod, Secret};
+use kube::{Api, Client, ResourceExt};
+use percent_encoding::percent_decode_str;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use url::Url;
+
+const POLL_INTERVAL_SECS: u64 = 5;
+const BACKUP_TIMEOUT_SECS: u64 = 3600;
+const RESTORE_TIMEOUT_SECS: u64 = 3600;
+const ROTATE_TIMEOUT_SECS: u64 = 600;
+const CLONE_TIMEOUT_SECS: u64 = 3600;
+const FIELD_SEPARATOR: &str = "\u{1f}";
+
+/// Database roles `stack db psql`, `dump` and `forward` connect as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DbRole {
+    /// `application_readonly`
+    Readonly,
+    /// `application_user`
+    App,
+    /// `db-owner`, which owns the schema
+    Owner,
+}
+
+impl DbRole {
+    fn url_key(self) -> &'static str {
+        match self {
+            DbRole::Readonly => "readonly-url",
+            DbRole::App => "application-url",
+            DbRole::Owner => "migrations-url",
+        }
+    }
+}
+
+pub async fn backup(args: &DbBackupArgs) -> Result<()> {
+    let (client, namespace, app_name) = connect(&args.target).await?;
+
+    let backup_name = database_backup::create_backup(client.clone(), &namespace, &app_name).await?;
+    println!("📦 Started backup {}", backup_name);
+    if !args.wait {
+        return Ok(());
+    }
+
+    let mut waited = 0;
+    loop {
+        let backup = database_backup::get_backup(client.clone(), &namespace, &backup_name).await?;
+        match database_backup::backup_phase(&backup) {
+            "completed" => break,
+            "failed" => {
+                let reason = backup
+                    .status
+                    .as_ref()
+                    .and_then(|status| status.error.clone())
+                    .unwrap_or_else(|| "no error reported".to_string());
+                bail!("Backup {} failed: {}", backup_name, reason);
+            }
+            _ => {}
+        }
+        if waited >= BACKUP_TIMEOUT_SECS {
+            bail!("Timed out waiting for backup {}", backup_name);
+        }
+        tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)).await;
+        waited += POLL_INTERVAL_SECS;
+    }
+    println!("✅ Backup {} completed", backup_name);
+
+    Ok(())
+}
+
+pub async fn list_backups(args: &DbManifestArgs) -> Result<()> {
+    let (client, namespace, app_name) = connect(args).await?;
+
+    let backups = database_backup::list_backups(client, &namespace, &app_name).await?;
+    if backups.is_empty() {
+        println!(
+            "No backups found for {}",
+            database::cluster_resource_name(&app_name)
+        );
+        return Ok(());
+    }
+
+    let rows: Vec<[String; 4]> = backups
+        .iter()
+        .map(|backup| {
+            let status = backup.status.clone().unwrap_or_default();
+            [
+                backup.name_any(),
+                database_backup::backup_phase(backup).to_string(),
+                status.started_at.unwrap_or_else(|| "-".to_string()),
+                status.stopped_at.unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+    let name_width = rows
+        .iter()
+        .map(|row| row[0].len())
+        .max()
+        .unwrap_or(0)
+        .max("NAME".len());
+    println!(
+        "{:<name_width$}  {:<10}  {:<20}  STOPPED",
+        "NAME", "PHASE", "STARTED"
+    );
+    for [name, phase, started, stopped] in rows {
+        println!("{name:<name_width$}  {phase:<10}  {started:<20}  {stopped}");
+    }
+
+    Ok(())
+}
+
+pub async fn restore(args: &DbRestoreArgs) -> Result<()> {
+    let (client, namespace, app_name) = connect(&args.target).await?;
+    let cluster_name = database::active_cluster_name(client.clone(), &namespace, &app_name).await?;
+
+    let question = match (&args.backup, &args.to) {
+        (Some(backup), _) => format!(
+            "Replace database cluster {} in {} with backup {}? Writes made after the backup are lost",
+            cluster_name, namespace, backup
+        ),
+        (None, Some(to)) => format!(
+            "Switch {} to a copy of {} as of {}? Writes made after that time are not carried over",
+            namespace, cluster_name, to
+        ),
+        (None, None) => bail!("Pass --backup or --to"),
+    };
+    if !args.yes && !new::confirm(&question, false)? {
+        println!("Restore cancelled");
+        return Ok(());
+    }
+
+    if let Some(backup) = &args.backup {
+        println!("♻️ Recovering {} into a new cluster...", backup);
+        let recovered =
+            database_backup::restore(client.clone(), &namespace, &app_name, backup).await?;
+        wait_for_ready(client.clone(), &namespace, &recovered).await?;
+        println!("✅ Recovered cluster {} is ready", recovered);
+
+        let restarted =
+            database_backup::switch_cluster(client.clone(), &namespace, &app_name, &recovered)
+                .await?;
+        println!("🔀 Pointed database-urls at {}", recovered);
+        for name in restarted {
+            println!("🔄 Restarted {}", name);
+        }
+        database_backup::delete_cluster(client, &namespace, &cluster_name).await?;
+        println!("🗑️ Deleted the previous cluster {}", cluster_name);
+        return Ok(());
+    }
+
+    let to = args.to.as_deref().unwrap_or_default();
+    println!("⏪ Replaying WAL from {} up to {}...", cluster_name, to);
+    let recovered =
+        database_backup::restore_to_time(client.clone(), &namespace, &app_name, to).await?;
+    wait_for_ready(client.clone(), &namespace, &recovered).await?;
+    println!("✅ Recovered cluster {} is ready", recovered);
+
+    let restarted =
+        database_backup::switch_cluster(client, &namespace, &app_name, &recovered).await?;
+    println!("🔀 Pointed database-urls at {}", recovered);
+    for name in restarted {
+        println!("🔄 Restarted {}", name);
+    }
+    println!(
+        "The previous cluster is still running; delete it once you are happy with the restore:\n  kubectl -n {} delete cluster {}",
+        namespace, cluster_name
+    );
+
+    Ok(())
+}
+
+pub async fn rotate_passwords(args: &DbRotatePasswordsArgs) -> Result<()> {
+    let (client, namespace, app_name) = connect(&args.target).await?;
+
+    let roles = if args.roles.is_empty() {
+        "every database role".to_string()
+    } else {
+        args.roles.join(", ")
+    };
+    let question = format!(
+        "Set new passwords for {} in {}? Services using them are restarted",
+        roles, namespace
+    );
+    if !args.yes && !new::confirm(&question, false)? {
+        println!("Rotation cancelled");
+        return Ok(());
+    }
+
+    database_rotation::start(client.clone(), &namespace, &app_name, &args.roles).await?;
+    println!("🔑 Setting new passwords for {}...", roles);
+
+    let mut waited = 0;
+    loop {
+        match database_rotation::progress(client.clone(), &namespace, &app_name).await? {
+            Progress::Running => {}
+            Progress::Failed(reason) => {
+                bail!("Rotation failed, the old passwords still apply: {}", reason)
+            }
+            Progress::Finished(restarted) => {
+                for name in restarted {
+                    println!("🔄 Restarted {}", name);
+                }
+                break;
+            }
+            // The operator picked up the finished Job first.
+            Progress::Idle => {
+                if let Some(reason) = database_rotation::failure(client.clone(), &namespace).await?
+                {
+                    bail!("Rotation failed, the old passwords still apply: {}", reason);
+                }
+                break;
+            }
+        }
+        if waited >= ROTATE_TIMEOUT_SECS {
+            bail!(
+                "Timed out waiting for the rotation; check `kubectl -n {} get job {}`",
+                namespace,
+                database_rotation::job_name(&app_name)
+            );
+        }
+        tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)).await;
+        waited += POLL_INTERVAL_SECS;
+    }
+    println!("✅ Rotated passwords for {}", roles);
+
+    Ok(())
+}
+
+pub async fn clone(args: &DbCloneArgs) -> Result<()> {
+    let mask_sql = args
+        .mask_sql
+        .as_ref()
+        .map(|path| {
+            fs::read_to_string(path)
+                .with_context(|| format!("Failed to read masking SQL at {}", path.display()))
+        })
+        .transpose()?;
+    let (client, from_namespace, from_app) = connect(&DbManifestArgs {
+        manifest: args.from_manifest.clone(),
+        profile: args.from_profile.clone(),
+    })
+    .await?;
+    let (to_namespace, to_app) = target(&DbManifestArgs {
+        manifest: args.to_manifest.clone(),
+        profile: args.to_profile.clone(),
+    })?;
+    if from_namespace == to_namespace {
+        bail!(
+            "Source and target are both in namespace {}; clone between namespaces",
+            to_namespace
+        );
+    }
+
+    let question = format!(
+        "Replace the database of {} in {} with a copy of {} in {}? Everything in it now is lost",
+        to_app, to_namespace, from_app, from_namespace
+    );
+    if !args.yes && !new::confirm(&question, false)? {
+        println!("Clone cancelled");
+        return Ok(());
+    }
+
+    let source = database_clone::source(client.clone(), &from_namespace, &from_app).await?;
+    database_clone::start(
+        client.clone(),
+        &to_namespace,
+        &to_app,
+        &source,
+        mask_sql.as_deref(),
+    )
+    .await?;
+    println!("📋 Copying {} into {}...", from_namespace, to_namespace);
+
+    let job_name = database_clone::job_name(&to_app);
+    let mut waited = 0;
+    loop {
+        match database_clone::state(client.clone(), &to_namespace, &to_app).await? {
+            CloneState::Succeeded => break,
+            CloneState::Failed => bail!(
+                "Clone failed; see `kubectl -n {} logs job/{}`",
+                to_namespace,
+                job_name
+            ),
+            CloneState::Running => {}
+        }
+        if waited >= CLONE_TIMEOUT_SECS {
+            bail!(
+                "Timed out waiting for the clone; check `kubectl -n {} logs job/{}`",
+                to_namespace,
+                job_name
+            );
+        }
+        tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)).await;
+        waited += POLL_INTERVAL_SECS;
+    }
+    if mask_sql.is_some() {
+        println!("🎭 Ran the masking SQL");
+    }
+    println!("✅ {} now holds a copy of {}", to_namespace, from_namespace);
+
+    Ok(())
+}
+
+pub async fn psql(args: &DbPsqlArgs) -> Result<()> {
+    let (client, namespace, app_name) = connect(&args.target).await?;
+    let (url, forwarder) = open_forward(client, &namespace, &app_name, args.role, None).await?;
+
+    // Ctrl-C is for psql, which cancels the running query with it. Without a handler of
+    // our own it would also end this process and the port-forward with it.
+    tokio::spawn(async { while tokio::signal::ctrl_c().await.is_ok() {} });
+    let result = run_client("psql", &url, &args.psql_args).await;
+    forwarder.abort();
+    result
+}
+
+pub async fn dump(args: &DbDumpArgs) -> Result<()> {
+    // The dump goes to stdout, so nothing else may.
+    let (namespace, app_name) = target(&args.target)?;
+    let client = Client::try_default().await?;
+    let (url, forwarder) = open_forward(client, &namespace, &app_name, args.role, None).await?;
+
+    eprintln!("📤 Dumping the {} database...", namespace);
+    let result = run_client("pg_dump", &url, &args.pg_dump_args).await;
+    forwarder.abort();
+    result
+}
+
+pub async fn forward(args: &DbForwardArgs) -> Result<()> {
+    let (client, namespace, app_name) = connect(&args.target).await?;
+    let (url, forwarder) =
+        open_forward(client, &namespace, &app_name, args.role, args.port).await?;
+
+    println!(
+        "🔀 Forwarding to the {} database, press Ctrl-C to stop",
+        namespace
+    );
+    println!("{}", url);
+    tokio::signal::ctrl_c().await?;
+    forwarder.abort();
+
+    Ok(())
+}
+
+pub async fn migrate_status(args: &DbManifestArgs) -> Result<()> {
+    let (stack_app, _) = manifest::load_stackapp(&args.manifest, args.profile.as_deref())?;
+    if stack_app.spec.migrations.is_none() {
+        bail!("{} has no spec.migrations", args.manifest.display());
+    }
+    let local = match migrations::sql_dir(&args.manifest, &stack_app) {
+        Some(dir) => migrations::sql_files(&dir)?,
+        None => Default::default(),
+    };
+
+    let (client, namespace, app_name) = connect(args).await?;
+    let (url, forwarder) = open_forward(client, &namespace, &app_name, DbRole::Owner, None).await?;
+    let table = database_migrations::TRACKING_TABLE;
+    // The table is referenced only once it exists, which is after the first Job ran.
+    let exists = query_client(
+        &url,
+        &format!("SELECT to_regclass('{}') IS NOT NULL", table),
+    )
+    .await;
+    let applied = match exists {
+        Ok(exists) if first_field(&exists) == Some("t") => {
+            query_client(
+                &url,
+                &format!("SELECT version, applied_at FROM {} ORDER BY version", table),
+            )
+            .await
+        }
+        Ok(_) => Ok(Vec::new()),
+        Err(err) => Err(err),
+    };
+    forwarder.abort();
+    let applied: BTreeMap<String, String> = applied?
+        .into_iter()
+        .filter_map(|row| match <[String; 2]>::try_from(row) {
+            Ok([version, applied_at]) => Some((version, applied_at)),
+            Err(_) => None,
+        })
+        .collect();
+
+    let mut rows: BTreeMap<String, String> = local
+        .keys()
+        .map(|file| {
+            let version = file.trim_end_matches(".sql").to_string();
+            let state = match applied.get(&version) {
+                Some(applied_at) => format!("applied {}", applied_at),
+                None => "pending".to_string(),
+            };
+            (version, state)
+        })
+        .collect();
+    for (version, applied_at) in &applied {
+        rows.entry(version.clone())
+            .or_insert_with(|| format!("applied {}", applied_at));
+    }
+    if rows.is_empty() {
+        println!("No migrations yet; create one with `stack db migrate new <name>`");
+        return Ok(());
+    }
+
+    let version_width = rows
+        .keys()
+        .map(String::len)
+        .max()
+        .unwrap_or(0)
+        .max("VERSION".len());
+    println!("{:<version_width$}  STATE", "VERSION");
+    for (version, state) in rows {
+        println!("{version:<version_width$}  {state}");
+    }
+
+    Ok(())
+}
+
+pub fn migrate_new(args: &DbMigrateNewArgs) -> Result<()> {
+    let (stack_app, _) =
+        manifest::load_stackapp(&args.target.manifest, args.target.profile.as_deref())?;
+    let dir = migrations::sql_dir(&args.target.manifest, &stack_app).ok_or_else(|| {
+        anyhow!(
+            "{} has no spec.migrations.sql_dir",
+            args.target.manifest.display()
+        )
+    })?;
+    let files = migrations::sql_files(&dir)?;
+
+    let numbers: Vec<&str> = files
+        .keys()
+        .map(|file| {
+            file.split(|ch: char| !ch.is_ascii_digit())
+                .next()
+                .unwrap_or_default()
+        })
+        .collect();
+    let width = numbers
+        .iter()
+        .map(|number| number.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    let next = numbers
+        .iter()
+        .filter_map(|number| number.parse::<u64>().ok())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let slug: String = args
+        .name
+        .trim()
+        .to_ascii_lowercase()
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+        .collect();
+    let slug = slug.trim_matches('_');
+    if slug.is_empty() {
+        bail!("Give the migration a name with letters or digits in it");
+    }
+
+    let path = dir.join(format!("{:0width$}_{}.sql", next, slug));
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    fs::write(&path, format!("-- {}\n", args.name.trim()))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("📝 Created {}", path.display());
+
+    Ok(())
+}
+
+pub async fn top(args: &DbTopArgs) -> Result<()> {
+    let (client, namespace, app_name) = connect(&args.target).await?;
+    let (url, forwarder) = open_forward(client, &namespace, &app_name, DbRole::Owner, None).await?;
+    let result = print_top(&url, args.limit).await;
+    forwarder.abort();
+    result
+}
+
+async fn print_top(url: &str, limit: u32) -> Result<()> {
+    let installed = query_client(
+        url,
+        &format!(
+            "SELECT count(*) FROM pg_extension WHERE extname = '{}'",
+            database_monitoring::STATEMENTS_EXTENSION
+        ),
+    )
+    .await?;
+    println!("Slowest queries");
+    if first_field(&installed) == Some("1") {
+        let rows = query_client(
+            url,
+            &format!(
+                "SELECT calls, round(mean_exec_time::numeric, 2), round(total_exec_time::numeric, 2), \
+                 left(regexp_replace(query, '\\s+', ' ', 'g'), 80) \
+                 FROM pg_stat_statements WHERE dbid = (SELECT oid FROM pg_database WHERE datname = current_database()) \
+                 ORDER BY mean_exec_time DESC LIMIT {}",
+                limit
+            ),
+        )
+        .await?;
+        print_table(&["CALLS", "MEAN MS", "TOTAL MS", "QUERY"], rows);
+    } else {
+        println!(
+            "  {} is not installed; add components.db.monitoring to the manifest and deploy",
+            database_monitoring::STATEMENTS_EXTENSION
+        );
+    }
+
+    println!();
+    println!("Connections");
+    let rows = query_client(
+        url,
+        "SELECT coalesce(usename, '-'), coalesce(state, '-'), count(*) FROM pg_stat_activity \
+         WHERE backend_type = 'client backend' GROUP BY 1, 2 ORDER BY 3 DESC, 1, 2",
+    )
+    .await?;
+    print_table(&["ROLE", "STATE", "COUNT"], rows);
+
+    println!();
+    println!("Dead rows");
+    let rows = query_client(
+        url,
+        &format!(
+            "SELECT schemaname || '.' || relname, n_live_tup, n_dead_tup, \
+             round(100.0 * n_dead_tup / greatest(n_live_tup + n_dead_tup, 1), 1), \
+             pg_size_pretty(pg_total_relation_size(relid)) \
+             FROM pg_stat_user_tables ORDER BY n_dead_tup DESC, 1 LIMIT {}",
+            limit
+        ),
+    )
+    .await?;
+    print_table(&["TABLE", "LIVE", "DEAD", "DEAD %", "SIZE"], rows);
+
+    Ok(())
+}
+
+/// Prints `rows` under `headers`, each column as wide as its widest value.
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    if rows.is_empty() {
+        println!("  none");
+        return;
+    }
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(column, header)| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|value| value.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(header.len())
+        })
+        .collect();
+    let format_row = |values: Vec<&str>| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:<width$}", value, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", format_row(headers.to_vec()));
+    for row in &rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+}
+
+/// Listens on `port` of localhost, or on a free port when `None`, and forwards every
+/// connection to the primary of the app's cluster. Returns the URL of `role` rewritten
+/// to point at the listener, and the task accepting connections.
+async fn open_forward(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    role: DbRole,
+    port: Option<u16>,
+) -> Result<(String, JoinHandle<()>)> {
+    let secret_api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let urls = secret_api
+        .get_opt(DATABASE_URLS_SECRET_NAME)
+        .await?
+        .ok_or_else(|| {
+            anyhow!(
+                "{} has no {} secret yet; deploy the app first",
+                namespace,
+                DATABASE_URLS_SECRET_NAME
+            )
+        })?;
+    let url = secrets::decode_secret_field(&urls, role.url_key())
+        .ok_or_else(|| anyhow!("{} has no {}", DATABASE_URLS_SECRET_NAME, role.url_key()))?;
+
+    let cluster_name = database::active_cluster_name(client.clone(), namespace, app_name).await?;
+    let cluster_api: Api<Cluster> = Api::namespaced(client.clone(), namespace);
+    let primary = cluster_api
+        .get_opt(&cluster_name)
+        .await?
+        .and_then(|cluster| cluster.status)
+        .and_then(|status| status.current_primary)
+        .ok_or_else(|| {
+            anyhow!(
+                "Cluster {} has no running primary; with components.db.external, connect to the server directly",
+                cluster_name
+            )
+        })?;
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port.unwrap_or(0)))
+        .await
+        .with_context(|| format!("Failed to listen on local port {}", port.unwrap_or(0)))?;
+    let local_port = listener.local_addr()?.port();
+    let pod_api: Api<Pod> = Api::namespaced(client, namespace);
+    let forwarder = tokio::spawn(async move {
+        while let Ok((mut connection, _)) = listener.accept().await {
+            let pod_api = pod_api.clone();
+            let primary = primary.clone();
+            tokio::spawn(async move {
+                if let Err(err) = forward_connection(&pod_api, &primary, &mut connection).await {
+                    eprintln!("⚠️ Port-forward to {} failed: {}", primary, err);
+                }
+            });
+        }
+    });
+
+    let url = secrets::rewrite_database_url(
+        &url,
+        Some(&Ipv4Addr::LOCALHOST.to_string()),
+        Some(local_port),
+    )?;
+    Ok((url, forwarder))
+}
+
+async fn forward_connection(
+    pod_api: &Api<Pod>,
+    pod_name: &str,
+    connection: &mut TcpStream,
+) -> Result<()> {
+    let mut port_forward = pod_api.portforward(pod_name, &[DEFAULT_DB_PORT]).await?;
+    let mut upstream = port_forward
+        .take_stream(DEFAULT_DB_PORT)
+        .ok_or_else(|| anyhow!("No stream for port {}", DEFAULT_DB_PORT))?;
+    tokio::io::copy_bidirectional(connection, &mut upstream).await?;
+    drop(upstream);
+    port_forward.join().await?;
+    Ok(())
+}
+
+/// A PostgreSQL client tool installed locally, pointed at `url`. The password goes in
+/// `PGPASSWORD` rather than the command line, where other local users could read it.
+fn client_command<I, S>(program: &str, url: &str, args: I) -> Result<Command>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut url = Url::parse(url).context("Failed to parse the database URL")?;
+    let password = url.password().map(|password| {
+        percent_decode_str(password)
+            .decode_utf8_lossy()
+            .into_owned()
+    });
+    let mut command = Command::new(program);
+    command.args(args);
+    if let Some(password) = password {
+        url.set_password(None)
+            .map_err(|_| anyhow!("Failed to remove the password from the database URL"))?;
+        command.env("PGPASSWORD", password);
+    }
+    command.arg(format!("--dbname={}", url));
+    Ok(command)
+}
+
+/// Runs a PostgreSQL client tool installed locally against `url`.
+async fn run_client(program: &str, url: &str, extra_args: &[String]) -> Result<()> {
+    let status = client_command(program, url, extra_args)?
+        .status()
+        .await
+        .map_err(|err| spawn_error(program, err))?;
+    if !status.success() {
+        bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
+
+/// Runs `query` with the local psql and returns its rows. Fields are split on the unit
+/// separator, which unlike `|` does not turn up in query texts.
+async fn query_client(url: &str, query: &str) -> Result<Vec<Vec<String>>> {
+    let output = client_command(
+        "psql",
+        url,
+        [
+            "-v",
+            "ON_ERROR_STOP=1",
+            "-At",
+            "-F",
+            FIELD_SEPARATOR,
+            "-c",
+            query,
+        ],
+    )?
+    .output()
+    .await
+    .map_err(|err| spawn_error("psql", err))?;
+    if !output.status.success() {
+        bail!(
+            "psql exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.split(FIELD_SEPARATOR).map(str::to_string).collect())
+        .collect())
+}
+
+fn first_field(rows: &[Vec<String>]) -> Option<&str> {
+    rows.first()?.first().map(String::as_str)
+}
+
+fn spawn_error(program: &str, err: io::Error) -> anyhow::Error {
+    match err.kind() {
+        io::ErrorKind::NotFound => anyhow!(
+            "{} is not installed; install the PostgreSQL client tools",
+            program
+        ),
+        _ => anyhow!("Failed to run {}: {}", program, err),
+    }
+}
+
+async fn wait_for_ready(client: Client, namespace: &str, cluster_name: &str) -> Result<()> {
+    let cluster_api: Api<Cluster> = Api::namespaced(client, namespace);
+    let mut waited = 0;
+    loop {
+        let status = cluster_api
+            .get(cluster_name)
+            .await
+            .ok()
+            .and_then(|cluster| cluster.status);
+        if status.as_ref().and_then(|s| s.ready_instances).unwrap_or(0) > 0 {
+            return Ok(());
+        }
+        if waited >= RESTORE_TIMEOUT_SECS {
+            bail!(
+                "Timed out waiting for {} to become ready; check `kubectl -n {} get cluster {}`",
+                cluster_name,
+                namespace,
+                cluster_name
+            );
+        }
+        tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)).await;
+        waited += POLL_INTERVAL_SECS;
+    }
+}
+
+async fn connect(args: &DbManifestArgs) -> Result<(Client, String, String)> {
+    let (namespace, app_name) = target(args)?;
+
+    println!("🔌 Connecting to the cluster...");
+    let client = Client::try_default().await?;
+    println!("✅ Connected");
+
+    Ok((client, namespace, app_name))
+}
+
+/// Namespace and name of the StackApp in the manifest.
+fn target(args: &DbManifestArgs) -> Result<(String, String)> {
+    let (stack_app, _) = manifest::load_stackapp(&args.manifest, args.profile.as_deref())?;
+    let namespace = stack_app
+        .namespace()
+        .ok_or_else(|| anyhow!("StackApp manifest is missing metadata.namespace"))?;
+    Ok((namespace, stack_app.name_any()))
+}
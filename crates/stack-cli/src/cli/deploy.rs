@@ -1,7 +1,7 @@
 use super::{
     apply,
     init::{ensure_namespace, ensure_stackapp_crd},
-    manifest,
+    manifest, migrations,
 };
 use anyhow::{anyhow, Context, Result};
 use kube::{Client, ResourceExt};
@@ -23,6 +23,8 @@ pub async fn deploy(deployer: &crate::cli::Deployer) -> Result<()> {
     ensure_stackapp_crd(&client).await?;
     ensure_namespace(&client, &namespace).await?;
 
+    migrations::package(&client, &deployer.manifest, &stack_app).await?;
+
     apply::apply(&client, &manifest_raw, None)
         .await
         .context("Failed to apply StackApp manifest")?;
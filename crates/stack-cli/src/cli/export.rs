@@ -0,0 +1,798 @@
+use crate::cli::manifest;
+use crate::operator::reconcile;
+use crate::services::deployment::{Command, ServiceDeployment};
+use crate::services::{
+    auth, credentials, database, database_extensions, database_services, jwt_secrets, nginx,
+    postgrest, realtime, storage,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use kube::ResourceExt;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+const COMPOSE_FILE: &str = "docker-compose.yml";
+const ENV_FILE: &str = ".env";
+const NGINX_CONFIG_FILE: &str = "default.conf";
+const DB_INIT_SCRIPT: &str = "db/init.sh";
+const DB_SERVICE_NAME: &str = "db";
+/// Official-image based so `docker-entrypoint-initdb.d` works; ships pgvector for the
+/// `vector` extension created at initdb.
+const DB_IMAGE: &str = "pgvector/pgvector:pg17";
+const DB_PORT: u16 = 5432;
+const DEFAULT_NGINX_HOST_PORT: u16 = 8080;
+const POSTGRES_PASSWORD_VAR: &str = "POSTGRES_PASSWORD";
+const APPLICATION_PASSWORD_VAR: &str = "APPLICATION_USER_PASSWORD";
+const READONLY_PASSWORD_VAR: &str = "APPLICATION_READONLY_PASSWORD";
+
+pub fn compose(args: &crate::cli::ExportComposeArgs) -> Result<()> {
+    let (stack_app, _) = manifest::load_stackapp(&args.manifest, args.profile.as_deref())?;
+    let app_name = stack_app.name_any();
+    let spec = &stack_app.spec;
+    let components = &spec.components;
+    let web_port = spec
+        .services
+        .web
+        .port
+        .ok_or_else(|| anyhow!("spec.services.web.port is required for the web service"))?;
+
+    let compose_path = args.output_dir.join(COMPOSE_FILE);
+    if compose_path.exists() && !args.force {
+        bail!(
+            "{} already exists; pass --force to overwrite it",
+            compose_path.display()
+        );
+    }
+
+    let env_path = args.output_dir.join(ENV_FILE);
+    let mut env = EnvFile::read(&env_path)?;
+    let mut report = Vec::new();
+
+    // Secrets the operator would create, reusing values from an earlier export so an
+    // existing database volume keeps working.
+    let db_name = database::database_name(&app_name);
+    let db_host = database::cluster_rw_service_name(&app_name);
+    let override_password = components
+        .db
+        .as_ref()
+        .and_then(|db| db.danger_override_password.clone());
+    let password = |env: &EnvFile, var: &str| {
+        override_password
+            .clone()
+            .or_else(|| env.existing(var))
+            .unwrap_or_else(credentials::password)
+    };
+
+    let superuser_password = password(&env, POSTGRES_PASSWORD_VAR);
+    let dbowner_password = password(&env, &env_key("db-owner", "password"));
+    let authenticator_password = password(&env, &env_key("db-authenticator", "password"));
+    let app_database_password = password(&env, APPLICATION_PASSWORD_VAR);
+    let readonly_database_password = password(&env, READONLY_PASSWORD_VAR);
+
+    env.set(POSTGRES_PASSWORD_VAR, superuser_password);
+    env.set(APPLICATION_PASSWORD_VAR, app_database_password.clone());
+    env.set(READONLY_PASSWORD_VAR, readonly_database_password.clone());
+    env.set_secret("db-owner", "username", "db-owner".to_string());
+    env.set_secret("db-owner", "password", dbowner_password.clone());
+    env.set_secret("db-authenticator", "username", "authenticator".to_string());
+    env.set_secret(
+        "db-authenticator",
+        "password",
+        authenticator_password.clone(),
+    );
+    let endpoint = database::DatabaseEndpoint {
+        host: db_host.clone(),
+        readonly_host: db_host.clone(),
+        pooled_host: db_host.clone(),
+        port: database::DEFAULT_DB_PORT,
+        sslmode: "disable".to_string(),
+    };
+    let credentials = database::RoleCredentials {
+        dbowner_user: "db-owner".to_string(),
+        dbowner_password: dbowner_password.clone(),
+        application_password: app_database_password.clone(),
+        readonly_password: readonly_database_password.clone(),
+        authenticator_password: authenticator_password.clone(),
+    };
+    let database_urls = database::database_urls(&endpoint, &db_name, &credentials);
+    for (key, value) in &database_urls {
+        env.set_secret("database-urls", key, value.clone());
+    }
+
+    let jwt = jwt_secrets::secret_data(|key| {
+        env.existing(&env_key(jwt_secrets::JWT_AUTH_SECRET_NAME, key))
+    })?;
+    let anon_jwt = jwt[jwt_secrets::JWT_ANON_TOKEN_KEY].clone();
+    for (key, value) in jwt {
+        env.set_secret(jwt_secrets::JWT_AUTH_SECRET_NAME, key, value);
+    }
+
+    if components.auth.is_some() {
+        let auth_secret =
+            auth::secret_data(|key| env.existing(&env_key(auth::AUTH_ADMIN_SECRET_NAME, key)));
+        for (key, value) in auth_secret {
+            env.set_secret(auth::AUTH_ADMIN_SECRET_NAME, key, value);
+        }
+    }
+
+    if components.realtime.is_some() {
+        let realtime_secret = realtime::secret_data(|key| {
+            env.existing(&env_key(realtime::REALTIME_SECRET_NAME, key))
+        });
+        for (key, value) in realtime_secret {
+            env.set_secret(realtime::REALTIME_SECRET_NAME, key, value);
+        }
+    }
+
+    // A user-provided S3 secret is left to `.env`; its keys are reported as missing.
+    let storage_config = components.storage.as_ref();
+    let bundles_minio = storage_config.is_some() && storage::installs_minio(storage_config);
+    if storage_config.is_some_and(|config| config.s3_secret_name.is_none()) {
+        let s3_secret = storage::s3_secret_data(|key| {
+            env.existing(&env_key(storage::STORAGE_S3_SECRET_NAME, key))
+        });
+        for (key, value) in s3_secret {
+            env.set_secret(storage::STORAGE_S3_SECRET_NAME, key, value);
+        }
+    }
+
+    // Workloads, built exactly as the operator builds its Deployments.
+    let mut app_services = vec![reconcile::web_service_deployment(spec, &app_name, web_port)];
+    for (name, service) in &spec.services.extra {
+        app_services.push(reconcile::extra_service_deployment(name, service));
+    }
+    if let Some(auth_config) = components.auth.as_ref() {
+        app_services.push(auth::service_deployment(&app_name, auth_config));
+    }
+    if let Some(rest_config) = components.rest.as_ref() {
+        app_services.push(postgrest::service_deployment(Some(rest_config)));
+    }
+    if components.realtime.is_some() {
+        app_services.push(realtime::service_deployment(&app_name));
+    }
+    let minio =
+        bundles_minio.then(|| storage::minio_deployment(&storage::s3_secret_name(storage_config)));
+    if let Some(config) = storage_config {
+        app_services.push(storage::service_deployment(&app_name, Some(config)));
+    }
+
+    if components.oidc.is_some() {
+        report.push(
+            "components.oidc: Keycloak and oauth2-proxy are not exported; nginx forwards the anon JWT instead"
+                .to_string(),
+        );
+    }
+    for (component, enabled) in [
+        ("document_engine", components.document_engine.is_some()),
+        ("selenium", components.selenium.is_some()),
+        ("mailhog", components.mailhog.is_some()),
+    ] {
+        if enabled {
+            report.push(format!("components.{}: not exported", component));
+        }
+    }
+    if components
+        .db
+        .as_ref()
+        .and_then(|db| db.image_name.as_ref())
+        .is_some()
+    {
+        report.push(format!(
+            "components.db.image_name: CloudNativePG images are not used; the database runs {}",
+            DB_IMAGE
+        ));
+    }
+    if components
+        .db
+        .as_ref()
+        .and_then(|db| db.instances)
+        .is_some_and(|instances| instances > 1)
+    {
+        report.push(
+            "components.db.instances: compose runs a single Postgres; readonly-url points at it"
+                .to_string(),
+        );
+    }
+
+    if components
+        .db
+        .as_ref()
+        .is_some_and(|db| db.external.is_some())
+    {
+        report.push(
+            "components.db.external: compose runs its own Postgres instead of the external server"
+                .to_string(),
+        );
+    }
+    // Compose has no per-service roles; those services get the application role instead.
+    for (service, _) in database_services::service_databases(spec, &app_name) {
+        let secret_name = database_services::secret_name(&service);
+        env.set_secret(&secret_name, "username", "application_user".to_string());
+        env.set_secret(&secret_name, "password", app_database_password.clone());
+        env.set_secret(
+            &secret_name,
+            "url",
+            database_urls["application-url"].clone(),
+        );
+        let key = if service == app_name {
+            "web"
+        } else {
+            service.as_str()
+        };
+        report.push(format!(
+            "services.{}.database: no dedicated role or schema; the service connects as application_user",
+            key
+        ));
+    }
+    if spec.migrations.is_some() {
+        report.push(
+            "spec.migrations: not applied; run the SQL files against the compose database yourself"
+                .to_string(),
+        );
+    }
+    if components
+        .db
+        .as_ref()
+        .is_some_and(|db| db.monitoring.is_some())
+    {
+        report.push(
+            "components.db.monitoring: no PodMonitor, and pg_stat_statements is created but not preloaded"
+                .to_string(),
+        );
+    }
+    if components
+        .db
+        .as_ref()
+        .is_some_and(|db| db.webhooks.is_some())
+    {
+        report.push(
+            "components.db.webhooks: no triggers or dispatcher; call the endpoints from the app instead"
+                .to_string(),
+        );
+    }
+    if components.db.as_ref().is_some_and(|db| db.cdc.is_some()) {
+        report.push(
+            "components.db.cdc: no publication, slot or relay; nothing streams row changes"
+                .to_string(),
+        );
+    }
+    if components.db.as_ref().is_some_and(|db| db.pooler.is_some()) {
+        report.push(
+            "components.db.pooler: compose has no PgBouncer; application-pooled-url points at Postgres"
+                .to_string(),
+        );
+    }
+
+    let nginx_config = nginx::nginx_config(
+        &nginx::NginxMode::StaticJwt { token: anon_jwt },
+        web_port,
+        &app_name,
+        components.auth.is_some(),
+        storage_config.is_some(),
+        storage::storage_upload_size_limit_bytes(storage_config),
+        components.rest.is_some(),
+        components.realtime.is_some(),
+        false,
+    );
+
+    let mut compose = Compose::new(&mut env);
+    compose.add_database(
+        &db_name,
+        &db_host,
+        components.db.as_ref().and_then(|db| db.expose_db_port),
+    );
+
+    let db_healthy = BTreeMap::from([(DB_SERVICE_NAME.to_string(), "service_healthy")]);
+    if let Some(minio) = minio {
+        compose.add(minio, BTreeMap::new(), vec![]);
+    }
+
+    let mut nginx_dependencies = BTreeMap::new();
+    for deployment in app_services {
+        let mut dependencies = db_healthy.clone();
+        if deployment.name == storage::STORAGE_NAME && bundles_minio {
+            dependencies.insert("minio".to_string(), "service_started");
+        }
+
+        let ports = match deployment.name.as_str() {
+            postgrest::REST_NAME => components
+                .rest
+                .as_ref()
+                .and_then(|rest| rest.expose_rest_port)
+                .map(|port| format!("{}:{}", port, postgrest::DEFAULT_REST_PORT))
+                .into_iter()
+                .collect(),
+            _ => vec![],
+        };
+
+        nginx_dependencies.insert(deployment.name.clone(), "service_started");
+        compose.add(deployment, dependencies, ports);
+    }
+
+    let nginx_host_port = components
+        .oidc
+        .as_ref()
+        .and_then(|oidc| oidc.expose_auth_port)
+        .or_else(|| components.ingress.as_ref().and_then(|ingress| ingress.port))
+        .unwrap_or(DEFAULT_NGINX_HOST_PORT);
+    compose.add(
+        nginx::service_deployment(),
+        nginx_dependencies,
+        vec![format!("{}:{}", nginx_host_port, nginx::NGINX_PORT)],
+    );
+
+    let rendered = compose.render(&app_name)?;
+
+    write_file(&compose_path, &rendered)?;
+    println!("📝 Wrote {}", compose_path.display());
+
+    let nginx_config_path = args
+        .output_dir
+        .join(nginx::NGINX_NAME)
+        .join(NGINX_CONFIG_FILE);
+    write_file(&nginx_config_path, &nginx_config)?;
+    println!("📝 Wrote {}", nginx_config_path.display());
+
+    let init_script_path = args.output_dir.join(DB_INIT_SCRIPT);
+    let (extensions, _) = database_extensions::extensions(components.db.as_ref())?;
+    write_file(&init_script_path, &db_init_script(&db_name, &extensions))?;
+    println!("📝 Wrote {}", init_script_path.display());
+
+    for (var, source) in env.missing() {
+        report.push(format!("{}: set {} in {}", source, var, ENV_FILE));
+    }
+    env.write(&env_path)?;
+    println!(
+        "🔐 Wrote secrets to {} (keep it out of version control)",
+        env_path.display()
+    );
+
+    if !report.is_empty() {
+        println!("⚠️ Needs review:");
+        for item in &report {
+            println!("   - {}", item);
+        }
+    }
+
+    println!(
+        "✅ Start it with `docker compose up` and open http://localhost:{}",
+        nginx_host_port
+    );
+
+    Ok(())
+}
+
+/// The initdb SQL from `database::deploy`, run by the postgres image entrypoint.
+///
+/// CloudNativePG creates the owner role from the `db-owner` secret before running
+/// `postInitSQL` in the `postgres` database and `postInitApplicationSQL` in the app
+/// database; the script does the same. Passwords come from the container environment.
+fn db_init_script(db_name: &str, extensions: &[String]) -> String {
+    let mut superuser_sql = vec![
+        format!(
+            "CREATE ROLE \"db-owner\" LOGIN PASSWORD '${{{}}}'",
+            env_key("db-owner", "password")
+        ),
+        format!("ALTER DATABASE \"{}\" OWNER TO \"db-owner\"", db_name),
+    ];
+    superuser_sql.extend(database::post_init_sql(
+        &format!("${{{}}}", APPLICATION_PASSWORD_VAR),
+        &format!("${{{}}}", READONLY_PASSWORD_VAR),
+        &format!("${{{}}}", env_key("db-authenticator", "password")),
+    ));
+
+    format!(
+        "#!/bin/sh\n\
+         # Generated by `stack export compose`; mirrors the CloudNativePG bootstrap.\n\
+         psql -v ON_ERROR_STOP=1 --username \"$POSTGRES_USER\" --dbname postgres <<SQL\n\
+         {};\n\
+         SQL\n\
+         psql -v ON_ERROR_STOP=1 --username \"$POSTGRES_USER\" --dbname \"$POSTGRES_DB\" <<SQL\n\
+         {};\n\
+         SQL\n",
+        superuser_sql.join(";\n"),
+        // The heredoc expands variables, so the DO block's `$$` is escaped.
+        database::post_init_application_sql(extensions)
+            .join(";\n")
+            .replace('$', "\\$")
+    )
+}
+
+/// Translates operator `ServiceDeployment`s into compose services.
+struct Compose<'a> {
+    env: &'a mut EnvFile,
+    services: Map<String, Value>,
+    volumes: BTreeSet<String>,
+}
+
+impl<'a> Compose<'a> {
+    fn new(env: &'a mut EnvFile) -> Self {
+        Compose {
+            env,
+            services: Map::new(),
+            volumes: BTreeSet::new(),
+        }
+    }
+
+    fn add_database(&mut self, db_name: &str, db_host: &str, host_port: Option<u16>) {
+        let volume = format!("{}-data", DB_SERVICE_NAME);
+        self.volumes.insert(volume.clone());
+
+        let mut environment = Map::new();
+        environment.insert("POSTGRES_USER".to_string(), json!("postgres"));
+        environment.insert("POSTGRES_DB".to_string(), json!(db_name));
+        for var in [
+            POSTGRES_PASSWORD_VAR.to_string(),
+            APPLICATION_PASSWORD_VAR.to_string(),
+            READONLY_PASSWORD_VAR.to_string(),
+            env_key("db-owner", "password"),
+            env_key("db-authenticator", "password"),
+        ] {
+            environment.insert(var.clone(), json!(self.env.reference(&var, &var)));
+        }
+
+        let mut service = json!({
+            "image": DB_IMAGE,
+            "restart": "unless-stopped",
+            "environment": environment,
+            "volumes": [
+                format!("{}:/var/lib/postgresql/data", volume),
+                format!("./{}:/docker-entrypoint-initdb.d/10-stack.sh:ro", DB_INIT_SCRIPT)
+            ],
+            "healthcheck": {
+                "test": [
+                    "CMD-SHELL",
+                    format!("pg_isready -h 127.0.0.1 -U postgres -d {}", db_name)
+                ],
+                "interval": "5s",
+                "timeout": "5s",
+                "retries": 20
+            },
+            // Keep the operator's service name so every generated URL resolves unchanged.
+            "networks": {
+                "default": {
+                    "aliases": [db_host]
+                }
+            }
+        });
+        if let Some(port) = host_port {
+            service["ports"] = json!([format!("{}:{}", port, DB_PORT)]);
+        }
+
+        self.services.insert(DB_SERVICE_NAME.to_string(), service);
+    }
+
+    /// Adds a deployment, running its init containers as one-shot services that must
+    /// finish, in order, before it starts.
+    fn add(
+        &mut self,
+        deployment: ServiceDeployment,
+        dependencies: BTreeMap<String, &'static str>,
+        ports: Vec<String>,
+    ) {
+        let mut main_dependencies = dependencies.clone();
+        let mut previous_init: Option<String> = None;
+
+        for (index, init_container) in deployment.init_containers.into_iter().enumerate() {
+            let init_name = format!("{}-init-{}", deployment.name, index + 1);
+            let mut init_dependencies = dependencies.clone();
+            if let Some(previous) = previous_init.take() {
+                init_dependencies.insert(previous, "service_completed_successfully");
+            }
+
+            let mut service = json!({
+                "image": init_container.image_name,
+                "restart": "no",
+            });
+            self.set_environment(&mut service, &init_container.env);
+            if let Some(command) = init_container.command {
+                set_command(&mut service, command);
+            }
+            set_dependencies(&mut service, init_dependencies);
+
+            self.services.insert(init_name.clone(), service);
+            previous_init = Some(init_name);
+        }
+
+        if let Some(previous) = previous_init {
+            main_dependencies.insert(previous, "service_completed_successfully");
+        }
+
+        let mut service = json!({
+            "image": deployment.image_name,
+            "restart": "unless-stopped",
+        });
+        self.set_environment(&mut service, &deployment.env);
+        if let Some(command) = deployment.command {
+            set_command(&mut service, command);
+        }
+        if !ports.is_empty() {
+            service["ports"] = json!(ports);
+        }
+
+        let mounts = self.volume_mounts(
+            &deployment.name,
+            &deployment.volume_mounts,
+            &deployment.volumes,
+        );
+        if !mounts.is_empty() {
+            service["volumes"] = json!(mounts);
+        }
+        set_dependencies(&mut service, main_dependencies);
+
+        self.services.insert(deployment.name, service);
+    }
+
+    /// Plain values are copied (with `$` escaped) and secret references become
+    /// `${SECRET_KEY}` interpolations resolved from `.env`.
+    fn set_environment(&mut self, service: &mut Value, env: &[Value]) {
+        let mut environment = Map::new();
+
+        for var in env {
+            let Some(name) = var.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+
+            let value = if let Some(value) = var.get("value") {
+                expand_references(value.as_str().unwrap_or_default(), &environment)
+            } else if let Some(secret_ref) = var.pointer("/valueFrom/secretKeyRef") {
+                let secret_name = secret_ref["name"].as_str().unwrap_or_default();
+                let key = secret_ref["key"].as_str().unwrap_or_default();
+                self.env.reference(
+                    &env_key(secret_name, key),
+                    &format!("secret `{}` key `{}`", secret_name, key),
+                )
+            } else {
+                continue;
+            };
+
+            environment.insert(name.to_string(), json!(value));
+        }
+
+        if !environment.is_empty() {
+            service["environment"] = Value::Object(environment);
+        }
+    }
+
+    fn volume_mounts(&mut self, service: &str, mounts: &[Value], volumes: &[Value]) -> Vec<String> {
+        let mut translated = Vec::new();
+
+        for mount in mounts {
+            let (Some(name), Some(path)) = (
+                mount.get("name").and_then(Value::as_str),
+                mount.get("mountPath").and_then(Value::as_str),
+            ) else {
+                continue;
+            };
+            let Some(volume) = volumes
+                .iter()
+                .find(|volume| volume.get("name").and_then(Value::as_str) == Some(name))
+            else {
+                continue;
+            };
+
+            if let Some(claim) = volume
+                .pointer("/persistentVolumeClaim/claimName")
+                .and_then(Value::as_str)
+            {
+                self.volumes.insert(claim.to_string());
+                translated.push(format!("{}:{}", claim, path));
+            } else if let Some(config_map) =
+                volume.pointer("/configMap/name").and_then(Value::as_str)
+            {
+                translated.push(format!("./{}:{}:ro", config_map, path));
+            } else if volume.get("emptyDir").is_some() {
+                let named = format!("{}-{}", service, name);
+                self.volumes.insert(named.clone());
+                translated.push(format!("{}:{}", named, path));
+            }
+        }
+
+        translated
+    }
+
+    fn render(self, app_name: &str) -> Result<String> {
+        let volumes: Map<String, Value> = self
+            .volumes
+            .into_iter()
+            .map(|volume| (volume, json!({})))
+            .collect();
+
+        let compose = json!({
+            "name": app_name,
+            "services": self.services,
+            "volumes": volumes,
+        });
+
+        Ok(format!(
+            "# Generated by `stack export compose`; secrets are read from {}.\n{}",
+            ENV_FILE,
+            serde_yaml::to_string(&compose)?
+        ))
+    }
+}
+
+fn set_command(service: &mut Value, command: Command) {
+    // Kubernetes `command` replaces the image entrypoint and `args` its CMD.
+    if !command.command.is_empty() {
+        service["entrypoint"] = json!(command
+            .command
+            .iter()
+            .map(String::as_str)
+            .map(escape)
+            .collect::<Vec<_>>());
+    }
+    if !command.args.is_empty() {
+        service["command"] = json!(command
+            .args
+            .iter()
+            .map(String::as_str)
+            .map(escape)
+            .collect::<Vec<_>>());
+    }
+}
+
+fn set_dependencies(service: &mut Value, dependencies: BTreeMap<String, &'static str>) {
+    if dependencies.is_empty() {
+        return;
+    }
+
+    let depends_on: Map<String, Value> = dependencies
+        .into_iter()
+        .map(|(name, condition)| (name, json!({ "condition": condition })))
+        .collect();
+    service["depends_on"] = Value::Object(depends_on);
+}
+
+/// Compose interpolates `$` everywhere, so literal values need it doubled.
+fn escape(value: &str) -> String {
+    value.replace('$', "$$")
+}
+
+/// Kubernetes expands `$(NAME)` from variables defined earlier in the list; compose
+/// has no such thing, so the earlier value is substituted in directly.
+fn expand_references(value: &str, environment: &Map<String, Value>) -> String {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("$(") {
+        let Some(end) = rest[start..].find(')') else {
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+        expanded.push_str(&escape(&rest[..start]));
+        match environment.get(name).and_then(Value::as_str) {
+            Some(resolved) => expanded.push_str(resolved),
+            None => expanded.push_str(&escape(&rest[start..=start + end])),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(&escape(rest));
+    expanded
+}
+
+/// `.env` variable holding `key` of the Kubernetes secret `secret_name`.
+fn env_key(secret_name: &str, key: &str) -> String {
+    format!("{}_{}", secret_name, key)
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() {
+                ch.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Values for `.env`. Entries already in the file are kept as they are and only new
+/// variables are appended.
+struct EnvFile {
+    existing: BTreeMap<String, String>,
+    generated: BTreeMap<String, String>,
+    referenced: BTreeMap<String, String>,
+}
+
+impl EnvFile {
+    fn read(path: &Path) -> Result<Self> {
+        let mut existing = BTreeMap::new();
+        if path.exists() {
+            let raw = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            for line in raw.lines() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                if let Some((key, value)) = line.split_once('=') {
+                    let value = value.trim();
+                    let value = value
+                        .strip_prefix('\'')
+                        .and_then(|value| value.strip_suffix('\''))
+                        .unwrap_or(value);
+                    existing.insert(key.trim().to_string(), value.to_string());
+                }
+            }
+        }
+
+        Ok(EnvFile {
+            existing,
+            generated: BTreeMap::new(),
+            referenced: BTreeMap::new(),
+        })
+    }
+
+    fn existing(&self, var: &str) -> Option<String> {
+        self.existing.get(var).cloned()
+    }
+
+    fn set(&mut self, var: &str, value: String) {
+        self.generated.insert(var.to_string(), value);
+    }
+
+    fn set_secret(&mut self, secret_name: &str, key: &str, value: String) {
+        self.set(&env_key(secret_name, key), value);
+    }
+
+    /// Records that the compose file reads `var` and returns its interpolation.
+    fn reference(&mut self, var: &str, source: &str) -> String {
+        self.referenced.insert(var.to_string(), source.to_string());
+        format!("${{{}}}", var)
+    }
+
+    /// Referenced variables with no value, paired with where they come from.
+    fn missing(&self) -> Vec<(String, String)> {
+        self.referenced
+            .iter()
+            .filter(|(var, _)| {
+                !self.existing.contains_key(*var) && !self.generated.contains_key(*var)
+            })
+            .map(|(var, source)| (var.clone(), source.clone()))
+            .collect()
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        let mut appended = String::new();
+        for (var, value) in &self.generated {
+            if !self.existing.contains_key(var) {
+                appended.push_str(&format!("{}={}\n", var, quote(value)));
+            }
+        }
+        for (var, _) in self.missing() {
+            appended.push_str(&format!("{}=\n", var));
+        }
+
+        let mut contents = fs::read_to_string(path).unwrap_or_default();
+        if contents.is_empty() {
+            contents.push_str("# Generated by `stack export compose`.\n");
+        } else if !appended.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.push_str(&appended);
+
+        write_file(path, &contents)
+    }
+}
+
+/// Single quotes keep compose from interpolating values it should take literally.
+fn quote(value: &str) -> String {
+    if value
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || "-_.:/@?=&".contains(ch))
+    {
+        value.to_string()
+    } else {
+        format!("'{}'", value)
+    }
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
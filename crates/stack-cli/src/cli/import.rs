@@ -0,0 +1,594 @@
+use crate::cli::new::to_pruned_value;
+use crate::operator::crd::{
+    Components, EnvVar, MailhogConfig, SeleniumConfig, ServiceSpec, ServiceVolume, Services,
+    StackAppSpec, StorageConfig,
+};
+use crate::operator::rules;
+use anyhow::{anyhow, bail, Context, Result};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Compose keys that are translated into the StackApp service.
+const HANDLED_KEYS: [&str; 9] = [
+    "image",
+    "build",
+    "environment",
+    "ports",
+    "expose",
+    "command",
+    "entrypoint",
+    "volumes",
+    "container_name",
+];
+
+/// Compose services that map onto a bundled platform component.
+enum Recognised {
+    Postgres,
+    Minio,
+    Mailhog,
+    Selenium,
+}
+
+fn recognise(image: &str) -> Option<Recognised> {
+    let repository = image
+        .rsplit_once('@')
+        .map_or(image, |(repository, _)| repository);
+    let repository = match repository.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => name,
+        _ => repository,
+    };
+    let segments: Vec<&str> = repository.split('/').collect();
+    let last = segments.last().copied().unwrap_or_default();
+
+    match last {
+        "postgres" | "postgresql" | "postgis" | "pgvector" | "timescaledb" | "timescaledb-ha" => {
+            Some(Recognised::Postgres)
+        }
+        "minio" => Some(Recognised::Minio),
+        "mailhog" => Some(Recognised::Mailhog),
+        _ if segments.contains(&"selenium") => Some(Recognised::Selenium),
+        _ => None,
+    }
+}
+
+pub fn compose(args: &crate::cli::ImportComposeArgs) -> Result<()> {
+    let raw = fs::read_to_string(&args.file)
+        .with_context(|| format!("Failed to read {}", args.file.display()))?;
+    let doc: Value = serde_yaml::from_str(&raw)
+        .with_context(|| format!("Failed to parse {}", args.file.display()))?;
+
+    let services = doc
+        .get("services")
+        .and_then(Value::as_mapping)
+        .ok_or_else(|| anyhow!("{} has no services section", args.file.display()))?;
+
+    let name = args
+        .name
+        .clone()
+        .or_else(|| doc.get("name").and_then(Value::as_str).map(str::to_string))
+        .or_else(|| project_directory_name(&args.file))
+        .map(|name| dns_label(&name))
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| anyhow!("could not derive an app name; pass --name"))?;
+    let namespace = args.namespace.clone().unwrap_or_else(|| name.clone());
+
+    let mut report = Vec::new();
+    for key in doc.as_mapping().into_iter().flat_map(Mapping::keys) {
+        match key.as_str() {
+            Some("services" | "volumes" | "version" | "name") => {}
+            Some(other) => report.push(format!(
+                "top-level `{}` is not supported and was dropped",
+                other
+            )),
+            None => {}
+        }
+    }
+
+    let mut components = Components::default();
+    let mut translated: Vec<(String, bool, ServiceSpec)> = Vec::new();
+    let mut database_hosts: Vec<String> = Vec::new();
+
+    for (key, service) in services {
+        let compose_name = key
+            .as_str()
+            .ok_or_else(|| anyhow!("service names must be strings"))?;
+        let image = service.get("image").and_then(Value::as_str);
+
+        match image.and_then(recognise) {
+            Some(Recognised::Postgres) => {
+                components.db.get_or_insert_with(Default::default);
+                database_hosts.push(compose_name.to_string());
+                report.push(format!(
+                    "{}: replaced by components.db (CloudNativePG); roles and passwords are generated by the operator",
+                    compose_name
+                ));
+            }
+            Some(Recognised::Minio) => {
+                components.storage = Some(StorageConfig {
+                    install_minio: Some(true),
+                    ..Default::default()
+                });
+                report.push(format!(
+                    "{}: replaced by components.storage with the bundled MinIO; S3 credentials live in the `storage-s3` secret",
+                    compose_name
+                ));
+            }
+            Some(Recognised::Mailhog) => {
+                components.mailhog = Some(MailhogConfig::default());
+                if compose_name != crate::services::mailhog::MAILHOG_NAME {
+                    report.push(format!(
+                        "{}: replaced by components.mailhog, reachable as `{}` instead of `{}`",
+                        compose_name,
+                        crate::services::mailhog::MAILHOG_NAME,
+                        compose_name
+                    ));
+                }
+            }
+            Some(Recognised::Selenium) => {
+                components.selenium = Some(SeleniumConfig {
+                    image: image.map(str::to_string),
+                    ..Default::default()
+                });
+                if compose_name != crate::services::selenium::SELENIUM_NAME {
+                    report.push(format!(
+                        "{}: replaced by components.selenium, reachable as `{}` instead of `{}`",
+                        compose_name,
+                        crate::services::selenium::SELENIUM_NAME,
+                        compose_name
+                    ));
+                }
+            }
+            None => {
+                let has_build = service.get("build").is_some();
+                let spec = translate_service(compose_name, service, &mut report);
+                translated.push((compose_name.to_string(), has_build, spec));
+            }
+        }
+    }
+
+    for (compose_name, _, spec) in translated.iter_mut() {
+        rewrite_database_env(compose_name, spec, &database_hosts, &mut report);
+    }
+
+    let web_index = match args.web.as_deref() {
+        Some(web) => translated
+            .iter()
+            .position(|(name, _, _)| name == web)
+            .ok_or_else(|| anyhow!("--web {} is not an application service in the file", web))?,
+        None => translated
+            .iter()
+            .position(|(_, has_build, _)| *has_build)
+            .or_else(|| {
+                translated
+                    .iter()
+                    .position(|(_, _, spec)| spec.port.is_some())
+            })
+            .or_else(|| (!translated.is_empty()).then_some(0))
+            .ok_or_else(|| anyhow!("no application service found to use as services.web"))?,
+    };
+    let (web_name, _, web) = translated.remove(web_index);
+    if web.port.is_none() {
+        report.push(format!(
+            "{}: no container port found; set services.web.port before deploying",
+            web_name
+        ));
+    }
+
+    let reserved = rules::reserved_service_names(&name);
+    let mut extra = BTreeMap::new();
+    for (compose_name, _, spec) in translated {
+        let mut service_name = dns_label(&compose_name);
+        if reserved.contains(&service_name.as_str()) {
+            service_name = format!("{}-app", service_name);
+        }
+        // Names such as my_api and my-api end up as the same label.
+        if extra.contains_key(&service_name) {
+            let taken = service_name.clone();
+            service_name = (2..)
+                .map(|suffix| format!("{}-{}", taken, suffix))
+                .find(|candidate| !extra.contains_key(candidate))
+                .unwrap_or_default();
+            report.push(format!(
+                "{}: renamed to `{}` as another service already became `{}`",
+                compose_name, service_name, taken
+            ));
+        } else if service_name != compose_name {
+            report.push(format!(
+                "{}: renamed to `{}` (Kubernetes naming or a reserved Stack name)",
+                compose_name, service_name
+            ));
+        }
+        extra.insert(service_name, spec);
+    }
+
+    let spec = StackAppSpec {
+        services: Services { web, extra },
+        components,
+        migrations: None,
+    };
+
+    let mut metadata = Mapping::new();
+    metadata.insert("name".into(), name.clone().into());
+    metadata.insert("namespace".into(), namespace.into());
+    let mut manifest = Mapping::new();
+    manifest.insert("apiVersion".into(), "stack-cli.dev/v1".into());
+    manifest.insert("kind".into(), "StackApp".into());
+    manifest.insert("metadata".into(), Value::Mapping(metadata));
+    manifest.insert("spec".into(), to_pruned_value(&spec)?);
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("{}.stack.yaml", name)));
+    if output.exists() && !args.force {
+        bail!(
+            "{} already exists; pass --force to overwrite it",
+            output.display()
+        );
+    }
+
+    let mut rendered = format!(
+        "# Imported from {} by `stack import compose`.\n",
+        args.file.display()
+    );
+    if !report.is_empty() {
+        rendered.push_str("#\n# Review before deploying:\n");
+        for item in &report {
+            rendered.push_str(&format!("#   - {}\n", item));
+        }
+    }
+    rendered.push_str(&serde_yaml::to_string(&Value::Mapping(manifest))?);
+
+    fs::write(&output, rendered)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    println!("📝 Wrote {}", output.display());
+
+    if report.is_empty() {
+        println!("✅ Everything was translated");
+    } else {
+        println!("⚠️ Needs review:");
+        for item in &report {
+            println!("   - {}", item);
+        }
+    }
+
+    Ok(())
+}
+
+fn translate_service(name: &str, service: &Value, report: &mut Vec<String>) -> ServiceSpec {
+    let image = match service.get("image").and_then(Value::as_str) {
+        Some(image) => image.to_string(),
+        None => {
+            report.push(format!(
+                "{}: built from source; push an image and replace the placeholder",
+                name
+            ));
+            format!("ghcr.io/acme/{}:latest", dns_label(name))
+        }
+    };
+
+    if let Some(mapping) = service.as_mapping() {
+        for key in mapping.keys().filter_map(Value::as_str) {
+            if HANDLED_KEYS.contains(&key) {
+                continue;
+            }
+            let hint = match key {
+                "depends_on" => " (Kubernetes restarts pods until their dependencies are ready)",
+                "env_file" => " (copy the values into env or secret_env)",
+                "healthcheck" => " (probes are not configurable yet)",
+                _ => "",
+            };
+            report.push(format!("{}: `{}` was dropped{}", name, key, hint));
+        }
+    }
+
+    if service.get("build").is_some() && service.get("image").is_some() {
+        report.push(format!(
+            "{}: `build` was dropped; the image must be pushed to a registry",
+            name
+        ));
+    }
+
+    let env = translate_environment(name, service.get("environment"), report);
+    let port = translate_ports(name, service, report);
+    let command = service
+        .get("entrypoint")
+        .and_then(|entrypoint| translate_command(name, "entrypoint", entrypoint, report));
+    let args = service
+        .get("command")
+        .and_then(|command| translate_command(name, "command", command, report));
+    let volumes = translate_volumes(name, service.get("volumes"), report);
+
+    ServiceSpec {
+        image,
+        port,
+        env,
+        secret_env: vec![],
+        init: None,
+        database_url: None,
+        migrations_database_url: None,
+        readonly_database_url: None,
+        pooled_database_url: None,
+        jwt_secret: None,
+        anon_jwt: None,
+        service_role_jwt: None,
+        command,
+        args,
+        volumes,
+        database: None,
+    }
+}
+
+fn translate_environment(
+    service: &str,
+    environment: Option<&Value>,
+    report: &mut Vec<String>,
+) -> Vec<EnvVar> {
+    let mut env = Vec::new();
+    let mut push = |name: String, value: String| {
+        if value.contains("${") {
+            report.push(format!(
+                "{}: {} uses compose interpolation ({}); set the real value or move it to secret_env",
+                service, name, value
+            ));
+        }
+        env.push(EnvVar { name, value });
+    };
+
+    match environment {
+        Some(Value::Mapping(vars)) => {
+            for (key, value) in vars {
+                let Some(key) = key.as_str() else { continue };
+                push(key.to_string(), scalar_to_string(value));
+            }
+        }
+        Some(Value::Sequence(vars)) => {
+            for var in vars.iter().filter_map(Value::as_str) {
+                let (key, value) = var.split_once('=').unwrap_or((var, ""));
+                push(key.to_string(), value.to_string());
+            }
+        }
+        _ => {}
+    }
+
+    env
+}
+
+/// Moves env vars holding Postgres URLs into the `database-urls` mappings.
+fn rewrite_database_env(
+    service: &str,
+    spec: &mut ServiceSpec,
+    database_hosts: &[String],
+    report: &mut Vec<String>,
+) {
+    let mut kept = Vec::new();
+    for var in std::mem::take(&mut spec.env) {
+        let value = var.value.to_ascii_lowercase();
+        let is_postgres_url =
+            value.starts_with("postgres://") || value.starts_with("postgresql://");
+        let points_at_database = database_hosts.iter().any(|host| {
+            value.contains(&format!("@{}:", host)) || value.contains(&format!("@{}/", host))
+        });
+        let named_like_url = var.name.ends_with("DATABASE_URL") || var.name.ends_with("DB_URL");
+
+        if !((is_postgres_url || named_like_url) && (points_at_database || value.contains("${"))) {
+            kept.push(var);
+            continue;
+        }
+
+        let upper = var.name.to_ascii_uppercase();
+        let slot = if upper.contains("READ") {
+            &mut spec.readonly_database_url
+        } else if upper.contains("MIGRAT") || upper.contains("ADMIN") || upper.contains("OWNER") {
+            &mut spec.migrations_database_url
+        } else {
+            &mut spec.database_url
+        };
+
+        if slot.is_none() {
+            *slot = Some(var.name.clone());
+        } else {
+            report.push(format!(
+                "{}: {} also looks like a database URL but its mapping is taken; it was kept as a plain value",
+                service, var.name
+            ));
+            kept.push(var);
+        }
+    }
+    spec.env = kept;
+}
+
+fn translate_ports(name: &str, service: &Value, report: &mut Vec<String>) -> Option<u16> {
+    let mut container_ports = Vec::new();
+
+    for entry in service
+        .get("ports")
+        .and_then(Value::as_sequence)
+        .into_iter()
+        .flatten()
+    {
+        let (published, target) = match entry {
+            Value::Mapping(_) => (
+                entry.get("published").map(scalar_to_string),
+                entry.get("target").map(scalar_to_string),
+            ),
+            _ => {
+                let short = scalar_to_string(entry);
+                let short = short.split('/').next().unwrap_or_default().to_string();
+                match short.rsplit_once(':') {
+                    Some((host, target)) => (
+                        Some(host.rsplit(':').next().unwrap_or(host).to_string()),
+                        Some(target.to_string()),
+                    ),
+                    None => (None, Some(short)),
+                }
+            }
+        };
+
+        if let Some(published) = published {
+            report.push(format!(
+                "{}: host port {} was not carried over; expose it through a profile NodePort instead",
+                name, published
+            ));
+        }
+        if let Some(port) = target.and_then(|target| target.parse::<u16>().ok()) {
+            container_ports.push(port);
+        }
+    }
+
+    for entry in service
+        .get("expose")
+        .and_then(Value::as_sequence)
+        .into_iter()
+        .flatten()
+    {
+        if let Ok(port) = scalar_to_string(entry)
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .parse::<u16>()
+        {
+            container_ports.push(port);
+        }
+    }
+
+    // Keeps the first listed port first, as that is the one exposed.
+    let mut seen = BTreeSet::new();
+    container_ports.retain(|port| seen.insert(*port));
+    if container_ports.len() > 1 {
+        report.push(format!(
+            "{}: only container port {} is exposed; {:?} were dropped",
+            name,
+            container_ports[0],
+            &container_ports[1..]
+        ));
+    }
+
+    container_ports.first().copied()
+}
+
+fn translate_command(
+    name: &str,
+    key: &str,
+    value: &Value,
+    report: &mut Vec<String>,
+) -> Option<Vec<String>> {
+    match value {
+        Value::Sequence(parts) => Some(parts.iter().map(scalar_to_string).collect()),
+        Value::String(command) => {
+            if command.contains(['"', '\'', '\\']) {
+                report.push(format!(
+                    "{}: `{}` was split on whitespace; check its quoting",
+                    name, key
+                ));
+            }
+            Some(command.split_whitespace().map(str::to_string).collect())
+        }
+        _ => None,
+    }
+}
+
+fn translate_volumes(
+    name: &str,
+    volumes: Option<&Value>,
+    report: &mut Vec<String>,
+) -> Vec<ServiceVolume> {
+    let mut translated = Vec::new();
+
+    for entry in volumes.and_then(Value::as_sequence).into_iter().flatten() {
+        let (kind, source, target) = match entry {
+            Value::Mapping(_) => (
+                entry
+                    .get("type")
+                    .and_then(Value::as_str)
+                    .unwrap_or("volume")
+                    .to_string(),
+                entry
+                    .get("source")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                entry
+                    .get("target")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+            ),
+            _ => {
+                let short = scalar_to_string(entry);
+                let parts: Vec<&str> = short.split(':').collect();
+                match parts.as_slice() {
+                    [target] => ("volume".to_string(), None, target.to_string()),
+                    [source, target, ..] => {
+                        let kind = if source.starts_with(['.', '/', '~', '$']) {
+                            "bind"
+                        } else {
+                            "volume"
+                        };
+                        (
+                            kind.to_string(),
+                            Some(source.to_string()),
+                            target.to_string(),
+                        )
+                    }
+                    [] => continue,
+                }
+            }
+        };
+
+        if kind != "volume" || target.is_empty() {
+            report.push(format!(
+                "{}: {} mount {} -> {} was dropped; bake the files into the image or use a ConfigMap",
+                name,
+                kind,
+                source.unwrap_or_default(),
+                target
+            ));
+            continue;
+        }
+
+        let volume_name = source
+            .map(|source| dns_label(&source))
+            .unwrap_or_else(|| dns_label(target.rsplit('/').next().unwrap_or("data")));
+        translated.push(ServiceVolume {
+            name: volume_name,
+            mount_path: target,
+            size: None,
+        });
+    }
+
+    translated
+}
+
+fn scalar_to_string(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Number(number) => number.to_string(),
+        Value::Bool(flag) => flag.to_string(),
+        Value::Null => String::new(),
+        other => serde_yaml::to_string(other)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+fn project_directory_name(file: &Path) -> Option<String> {
+    fs::canonicalize(file)
+        .ok()?
+        .parent()?
+        .file_name()?
+        .to_str()
+        .map(str::to_string)
+}
+
+/// Lowercases and replaces anything Kubernetes rejects in a resource name with `-`.
+fn dns_label(name: &str) -> String {
+    let label: String = name
+        .to_ascii_lowercase()
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '-' })
+        .collect();
+    label.trim_matches('-').to_string()
+}
@@ -23,6 +23,7 @@ use kube_runtime::conditions;
 use kube_runtime::wait::await_condition;
 use kube_runtime::wait::Condition;
 use serde_json::json;
+use std::path::Path;
 
 const OPERATOR_IMAGE: &str = "ghcr.io/stack-cli/stack-operator";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -34,6 +35,10 @@ const KEYCLOAK_OPERATOR_YAML: &str = include_str!("../../keycloak/keycloak-opera
 const KEYCLOAK_DB_SIZE_GIB: i32 = 10;
 
 pub async fn init(initializer: &crate::cli::Initializer) -> Result<()> {
+    if initializer.vscode {
+        super::schema::write_vscode_settings(Path::new("."))?;
+    }
+
     println!("🔌 Connecting to the cluster...");
     let client = Client::try_default().await?;
     println!("✅ Connected");
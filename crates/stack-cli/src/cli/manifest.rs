@@ -27,7 +27,7 @@ pub fn load_stackapp(path: &Path, profile: Option<&str>) -> Result<(StackApp, St
     Ok((stack_app, merged_yaml))
 }
 
-fn apply_profile(doc: &mut Value, profile: Option<&str>) -> Result<()> {
+pub(crate) fn apply_profile(doc: &mut Value, profile: Option<&str>) -> Result<()> {
     let spec = doc
         .get_mut("spec")
         .and_then(Value::as_mapping_mut)
@@ -0,0 +1,93 @@
+use crate::operator::crd::StackApp;
+use crate::services::database_migrations;
+use anyhow::{anyhow, bail, Context, Result};
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::api::{Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `spec.migrations.sql_dir`, resolved against the directory of the manifest.
+pub fn sql_dir(manifest: &Path, stack_app: &StackApp) -> Option<PathBuf> {
+    let sql_dir = stack_app.spec.migrations.as_ref()?.sql_dir.as_ref()?;
+    let base = manifest.parent().unwrap_or_else(|| Path::new(""));
+    Some(base.join(sql_dir))
+}
+
+/// The migration files in `dir` by file name, which is also their order. A missing
+/// directory has none.
+pub fn sql_files(dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut files = BTreeMap::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("sql") {
+            continue;
+        }
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow!("{} is not valid UTF-8", path.display()))?
+            .to_string();
+        if !database_migrations::is_migration_file(&file_name) {
+            bail!(
+                "{} should be named like 0001_create_todos.sql: digits first, then letters, digits, `_`, `-` or `.`",
+                path.display()
+            );
+        }
+        let sql = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        files.insert(file_name, sql);
+    }
+    Ok(files)
+}
+
+/// Packages `spec.migrations.sql_dir` into the ConfigMap the operator's migrations Job
+/// mounts. Does nothing when the manifest has no sql_dir.
+pub async fn package(client: &Client, manifest: &Path, stack_app: &StackApp) -> Result<()> {
+    let Some(dir) = sql_dir(manifest, stack_app) else {
+        return Ok(());
+    };
+    if !dir.is_dir() {
+        bail!(
+            "spec.migrations.sql_dir {} is not a directory",
+            dir.display()
+        );
+    }
+    let files = sql_files(&dir)?;
+    let namespace = stack_app
+        .namespace()
+        .ok_or_else(|| anyhow!("StackApp manifest is missing metadata.namespace"))?;
+    let name = database_migrations::config_map_name(&stack_app.name_any());
+
+    let config_map = json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": {
+            "name": name,
+            "namespace": namespace
+        },
+        "data": files
+    });
+    let config_map_api: Api<ConfigMap> = Api::namespaced(client.clone(), &namespace);
+    config_map_api
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(config_map),
+        )
+        .await
+        .with_context(|| format!("Failed to package {} into {}", dir.display(), name))?;
+    println!(
+        "📜 Packaged {} migration{} from {}",
+        files.len(),
+        if files.len() == 1 { "" } else { "s" },
+        dir.display()
+    );
+    Ok(())
+}
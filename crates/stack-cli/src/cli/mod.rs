@@ -1,10 +1,17 @@
 pub mod apply;
 pub mod cloudflare;
+pub mod db;
 pub mod deploy;
+pub mod export;
+pub mod import;
 pub mod init;
 pub mod manifest;
+pub mod migrations;
+pub mod new;
+pub mod schema;
 pub mod secrets;
 pub mod status;
+pub mod validate;
 
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
@@ -27,6 +34,9 @@ pub struct Initializer {
     /// Skip installing the operator
     #[arg(long, default_value_t = false)]
     pub no_operator: bool,
+    /// Write .vscode/settings.json so *.stack.yaml files get StackApp autocompletion
+    #[arg(long, default_value_t = false)]
+    pub vscode: bool,
 }
 
 #[derive(Parser)]
@@ -91,6 +101,287 @@ pub struct CloudflareArgs {
     pub ingress_target: Option<String>,
 }
 
+#[derive(Parser)]
+pub struct ValidateArgs {
+    /// Path to the StackApp manifest to check (every profile is checked too)
+    #[arg(long)]
+    pub manifest: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct SchemaArgs {
+    /// File to write the JSON schema to (printed to stdout when omitted)
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+pub struct NewArgs {
+    /// Application name (also used as the namespace unless --namespace is set)
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Namespace to deploy the application into
+    #[arg(long)]
+    pub namespace: Option<String>,
+    /// Components to include, comma separated (prompted for in a terminal when omitted)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub components: Vec<new::Component>,
+    /// Framework preset for the web service
+    #[arg(long, value_enum)]
+    pub framework: Option<new::Framework>,
+    /// Container image for the web service
+    #[arg(long)]
+    pub image: Option<String>,
+    /// First NodePort handed out in the dev profile; staging starts 80 above it
+    #[arg(long, default_value_t = 30010)]
+    pub base_port: u16,
+    /// Where to write the manifest (defaults to <name>.stack.yaml)
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// Overwrite the output file if it already exists
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+}
+
+#[derive(Parser)]
+pub struct ImportArgs {
+    #[command(subcommand)]
+    pub source: ImportSource,
+}
+
+#[derive(Subcommand)]
+pub enum ImportSource {
+    /// Translate a docker-compose file into a StackApp manifest
+    Compose(ImportComposeArgs),
+}
+
+#[derive(Parser)]
+pub struct ImportComposeArgs {
+    /// Path to the docker-compose file
+    pub file: PathBuf,
+    /// Application name (defaults to the compose project name or its directory)
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Namespace to deploy the application into
+    #[arg(long)]
+    pub namespace: Option<String>,
+    /// Compose service to use as services.web (defaults to the first one with a build section)
+    #[arg(long)]
+    pub web: Option<String>,
+    /// Where to write the manifest (defaults to <name>.stack.yaml)
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// Overwrite the output file if it already exists
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+}
+
+#[derive(Parser)]
+pub struct ExportArgs {
+    #[command(subcommand)]
+    pub target: ExportTarget,
+}
+
+#[derive(Subcommand)]
+pub enum ExportTarget {
+    /// Write a docker-compose.yml (plus .env) that runs the app without Kubernetes
+    Compose(ExportComposeArgs),
+}
+
+#[derive(Parser)]
+pub struct ExportComposeArgs {
+    /// Path to the StackApp manifest to export
+    #[arg(long)]
+    pub manifest: PathBuf,
+    /// Optional profile name to merge from spec.profiles
+    #[arg(long)]
+    pub profile: Option<String>,
+    /// Directory to write docker-compose.yml, .env and supporting files into
+    #[arg(long, default_value = ".")]
+    pub output_dir: PathBuf,
+    /// Overwrite an existing docker-compose.yml (.env values are always kept)
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+}
+
+#[derive(Parser)]
+pub struct DbArgs {
+    #[command(subcommand)]
+    pub command: DbCommand,
+}
+
+#[derive(Subcommand)]
+pub enum DbCommand {
+    /// Take an on-demand base backup of the application database
+    Backup(DbBackupArgs),
+    /// Inspect existing database backups
+    Backups(DbBackupsArgs),
+    /// Restore the application database from a backup or to a point in time
+    Restore(DbRestoreArgs),
+    /// Set new passwords for the database roles and restart the services using them
+    RotatePasswords(DbRotatePasswordsArgs),
+    /// Replace an app's database with a copy of another app's, e.g. production into staging
+    Clone(DbCloneArgs),
+    /// Open psql against the database through a port-forward to the primary
+    Psql(DbPsqlArgs),
+    /// Write a pg_dump of the database to stdout through a port-forward to the primary
+    Dump(DbDumpArgs),
+    /// Forward a local port to the database primary and print the URL to connect with
+    Forward(DbForwardArgs),
+    /// Inspect and create SQL migrations from spec.migrations
+    Migrate(DbMigrateArgs),
+    /// Show the slowest queries, connections per role and tables with the most dead rows
+    Top(DbTopArgs),
+}
+
+#[derive(Parser)]
+pub struct DbManifestArgs {
+    /// Path to the StackApp manifest
+    #[arg(long)]
+    pub manifest: PathBuf,
+    /// Optional profile name to merge from spec.profiles
+    #[arg(long)]
+    pub profile: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct DbBackupArgs {
+    #[command(flatten)]
+    pub target: DbManifestArgs,
+    /// Wait until the backup has completed
+    #[arg(long, default_value_t = false)]
+    pub wait: bool,
+}
+
+#[derive(Parser)]
+pub struct DbBackupsArgs {
+    #[command(subcommand)]
+    pub command: DbBackupsCommand,
+}
+
+#[derive(Subcommand)]
+pub enum DbBackupsCommand {
+    /// List backups of the application database, oldest first
+    List(DbManifestArgs),
+}
+
+#[derive(Parser)]
+#[command(group(clap::ArgGroup::new("point").required(true).args(["backup", "to"])))]
+pub struct DbRestoreArgs {
+    #[command(flatten)]
+    pub target: DbManifestArgs,
+    /// Recover this backup (see `stack db backups list`) into a new cluster, switch the app to it and delete the old one
+    #[arg(long)]
+    pub backup: Option<String>,
+    /// Replay WAL up to this RFC 3339 time into a new cluster and switch the app to it
+    #[arg(long)]
+    pub to: Option<String>,
+    /// Skip the confirmation prompt
+    #[arg(long, default_value_t = false)]
+    pub yes: bool,
+}
+
+#[derive(Parser)]
+pub struct DbRotatePasswordsArgs {
+    #[command(flatten)]
+    pub target: DbManifestArgs,
+    /// Role to rotate; repeat for several. Defaults to every role
+    #[arg(long = "role", value_parser = ["application_user", "application_readonly", "authenticator", "db-owner"])]
+    pub roles: Vec<String>,
+    /// Skip the confirmation prompt
+    #[arg(long, default_value_t = false)]
+    pub yes: bool,
+}
+
+#[derive(Parser)]
+pub struct DbCloneArgs {
+    /// Manifest of the StackApp to copy from
+    #[arg(long)]
+    pub from_manifest: PathBuf,
+    /// Optional profile of the source manifest
+    #[arg(long)]
+    pub from_profile: Option<String>,
+    /// Manifest of the StackApp whose database is replaced
+    #[arg(long)]
+    pub to_manifest: PathBuf,
+    /// Optional profile of the target manifest
+    #[arg(long)]
+    pub to_profile: Option<String>,
+    /// SQL file run against the copy afterwards, e.g. to mask personal data
+    #[arg(long)]
+    pub mask_sql: Option<PathBuf>,
+    /// Skip the confirmation prompt
+    #[arg(long, default_value_t = false)]
+    pub yes: bool,
+}
+
+#[derive(Parser)]
+pub struct DbPsqlArgs {
+    #[command(flatten)]
+    pub target: DbManifestArgs,
+    /// Role to connect as
+    #[arg(long, value_enum, default_value_t = db::DbRole::App)]
+    pub role: db::DbRole,
+    /// Extra arguments passed to psql after `--`
+    #[arg(last = true)]
+    pub psql_args: Vec<String>,
+}
+
+#[derive(Parser)]
+pub struct DbDumpArgs {
+    #[command(flatten)]
+    pub target: DbManifestArgs,
+    /// Role to dump as
+    #[arg(long, value_enum, default_value_t = db::DbRole::Owner)]
+    pub role: db::DbRole,
+    /// Extra arguments passed to pg_dump after `--`
+    #[arg(last = true)]
+    pub pg_dump_args: Vec<String>,
+}
+
+#[derive(Parser)]
+pub struct DbForwardArgs {
+    #[command(flatten)]
+    pub target: DbManifestArgs,
+    /// Role whose URL is printed
+    #[arg(long, value_enum, default_value_t = db::DbRole::App)]
+    pub role: db::DbRole,
+    /// Local port to listen on (a free one is picked when omitted)
+    #[arg(long)]
+    pub port: Option<u16>,
+}
+
+#[derive(Parser)]
+pub struct DbMigrateArgs {
+    #[command(subcommand)]
+    pub command: DbMigrateCommand,
+}
+
+#[derive(Subcommand)]
+pub enum DbMigrateCommand {
+    /// List migrations and whether they have been applied
+    Status(DbManifestArgs),
+    /// Create the next numbered SQL file in spec.migrations.sql_dir
+    New(DbMigrateNewArgs),
+}
+
+#[derive(Parser)]
+pub struct DbMigrateNewArgs {
+    #[command(flatten)]
+    pub target: DbManifestArgs,
+    /// Short description, used in the file name (e.g. create_todos)
+    pub name: String,
+}
+
+#[derive(Parser)]
+pub struct DbTopArgs {
+    #[command(flatten)]
+    pub target: DbManifestArgs,
+    /// Rows to show per section
+    #[arg(long, default_value_t = 10)]
+    pub limit: u32,
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Deploy an application into Kubernetes
@@ -105,4 +396,16 @@ pub enum Commands {
     Secrets(SecretsArgs),
     /// Create Cloudflare tunnel secret and deploy cloudflared
     Cloudflare(CloudflareArgs),
+    /// Check a StackApp manifest and its profiles against the schema
+    Validate(ValidateArgs),
+    /// Export the StackApp manifest JSON schema for editor autocompletion
+    Schema(SchemaArgs),
+    /// Scaffold a new StackApp manifest
+    New(NewArgs),
+    /// Convert an existing project description into a StackApp manifest
+    Import(ImportArgs),
+    /// Convert a StackApp manifest into another deployment format
+    Export(ExportArgs),
+    /// Work with the application database: backups and restores, clones, migrations, password rotation, psql, dump, forward and top
+    Db(DbArgs),
 }
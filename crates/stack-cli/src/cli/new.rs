@@ -0,0 +1,461 @@
+use crate::cli::schema::spec_schema;
+use crate::operator::crd::{
+    Components, DbConfig, DbDeletionPolicy, DocumentEngineConfig, IngressConfig, MailhogConfig,
+    OidcConfig, RealtimeConfig, RestConfig, SeleniumConfig, ServiceSpec, StorageConfig,
+    SupabaseAuthConfig,
+};
+use crate::operator::rules;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+
+const DEV_PASSWORD: &str = "testpassword";
+/// Staging NodePorts start this far above the dev ones so both profiles can share a cluster.
+const STAGING_PORT_OFFSET: u16 = 80;
+
+/// Platform components `stack new` can add to the manifest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Component {
+    Db,
+    Auth,
+    Rest,
+    Realtime,
+    Storage,
+    Oidc,
+    Mailhog,
+    Selenium,
+    #[value(name = "document_engine")]
+    DocumentEngine,
+}
+
+const DEFAULT_COMPONENTS: [Component; 3] = [Component::Db, Component::Rest, Component::Auth];
+
+/// Framework presets for the web service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Framework {
+    Nextjs,
+    Flask,
+    Django,
+    Rails,
+    Express,
+    Generic,
+}
+
+struct Preset {
+    port: u16,
+    database_url: &'static str,
+    /// Browser-visible env var that receives the anon JWT, for frameworks that bundle one.
+    anon_jwt: Option<&'static str>,
+}
+
+fn preset(framework: Framework) -> Preset {
+    match framework {
+        Framework::Nextjs => Preset {
+            port: 3000,
+            database_url: "DATABASE_URL",
+            anon_jwt: Some("NEXT_PUBLIC_SUPABASE_ANON_KEY"),
+        },
+        Framework::Flask => Preset {
+            port: 5000,
+            database_url: "DATABASE_URL",
+            anon_jwt: None,
+        },
+        Framework::Django => Preset {
+            port: 8000,
+            database_url: "DATABASE_URL",
+            anon_jwt: None,
+        },
+        Framework::Rails | Framework::Express => Preset {
+            port: 3000,
+            database_url: "DATABASE_URL",
+            anon_jwt: None,
+        },
+        Framework::Generic => Preset {
+            port: 8080,
+            database_url: "DATABASE_URL",
+            anon_jwt: None,
+        },
+    }
+}
+
+pub fn new(args: &crate::cli::NewArgs) -> Result<()> {
+    let interactive = args.components.is_empty() && io::stdin().is_terminal();
+
+    let name = match (&args.name, interactive) {
+        (Some(name), _) => name.clone(),
+        (None, true) => prompt("App name", "my-app")?,
+        (None, false) => bail!("--name is required when not running interactively"),
+    };
+    let namespace = args.namespace.clone().unwrap_or_else(|| name.clone());
+
+    let framework = match (args.framework, interactive) {
+        (Some(framework), _) => framework,
+        (None, true) => {
+            let answer = prompt(
+                "Framework (nextjs, flask, django, rails, express, generic)",
+                "generic",
+            )?;
+            Framework::from_str(&answer, true).map_err(|err| anyhow!(err))?
+        }
+        (None, false) => Framework::Generic,
+    };
+
+    let components: BTreeSet<Component> = if !args.components.is_empty() {
+        args.components.iter().copied().collect()
+    } else if interactive {
+        let mut selected = BTreeSet::new();
+        for component in Component::value_variants() {
+            let name = component_key(*component);
+            if confirm(
+                &format!("Include {}?", name),
+                DEFAULT_COMPONENTS.contains(component),
+            )? {
+                selected.insert(*component);
+            }
+        }
+        selected
+    } else {
+        DEFAULT_COMPONENTS.into_iter().collect()
+    };
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("{}.stack.yaml", name)));
+    if output.exists() && !args.force {
+        bail!(
+            "{} already exists; pass --force to overwrite it",
+            output.display()
+        );
+    }
+
+    let image = args
+        .image
+        .clone()
+        .unwrap_or_else(|| format!("ghcr.io/acme/{}:latest", name));
+    let manifest = render_manifest(
+        &name,
+        &namespace,
+        &image,
+        framework,
+        &components,
+        args.base_port,
+    )?;
+
+    fs::write(&output, manifest)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    println!("📝 Wrote {}", output.display());
+    println!(
+        "   Next: stack validate --manifest {0} && stack deploy --manifest {0} --profile dev",
+        output.display()
+    );
+
+    Ok(())
+}
+
+fn component_key(component: Component) -> &'static str {
+    match component {
+        Component::Db => "db",
+        Component::Auth => "auth",
+        Component::Rest => "rest",
+        Component::Realtime => "realtime",
+        Component::Storage => "storage",
+        Component::Oidc => "oidc",
+        Component::Mailhog => "mailhog",
+        Component::Selenium => "selenium",
+        Component::DocumentEngine => "document_engine",
+    }
+}
+
+/// Hands out NodePorts that are unique across every profile in the manifest.
+struct NodePorts {
+    next: u16,
+    used: BTreeSet<u16>,
+}
+
+impl NodePorts {
+    fn take(&mut self) -> Result<u16> {
+        while self.used.contains(&self.next) {
+            self.next += 1;
+        }
+        let port = self.next;
+        if !rules::NODEPORT_RANGE.contains(&port) {
+            bail!(
+                "NodePort {} is outside the allowed range {}-{}; choose a different --base-port",
+                port,
+                rules::NODEPORT_RANGE.start(),
+                rules::NODEPORT_RANGE.end()
+            );
+        }
+        self.used.insert(port);
+        self.next += 1;
+        Ok(port)
+    }
+
+    fn starting_at(&mut self, next: u16) -> &mut Self {
+        self.next = next;
+        self
+    }
+}
+
+fn render_manifest(
+    name: &str,
+    namespace: &str,
+    image: &str,
+    framework: Framework,
+    components: &BTreeSet<Component>,
+    base_port: u16,
+) -> Result<String> {
+    let has = |component: Component| components.contains(&component);
+    let public_url = format!("https://{}.example.com", name);
+
+    // Every field is listed so a new component fails to compile here until it is scaffolded.
+    let base = Components {
+        db: has(Component::Db).then(DbConfig::default),
+        oidc: has(Component::Oidc).then(|| OidcConfig {
+            hostname_url: Some(public_url.clone()),
+            ..Default::default()
+        }),
+        auth: has(Component::Auth).then(|| SupabaseAuthConfig {
+            api_external_url: format!("{}/auth", public_url),
+            site_url: public_url.clone(),
+            confirm_email: Some(true),
+        }),
+        storage: has(Component::Storage).then(|| StorageConfig {
+            install_minio: Some(true),
+            max_upload_size: Some("50mb".to_string()),
+            ..Default::default()
+        }),
+        ingress: None,
+        realtime: has(Component::Realtime).then(RealtimeConfig::default),
+        rest: has(Component::Rest).then(RestConfig::default),
+        document_engine: has(Component::DocumentEngine).then(DocumentEngineConfig::default),
+        selenium: has(Component::Selenium).then(SeleniumConfig::default),
+        mailhog: has(Component::Mailhog).then(MailhogConfig::default),
+    };
+
+    let mut ports = NodePorts {
+        next: base_port,
+        used: BTreeSet::new(),
+    };
+    let dev_ingress_port = ports.take()?;
+    let dev_local_url = format!("http://localhost:{}", dev_ingress_port);
+    let dev = Components {
+        db: if has(Component::Db) {
+            Some(DbConfig {
+                expose_db_port: Some(ports.take()?),
+                danger_override_password: Some(DEV_PASSWORD.to_string()),
+                deletion_policy: Some(DbDeletionPolicy::Delete),
+                ..Default::default()
+            })
+        } else {
+            None
+        },
+        oidc: has(Component::Oidc).then(|| OidcConfig {
+            hostname_url: Some(dev_local_url.clone()),
+            expose_auth_port: Some(dev_ingress_port),
+        }),
+        auth: has(Component::Auth).then(|| SupabaseAuthConfig {
+            api_external_url: format!("{}/auth", dev_local_url),
+            site_url: dev_local_url.clone(),
+            confirm_email: Some(false),
+        }),
+        storage: None,
+        ingress: (!has(Component::Oidc)).then_some(IngressConfig {
+            port: Some(dev_ingress_port),
+        }),
+        realtime: None,
+        rest: if has(Component::Rest) {
+            Some(RestConfig {
+                expose_rest_port: Some(ports.take()?),
+                ..Default::default()
+            })
+        } else {
+            None
+        },
+        document_engine: None,
+        selenium: if has(Component::Selenium) {
+            Some(SeleniumConfig {
+                expose_webdriver_port: Some(ports.take()?),
+                expose_vnc_port: Some(ports.take()?),
+                ..Default::default()
+            })
+        } else {
+            None
+        },
+        mailhog: if has(Component::Mailhog) {
+            Some(MailhogConfig {
+                expose_web_port: Some(ports.take()?),
+                ..Default::default()
+            })
+        } else {
+            None
+        },
+    };
+
+    let staging_ingress_port = ports
+        .starting_at(base_port.saturating_add(STAGING_PORT_OFFSET))
+        .take()?;
+    let staging = Components {
+        oidc: has(Component::Oidc).then(|| OidcConfig {
+            expose_auth_port: Some(staging_ingress_port),
+            ..Default::default()
+        }),
+        ingress: (!has(Component::Oidc)).then_some(IngressConfig {
+            port: Some(staging_ingress_port),
+        }),
+        ..Default::default()
+    };
+
+    let preset = preset(framework);
+    let uses_gateway = has(Component::Auth)
+        || has(Component::Rest)
+        || has(Component::Storage)
+        || has(Component::Realtime);
+    let web = ServiceSpec {
+        image: image.to_string(),
+        port: Some(preset.port),
+        env: vec![],
+        secret_env: vec![],
+        init: None,
+        database_url: has(Component::Db).then(|| preset.database_url.to_string()),
+        migrations_database_url: None,
+        readonly_database_url: None,
+        pooled_database_url: None,
+        jwt_secret: None,
+        anon_jwt: preset.anon_jwt.filter(|_| uses_gateway).map(str::to_string),
+        service_role_jwt: None,
+        command: None,
+        args: None,
+        volumes: vec![],
+        database: None,
+    };
+
+    let schema = spec_schema();
+    let describe_component = |key: &str| {
+        schema["properties"]["components"]["properties"][key]["description"]
+            .as_str()
+            .map(str::to_string)
+    };
+
+    let mut manifest = String::new();
+    manifest.push_str(&format!(
+        "# Generated by `stack new`. Check it with `stack validate --manifest <file>`.\n\
+         apiVersion: stack-cli.dev/v1\n\
+         kind: StackApp\n\
+         metadata:\n  name: {name}\n  namespace: {namespace}\n\
+         spec:\n"
+    ));
+
+    manifest.push_str("  components:\n");
+    manifest.push_str(&render_entries(&base, 4, describe_component)?);
+
+    manifest.push_str("  profiles:\n");
+    manifest.push_str(
+        "    # Local development: NodePorts for a single-node cluster, a fixed database password and a database deleted with the app.\n",
+    );
+    manifest.push_str("    dev:\n      components:\n");
+    manifest.push_str(&render_entries(&dev, 8, |_| None)?);
+    manifest.push_str(
+        "    # Staging: a single NodePort into nginx, on a port that doesn't clash with dev.\n",
+    );
+    manifest.push_str("    staging:\n      components:\n");
+    manifest.push_str(&render_entries(&staging, 8, |_| None)?);
+
+    manifest.push_str("  services:\n");
+    let framework_name = framework
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default();
+    manifest.push_str(&format!(
+        "    # {} preset: replace the image with your own build.\n",
+        framework_name
+    ));
+    let mut services = Mapping::new();
+    services.insert(Value::String("web".to_string()), to_pruned_value(&web)?);
+    manifest.push_str(&render_entries(&services, 4, |_| None)?);
+
+    Ok(manifest)
+}
+
+/// Renders each top-level key of `value` as YAML, indented and preceded by its description.
+fn render_entries<T: Serialize>(
+    value: &T,
+    indent: usize,
+    describe: impl Fn(&str) -> Option<String>,
+) -> Result<String> {
+    let Value::Mapping(entries) = to_pruned_value(value)? else {
+        return Err(anyhow!("expected a YAML mapping"));
+    };
+    if entries.is_empty() {
+        return Ok(format!("{}{{}}\n", " ".repeat(indent)));
+    }
+
+    let padding = " ".repeat(indent);
+    let mut rendered = String::new();
+    for (key, value) in entries {
+        if let Some(description) = key.as_str().and_then(&describe) {
+            rendered.push_str(&format!("{}# {}\n", padding, description));
+        }
+        let mut single = Mapping::new();
+        single.insert(key, value);
+        for line in serde_yaml::to_string(&single)?.lines() {
+            rendered.push_str(&format!("{}{}\n", padding, line));
+        }
+    }
+
+    Ok(rendered)
+}
+
+/// Serializes to YAML, dropping unset options and empty lists so the output stays minimal.
+pub(crate) fn to_pruned_value<T: Serialize>(value: &T) -> Result<Value> {
+    let mut value = serde_yaml::to_value(value)?;
+    prune(&mut value);
+    Ok(value)
+}
+
+fn prune(value: &mut Value) {
+    if let Value::Mapping(mapping) = value {
+        mapping.retain(|_, child| {
+            !(child.is_null() || matches!(child, Value::Sequence(items) if items.is_empty()))
+        });
+        for (_, child) in mapping.iter_mut() {
+            prune(child);
+        }
+    }
+}
+
+fn prompt(question: &str, default: &str) -> Result<String> {
+    print!("{} [{}]: ", question, default);
+    let answer = read_answer()?;
+    Ok(if answer.is_empty() {
+        default.to_string()
+    } else {
+        answer
+    })
+}
+
+pub(crate) fn confirm(question: &str, default: bool) -> Result<bool> {
+    let hint = if default { "Y/n" } else { "y/N" };
+    loop {
+        print!("{} [{}]: ", question, hint);
+        match read_answer()?.to_ascii_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Please answer y or n."),
+        }
+    }
+}
+
+fn read_answer() -> Result<String> {
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
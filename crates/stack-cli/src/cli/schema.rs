@@ -0,0 +1,210 @@
+use crate::operator::crd::{ServiceSpec, StackApp};
+use anyhow::{anyhow, Context, Result};
+use kube::{CustomResourceExt, Resource};
+use schemars::generate::SchemaSettings;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+const VSCODE_SCHEMA_PATH: &str = ".vscode/stackapp.schema.json";
+const STACK_MANIFEST_GLOB: &str = "*.stack.yaml";
+
+pub fn schema(args: &crate::cli::SchemaArgs) -> Result<()> {
+    let rendered = render(&manifest_schema())?;
+
+    match args.output.as_ref() {
+        Some(output) => {
+            write_file(output, &rendered)?;
+            println!("📝 Wrote StackApp schema to {}", output.display());
+        }
+        None => print!("{}", rendered),
+    }
+
+    Ok(())
+}
+
+/// Writes the schema into `.vscode/` and associates it with `*.stack.yaml` files
+/// through the `yaml.schemas` setting of the Red Hat YAML extension.
+pub fn write_vscode_settings(workspace: &Path) -> Result<()> {
+    let schema_path = workspace.join(VSCODE_SCHEMA_PATH);
+    write_file(&schema_path, &render(&manifest_schema())?)?;
+
+    let settings_path = workspace.join(".vscode/settings.json");
+    let mut settings: Value = match fs::read_to_string(&settings_path) {
+        Ok(existing) => serde_json::from_str(&existing).with_context(|| {
+            format!(
+                "{} is not plain JSON (comments are not supported); add \"yaml.schemas\": {{ \"{}\": \"{}\" }} by hand",
+                settings_path.display(),
+                VSCODE_SCHEMA_PATH,
+                STACK_MANIFEST_GLOB
+            )
+        })?,
+        Err(_) => json!({}),
+    };
+
+    let settings_map = settings
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("{} must contain a JSON object", settings_path.display()))?;
+    let yaml_schemas = settings_map
+        .entry("yaml.schemas")
+        .or_insert_with(|| json!({}));
+    let yaml_schemas = yaml_schemas.as_object_mut().ok_or_else(|| {
+        anyhow!(
+            "\"yaml.schemas\" in {} must be an object",
+            settings_path.display()
+        )
+    })?;
+    yaml_schemas.insert(VSCODE_SCHEMA_PATH.to_string(), json!(STACK_MANIFEST_GLOB));
+
+    write_file(&settings_path, &render(&settings)?)?;
+    println!(
+        "🧩 Associated {} with {} in {}",
+        STACK_MANIFEST_GLOB,
+        VSCODE_SCHEMA_PATH,
+        settings_path.display()
+    );
+
+    Ok(())
+}
+
+/// JSON schema for `spec`, taken from the StackApp CRD with `services` expanded.
+///
+/// The CRD keeps `services` schemaless so extra services can use arbitrary keys, which
+/// means typos there would otherwise never be reported.
+pub(crate) fn spec_schema() -> Value {
+    let crd = serde_json::to_value(StackApp::crd()).unwrap_or_default();
+    let mut spec =
+        crd["spec"]["versions"][0]["schema"]["openAPIV3Schema"]["properties"]["spec"].clone();
+
+    let service = service_schema();
+    spec["properties"]["services"] = json!({
+        "description": "Services to deploy into the namespace (web and optional helpers).",
+        "type": "object",
+        "required": ["web"],
+        "properties": {
+            "web": service.clone()
+        },
+        "additionalProperties": service
+    });
+
+    spec
+}
+
+fn service_schema() -> Value {
+    let schema = SchemaSettings::openapi3()
+        .with(|settings| settings.inline_subschemas = true)
+        .into_generator()
+        .into_root_schema_for::<ServiceSpec>();
+    let mut schema = serde_json::to_value(schema).unwrap_or_default();
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("$schema");
+        object.remove("title");
+    }
+    schema
+}
+
+/// Standalone JSON Schema for a StackApp manifest file, including `spec.profiles`.
+pub(crate) fn manifest_schema() -> Value {
+    let spec = spec_schema();
+    let profile = to_json_schema(spec.clone(), true);
+    let mut spec = to_json_schema(spec, false);
+    spec["properties"]["profiles"] = json!({
+        "description": "Named overlays merged into spec by `stack deploy --profile <name>`.",
+        "type": "object",
+        "additionalProperties": profile
+    });
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "StackApp",
+        "description": "Stack application manifest.",
+        "type": "object",
+        "required": ["apiVersion", "kind", "metadata", "spec"],
+        "properties": {
+            "apiVersion": { "enum": [StackApp::api_version(&())] },
+            "kind": { "enum": [StackApp::kind(&())] },
+            "metadata": {
+                "type": "object",
+                "required": ["name", "namespace"],
+                "properties": {
+                    "name": {
+                        "description": "Application name; also names the web Deployment and database.",
+                        "type": "string"
+                    },
+                    "namespace": {
+                        "description": "Namespace the application is deployed into.",
+                        "type": "string"
+                    }
+                }
+            },
+            "spec": spec
+        }
+    })
+}
+
+/// Turns the CRD's OpenAPI flavoured schema into plain JSON Schema for editors.
+///
+/// `nullable` becomes a `null` type, Kubernetes extensions are dropped and objects with
+/// known properties reject unknown keys. Profiles are partial overlays, so
+/// `strip_required` removes every `required` list.
+fn to_json_schema(schema: Value, strip_required: bool) -> Value {
+    let Value::Object(mut object) = schema else {
+        return schema;
+    };
+
+    let preserve_unknown = object
+        .remove("x-kubernetes-preserve-unknown-fields")
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false);
+    object.retain(|key, _| !key.starts_with("x-kubernetes-"));
+
+    if object.remove("nullable").and_then(|value| value.as_bool()) == Some(true) {
+        if let Some(Value::String(kind)) = object.get("type").cloned() {
+            object.insert("type".to_string(), json!([kind, "null"]));
+        }
+    }
+
+    if strip_required {
+        object.remove("required");
+    }
+
+    if let Some(Value::Object(properties)) = object.get_mut("properties") {
+        for property in properties.values_mut() {
+            *property = to_json_schema(property.take(), strip_required);
+        }
+        if !preserve_unknown && !object.contains_key("additionalProperties") {
+            object.insert("additionalProperties".to_string(), json!(false));
+        }
+    }
+
+    for key in ["items", "additionalProperties"] {
+        if let Some(child @ Value::Object(_)) = object.get_mut(key) {
+            *child = to_json_schema(child.take(), strip_required);
+        }
+    }
+
+    for key in ["anyOf", "oneOf", "allOf"] {
+        if let Some(Value::Array(branches)) = object.get_mut(key) {
+            for branch in branches.iter_mut() {
+                *branch = to_json_schema(branch.take(), strip_required);
+            }
+        }
+    }
+
+    Value::Object(object)
+}
+
+fn render(value: &Value) -> Result<String> {
+    Ok(format!("{}\n", serde_json::to_string_pretty(value)?))
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
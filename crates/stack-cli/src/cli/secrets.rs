@@ -6,7 +6,7 @@ use kube::{Api, Client, ResourceExt};
 use std::collections::BTreeMap;
 use url::Url;
 
-fn decode_secret_field(secret: &Secret, key: &str) -> Option<String> {
+pub fn decode_secret_field(secret: &Secret, key: &str) -> Option<String> {
     if let Some(data) = &secret.data {
         if let Some(value) = data.get(key) {
             if let Ok(decoded) = String::from_utf8(value.0.clone()) {
@@ -33,7 +33,7 @@ fn to_env_key(key: &str) -> String {
         .collect()
 }
 
-fn rewrite_database_url(value: &str, host: Option<&str>, port: Option<u16>) -> Result<String> {
+pub fn rewrite_database_url(value: &str, host: Option<&str>, port: Option<u16>) -> Result<String> {
     if host.is_none() && port.is_none() {
         return Ok(value.to_string());
     }
@@ -69,6 +69,7 @@ pub async fn secrets(args: &crate::cli::SecretsArgs) -> Result<()> {
     if let Ok(database_urls) = secrets_api.get("database-urls").await {
         let keys = [
             "application-url",
+            "application-pooled-url",
             "migrations-url",
             "readonly-url",
             "authenticator-url",
@@ -83,6 +84,7 @@ pub async fn secrets(args: &crate::cli::SecretsArgs) -> Result<()> {
     if args.db_host.is_some() || args.db_port.is_some() {
         let keys = [
             to_env_key("application-url"),
+            to_env_key("application-pooled-url"),
             to_env_key("migrations-url"),
             to_env_key("readonly-url"),
             to_env_key("authenticator-url"),
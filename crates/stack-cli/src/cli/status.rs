@@ -1,8 +1,9 @@
 use crate::cli::manifest;
+use crate::services::database::{self, Cluster};
 use crate::services::jwt_secrets;
 use anyhow::{anyhow, Context, Result};
 use k8s_openapi::api::core::v1::{Pod, Secret};
-use kube::api::{ListParams, LogParams};
+use kube::api::{GetParams, ListParams, LogParams};
 use kube::{Api, Client, ResourceExt};
 
 fn decode_secret_field(secret: &Secret, key: &str) -> Option<String> {
@@ -111,6 +112,8 @@ pub async fn status(args: &crate::cli::StatusArgs) -> Result<()> {
         );
     }
 
+    print_database_status(client.clone(), &namespace, &stack_app.name_any()).await?;
+
     let jwt_secret_api: Api<Secret> = Api::namespaced(client.clone(), namespace.as_str());
     if let Ok(jwt_secret) = jwt_secret_api.get(jwt_secrets::JWT_AUTH_SECRET_NAME).await {
         let anon_jwt =
@@ -128,3 +131,60 @@ pub async fn status(args: &crate::cli::StatusArgs) -> Result<()> {
 
     Ok(())
 }
+
+async fn print_database_status(client: Client, namespace: &str, app_name: &str) -> Result<()> {
+    let cluster_name = database::active_cluster_name(client.clone(), namespace, app_name).await?;
+    let clusters: Api<Cluster> = Api::namespaced(client.clone(), namespace);
+    let Some(cluster) = clusters.get_opt(&cluster_name).await? else {
+        println!("🐘 Database: cluster {} not found", cluster_name);
+        return Ok(());
+    };
+    let status = cluster.status.unwrap_or_default();
+
+    println!("🐘 Database {}", cluster_name);
+    println!(
+        "   Phase: {}",
+        status.phase.as_deref().unwrap_or("<unknown>")
+    );
+    println!(
+        "   Ready: {}/{}",
+        status.ready_instances.unwrap_or(0),
+        cluster.spec.instances
+    );
+    let primary = status.current_primary.clone().unwrap_or_default();
+    println!(
+        "   Primary: {}",
+        if primary.is_empty() {
+            "<none>"
+        } else {
+            &primary
+        }
+    );
+    for instance in status
+        .instance_names
+        .iter()
+        .filter(|name| **name != primary)
+    {
+        match replication_lag(client.clone(), namespace, instance).await {
+            Some(lag) => println!("   Replica {}: {:.1}s behind", instance, lag),
+            None => println!("   Replica {}: lag unknown", instance),
+        }
+    }
+
+    Ok(())
+}
+
+/// Replay lag in seconds as reported by the CloudNativePG instance manager metrics.
+async fn replication_lag(client: Client, namespace: &str, pod: &str) -> Option<f64> {
+    let request = kube::core::Request::new(format!(
+        "/api/v1/namespaces/{}/pods/{}:9187/proxy",
+        namespace, pod
+    ))
+    .get("metrics", &GetParams::default())
+    .ok()?;
+    let metrics = client.request_text(request).await.ok()?;
+    metrics
+        .lines()
+        .filter(|line| line.starts_with("cnpg_pg_replication_lag"))
+        .find_map(|line| line.split_whitespace().last()?.parse().ok())
+}
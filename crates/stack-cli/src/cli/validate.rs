@@ -0,0 +1,672 @@
+use crate::cli::manifest;
+use crate::cli::schema::spec_schema;
+use crate::operator::crd::{DbDeletionPolicy, StackApp};
+use crate::operator::rules;
+use crate::services::database_cdc;
+use crate::services::database_extensions;
+use crate::services::database_external;
+use crate::services::database_migrations;
+use crate::services::database_parameters::{self, Field};
+use crate::services::database_rotation;
+use crate::services::database_seed;
+use crate::services::database_services;
+use crate::services::database_webhooks;
+use anyhow::{anyhow, Context, Result};
+use kube::Resource;
+use serde_json::Value as JsonValue;
+use serde_yaml::Value as YamlValue;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A single problem found in the manifest, addressed by its path from the document root.
+#[derive(Clone, Debug, PartialEq)]
+struct Issue {
+    path: Vec<Segment>,
+    message: String,
+}
+
+struct Path<'a>(&'a [Segment]);
+
+impl fmt::Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if index == 0 => write!(f, "{}", key)?,
+                Segment::Key(key) => write!(f, ".{}", key)?,
+                Segment::Index(item) => write!(f, "[{}]", item)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn dotted_path(path: &str) -> Vec<Segment> {
+    path.split('.')
+        .map(|key| Segment::Key(key.to_string()))
+        .collect()
+}
+
+pub fn validate(args: &crate::cli::ValidateArgs) -> Result<()> {
+    let display_path = args.manifest.display().to_string();
+    let manifest_raw = fs::read_to_string(&args.manifest)
+        .with_context(|| format!("Failed to read manifest at {}", display_path))?;
+
+    let doc: YamlValue = match serde_yaml::from_str(&manifest_raw) {
+        Ok(doc) => doc,
+        Err(err) => {
+            let (line, column) = err
+                .location()
+                .map(|location| (location.line(), location.column()))
+                .unwrap_or((1, 1));
+            println!(
+                "{}:{}:{}: invalid YAML: {}",
+                display_path, line, column, err
+            );
+            return Err(anyhow!("{} is not valid YAML", display_path));
+        }
+    };
+
+    let locator = Locator::new(&manifest_raw);
+    let schema = spec_schema();
+    let mut reports: Vec<(usize, usize, String)> = Vec::new();
+
+    let base_issues = check_variant(&doc, None, &schema);
+    for issue in &base_issues {
+        let (line, column, _) = locator.locate(&issue.path);
+        reports.push((line, column, issue.message.clone()));
+    }
+
+    let profile_names: Vec<String> = match doc.get("spec").and_then(|spec| spec.get("profiles")) {
+        Some(YamlValue::Mapping(profiles)) => profiles
+            .keys()
+            .filter_map(|key| key.as_str().map(str::to_string))
+            .collect(),
+        Some(_) => {
+            let (line, column, _) = locator.locate(&dotted_path("spec.profiles"));
+            reports.push((line, column, "spec.profiles must be a map".to_string()));
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+
+    for profile in &profile_names {
+        for issue in check_variant(&doc, Some(profile), &schema) {
+            if base_issues.contains(&issue) {
+                continue;
+            }
+            let (line, column) = locate_in_profile(&locator, profile, &issue.path);
+            reports.push((
+                line,
+                column,
+                format!("[profile {}] {}", profile, issue.message),
+            ));
+        }
+    }
+
+    reports.sort();
+    reports.dedup();
+
+    if reports.is_empty() {
+        println!(
+            "✅ {} is valid ({} profile{} checked)",
+            display_path,
+            profile_names.len(),
+            if profile_names.len() == 1 { "" } else { "s" }
+        );
+        return Ok(());
+    }
+
+    for (line, column, message) in &reports {
+        println!("{}:{}:{}: {}", display_path, line, column, message);
+    }
+
+    Err(anyhow!(
+        "{} problem{} found in {}",
+        reports.len(),
+        if reports.len() == 1 { "" } else { "s" },
+        display_path
+    ))
+}
+
+/// Profile issues point at the overriding key when the profile sets it, otherwise at the base spec.
+fn locate_in_profile(locator: &Locator, profile: &str, path: &[Segment]) -> (usize, usize) {
+    if let Some((Segment::Key(root), rest)) = path.split_first() {
+        if root == "spec" {
+            let mut profile_path = dotted_path("spec.profiles");
+            profile_path.push(Segment::Key(profile.to_string()));
+            profile_path.extend(rest.iter().cloned());
+            let (line, column, complete) = locator.locate(&profile_path);
+            if complete {
+                return (line, column);
+            }
+        }
+    }
+    let (line, column, _) = locator.locate(path);
+    (line, column)
+}
+
+/// Checks the manifest as it would be applied with the given profile (or none).
+fn check_variant(doc: &YamlValue, profile: Option<&str>, schema: &JsonValue) -> Vec<Issue> {
+    let mut doc = doc.clone();
+    if let Err(err) = manifest::apply_profile(&mut doc, profile) {
+        return vec![Issue {
+            path: dotted_path("spec"),
+            message: err.to_string(),
+        }];
+    }
+
+    let mut issues = Vec::new();
+    check_document_header(&doc, &mut issues);
+
+    let header_issues = issues.len();
+    let spec = serde_json::to_value(doc.get("spec")).unwrap_or(JsonValue::Null);
+    let mut path = vec![Segment::Key("spec".to_string())];
+    check_value(&spec, schema, &mut path, &mut issues);
+
+    if issues.len() == header_issues {
+        match serde_yaml::from_value::<StackApp>(doc) {
+            Ok(stack_app) => check_rules(&stack_app, &mut issues),
+            Err(err) => issues.push(Issue {
+                path: vec![],
+                message: format!("failed to parse StackApp: {}", err),
+            }),
+        }
+    }
+
+    issues
+}
+
+fn check_document_header(doc: &YamlValue, issues: &mut Vec<Issue>) {
+    let expected_api_version = StackApp::api_version(&());
+    let expected_kind = StackApp::kind(&());
+
+    if doc.get("apiVersion").and_then(YamlValue::as_str) != Some(&*expected_api_version) {
+        issues.push(Issue {
+            path: vec![Segment::Key("apiVersion".to_string())],
+            message: format!("apiVersion must be `{}`", expected_api_version),
+        });
+    }
+    if doc.get("kind").and_then(YamlValue::as_str) != Some(&*expected_kind) {
+        issues.push(Issue {
+            path: vec![Segment::Key("kind".to_string())],
+            message: format!("kind must be `{}`", expected_kind),
+        });
+    }
+
+    let metadata = doc.get("metadata");
+    for field in ["name", "namespace"] {
+        if metadata
+            .and_then(|metadata| metadata.get(field))
+            .and_then(YamlValue::as_str)
+            .is_none()
+        {
+            issues.push(Issue {
+                path: vec![Segment::Key("metadata".to_string())],
+                message: format!("metadata.{} is required", field),
+            });
+        }
+    }
+}
+
+/// The semantic rules the operator enforces (or Kubernetes rejects) at reconcile time.
+fn check_rules(stack_app: &StackApp, issues: &mut Vec<Issue>) {
+    let spec = &stack_app.spec;
+    let app_name = stack_app.metadata.name.clone().unwrap_or_default();
+
+    if spec.services.web.port.is_none() {
+        issues.push(Issue {
+            path: dotted_path("spec.services.web"),
+            message: "spec.services.web.port is required for the web service".to_string(),
+        });
+    }
+
+    let reserved = rules::reserved_service_names(&app_name);
+    for name in spec.services.extra.keys() {
+        let mut path = dotted_path("spec.services");
+        path.push(Segment::Key(name.clone()));
+        if name.trim().is_empty() {
+            issues.push(Issue {
+                path,
+                message: "extra service name cannot be empty".to_string(),
+            });
+        } else if reserved.contains(&name.as_str()) {
+            issues.push(Issue {
+                path,
+                message: format!("extra service name '{}' is reserved", name),
+            });
+        }
+    }
+
+    let mut claimed: BTreeMap<u16, &str> = BTreeMap::new();
+    for (path, port) in rules::node_ports(spec) {
+        if !rules::NODEPORT_RANGE.contains(&port) {
+            issues.push(Issue {
+                path: dotted_path(path),
+                message: format!(
+                    "NodePort {} is outside the allowed range {}-{}",
+                    port,
+                    rules::NODEPORT_RANGE.start(),
+                    rules::NODEPORT_RANGE.end()
+                ),
+            });
+        }
+        if let Some(first) = claimed.get(&port) {
+            issues.push(Issue {
+                path: dotted_path(path),
+                message: format!("NodePort {} is already used by {}", port, first),
+            });
+        } else {
+            claimed.insert(port, path);
+        }
+    }
+
+    if let Some(postgresql) = spec
+        .components
+        .db
+        .as_ref()
+        .and_then(|db| db.postgresql.as_ref())
+    {
+        for (field, message) in database_parameters::problems(postgresql) {
+            let mut path = dotted_path("spec.components.db.postgresql");
+            match field {
+                Field::Parameter(name) => {
+                    path.push(Segment::Key("parameters".to_string()));
+                    path.push(Segment::Key(name));
+                }
+                Field::SharedPreloadLibrary(index) => {
+                    path.push(Segment::Key("shared_preload_libraries".to_string()));
+                    path.push(Segment::Index(index));
+                }
+                Field::PgHba(index) => {
+                    path.push(Segment::Key("pg_hba".to_string()));
+                    path.push(Segment::Index(index));
+                }
+            }
+            issues.push(Issue { path, message });
+        }
+    }
+
+    let service_databases = database_services::service_databases(spec, &app_name);
+    let mut service_problems = database_services::problems(&app_name, &service_databases);
+    if spec.components.db.is_none() {
+        service_problems.extend(
+            service_databases
+                .iter()
+                .map(|(service, _)| (service.clone(), "needs components.db".to_string())),
+        );
+    }
+    for (service, message) in service_problems {
+        let mut path = dotted_path("spec.services");
+        let key = if service == app_name {
+            "web"
+        } else {
+            service.as_str()
+        };
+        path.push(Segment::Key(key.to_string()));
+        path.push(Segment::Key("database".to_string()));
+        issues.push(Issue { path, message });
+    }
+
+    if let Some(migrations) = spec.migrations.as_ref() {
+        let mut messages = database_migrations::problems(migrations);
+        if spec.components.db.is_none() {
+            messages.push("needs components.db".to_string());
+        }
+        for message in messages {
+            issues.push(Issue {
+                path: dotted_path("spec.migrations"),
+                message,
+            });
+        }
+    }
+
+    if let Some(db) = spec.components.db.as_ref() {
+        for (index, message) in database_extensions::problems(db) {
+            let mut path = dotted_path("spec.components.db.extensions");
+            path.push(Segment::Index(index));
+            issues.push(Issue { path, message });
+        }
+        if let Some(interval) = db.rotation_interval.as_deref() {
+            if database_rotation::interval_seconds(interval).is_none() {
+                issues.push(Issue {
+                    path: dotted_path("spec.components.db.rotation_interval"),
+                    message: format!("'{}' should look like 12h, 30d or 4w", interval),
+                });
+            }
+        }
+        for (webhook, message) in db.webhooks.iter().flat_map(database_webhooks::problems) {
+            let mut path = dotted_path("spec.components.db.webhooks");
+            path.push(Segment::Key(webhook));
+            issues.push(Issue { path, message });
+        }
+        for message in db.cdc.iter().flat_map(database_cdc::problems) {
+            issues.push(Issue {
+                path: dotted_path("spec.components.db.cdc"),
+                message,
+            });
+        }
+        for message in db.seed.iter().flat_map(database_seed::problems) {
+            issues.push(Issue {
+                path: dotted_path("spec.components.db.seed"),
+                message,
+            });
+        }
+        if db.deletion_policy == Some(DbDeletionPolicy::Snapshot)
+            && db.backup.is_none()
+            && db.external.is_none()
+        {
+            issues.push(Issue {
+                path: dotted_path("spec.components.db.deletion_policy"),
+                message: "Snapshot takes its final backup through components.db.backup; set it or pick Delete or Retain"
+                    .to_string(),
+            });
+        }
+        if db.external.is_some() {
+            for field in database_external::unsupported_settings(db) {
+                let mut path = dotted_path("spec.components.db");
+                path.push(Segment::Key(field.to_string()));
+                issues.push(Issue {
+                    path,
+                    message:
+                        "only applies to the CloudNativePG cluster, not components.db.external"
+                            .to_string(),
+                });
+            }
+        }
+    }
+}
+
+fn check_value(
+    value: &JsonValue,
+    schema: &JsonValue,
+    path: &mut Vec<Segment>,
+    issues: &mut Vec<Issue>,
+) {
+    if let Some(branches) = schema
+        .get("anyOf")
+        .or_else(|| schema.get("oneOf"))
+        .and_then(JsonValue::as_array)
+    {
+        let mut first_failure: Option<Vec<Issue>> = None;
+        for branch in branches {
+            let mut branch_issues = Vec::new();
+            check_value(value, branch, path, &mut branch_issues);
+            if branch_issues.is_empty() {
+                return;
+            }
+            if first_failure.is_none() && !accepts_only_null(branch) {
+                first_failure = Some(branch_issues);
+            }
+        }
+        issues.extend(first_failure.unwrap_or_default());
+        return;
+    }
+
+    let types: Vec<&str> = match schema.get("type") {
+        Some(JsonValue::String(kind)) => vec![kind.as_str()],
+        Some(JsonValue::Array(kinds)) => kinds.iter().filter_map(JsonValue::as_str).collect(),
+        _ => vec![],
+    };
+
+    if value.is_null() {
+        let nullable = schema.get("nullable").and_then(JsonValue::as_bool) == Some(true);
+        if !(types.is_empty() || nullable || types.contains(&"null")) {
+            issues.push(Issue {
+                path: path.clone(),
+                message: format!("{} must be {}, found null", Path(path), types.join(" or ")),
+            });
+        }
+        return;
+    }
+
+    if !types.is_empty() && !types.iter().any(|kind| matches_type(value, kind)) {
+        issues.push(Issue {
+            path: path.clone(),
+            message: format!(
+                "{} must be {}, found {}",
+                Path(path),
+                types.join(" or "),
+                type_name(value)
+            ),
+        });
+        return;
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(JsonValue::as_array) {
+        if !allowed.contains(value) {
+            let options: Vec<String> = allowed.iter().map(JsonValue::to_string).collect();
+            issues.push(Issue {
+                path: path.clone(),
+                message: format!("{} must be one of {}", Path(path), options.join(", ")),
+            });
+        }
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(JsonValue::as_f64) {
+            if number < minimum {
+                issues.push(Issue {
+                    path: path.clone(),
+                    message: format!("{} must be at least {}", Path(path), minimum),
+                });
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(JsonValue::as_f64) {
+            if number > maximum {
+                issues.push(Issue {
+                    path: path.clone(),
+                    message: format!("{} must be at most {}", Path(path), maximum),
+                });
+            }
+        }
+    }
+
+    match value {
+        JsonValue::Object(object) => check_object(object, schema, path, issues),
+        JsonValue::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    path.push(Segment::Index(index));
+                    check_value(item, item_schema, path, issues);
+                    path.pop();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_object(
+    object: &serde_json::Map<String, JsonValue>,
+    schema: &JsonValue,
+    path: &mut Vec<Segment>,
+    issues: &mut Vec<Issue>,
+) {
+    let properties = schema.get("properties").and_then(JsonValue::as_object);
+
+    if let Some(required) = schema.get("required").and_then(JsonValue::as_array) {
+        for field in required.iter().filter_map(JsonValue::as_str) {
+            if !object.contains_key(field) {
+                issues.push(Issue {
+                    path: path.clone(),
+                    message: format!("{} is missing required field `{}`", Path(path), field),
+                });
+            }
+        }
+    }
+
+    let preserve_unknown = schema
+        .get("x-kubernetes-preserve-unknown-fields")
+        .and_then(JsonValue::as_bool)
+        == Some(true);
+    let additional = schema.get("additionalProperties");
+
+    for (key, child) in object {
+        path.push(Segment::Key(key.clone()));
+        if let Some(property) = properties.and_then(|properties| properties.get(key)) {
+            check_value(child, property, path, issues);
+        } else if let Some(additional @ JsonValue::Object(_)) = additional {
+            check_value(child, additional, path, issues);
+        } else if !preserve_unknown && additional != Some(&JsonValue::Bool(true)) {
+            let parent = Path(&path[..path.len() - 1]).to_string();
+            let suggestion = properties
+                .and_then(|properties| closest_key(key, properties.keys()))
+                .map(|candidate| format!(" (did you mean `{}`?)", candidate))
+                .unwrap_or_default();
+            issues.push(Issue {
+                path: path.clone(),
+                message: format!("unknown field `{}` in {}{}", key, parent, suggestion),
+            });
+        }
+        path.pop();
+    }
+}
+
+fn accepts_only_null(schema: &JsonValue) -> bool {
+    schema.get("type").and_then(JsonValue::as_str) == Some("null")
+}
+
+fn matches_type(value: &JsonValue, kind: &str) -> bool {
+    match kind {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "boolean",
+        JsonValue::Number(number) if number.is_f64() => "number",
+        JsonValue::Number(_) => "integer",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "object",
+    }
+}
+
+fn closest_key<'a>(key: &str, candidates: impl Iterator<Item = &'a String>) -> Option<&'a str> {
+    let threshold = (key.len() / 3).max(2);
+    candidates
+        .map(|candidate| (strsim::levenshtein(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// A non-blank, non-comment YAML line. Sequence items (`- key: value`) produce a
+/// marker entry for the dash plus an entry for the inline key, one indent deeper.
+struct Entry<'a> {
+    line: usize,
+    indent: usize,
+    dash: bool,
+    text: &'a str,
+}
+
+/// Maps manifest paths back to line and column numbers in block-style YAML.
+///
+/// `serde_yaml::Value` drops source positions, so this walks the raw text by indentation.
+/// Flow-style collections are not descended into; lookups stop at the enclosing key.
+struct Locator<'a> {
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> Locator<'a> {
+    fn new(raw: &'a str) -> Self {
+        let mut entries = Vec::new();
+        for (index, line) in raw.lines().enumerate() {
+            let text = line.trim_start();
+            if text.is_empty() || text.starts_with('#') || text.starts_with("---") {
+                continue;
+            }
+            let indent = line.len() - text.len();
+            if let Some(rest) = text.strip_prefix('-') {
+                if rest.is_empty() || rest.starts_with(' ') {
+                    entries.push(Entry {
+                        line: index + 1,
+                        indent,
+                        dash: true,
+                        text,
+                    });
+                    let inline = rest.trim_start();
+                    if !inline.is_empty() {
+                        entries.push(Entry {
+                            line: index + 1,
+                            indent: indent + (text.len() - inline.len()),
+                            dash: false,
+                            text: inline,
+                        });
+                    }
+                    continue;
+                }
+            }
+            entries.push(Entry {
+                line: index + 1,
+                indent,
+                dash: false,
+                text,
+            });
+        }
+        Locator { entries }
+    }
+
+    /// Returns the 1-based line and column of the deepest part of `path` found in the
+    /// source, plus whether the whole path was found.
+    fn locate(&self, path: &[Segment]) -> (usize, usize, bool) {
+        let mut start = 0;
+        let mut end = self.entries.len();
+        let mut position = (1, 1);
+
+        for segment in path {
+            let Some(first) = self.entries[start..end].first() else {
+                return (position.0, position.1, false);
+            };
+            let child_indent = first.indent;
+            let siblings = (start..end).filter(|&index| self.entries[index].indent == child_indent);
+
+            let found = match segment {
+                Segment::Key(key) => siblings
+                    .filter(|&index| !self.entries[index].dash)
+                    .find(|&index| entry_key(self.entries[index].text) == Some(key.as_str())),
+                Segment::Index(item) => siblings
+                    .filter(|&index| self.entries[index].dash)
+                    .nth(*item),
+            };
+
+            let Some(index) = found else {
+                return (position.0, position.1, false);
+            };
+
+            let entry = &self.entries[index];
+            position = (entry.line, entry.indent + 1);
+            start = index + 1;
+            end = (start..end)
+                .find(|&next| {
+                    let next = &self.entries[next];
+                    next.indent < child_indent
+                        || (next.indent == child_indent && (entry.dash || !next.dash))
+                })
+                .unwrap_or(end);
+        }
+
+        (position.0, position.1, true)
+    }
+}
+
+fn entry_key(text: &str) -> Option<&str> {
+    let (key, _) = text.split_once(':')?;
+    Some(key.trim().trim_matches(|c| c == '"' || c == '\''))
+}
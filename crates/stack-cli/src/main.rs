@@ -30,6 +30,64 @@ async fn main() -> Result<()> {
         cli::Commands::Cloudflare(args) => {
             cli::cloudflare::cloudflare(args).await?;
         }
+        cli::Commands::Validate(args) => {
+            cli::validate::validate(args)?;
+        }
+        cli::Commands::Schema(args) => {
+            cli::schema::schema(args)?;
+        }
+        cli::Commands::New(args) => {
+            cli::new::new(args)?;
+        }
+        cli::Commands::Import(args) => match &args.source {
+            cli::ImportSource::Compose(args) => {
+                cli::import::compose(args)?;
+            }
+        },
+        cli::Commands::Export(args) => match &args.target {
+            cli::ExportTarget::Compose(args) => {
+                cli::export::compose(args)?;
+            }
+        },
+        cli::Commands::Db(args) => match &args.command {
+            cli::DbCommand::Backup(args) => {
+                cli::db::backup(args).await?;
+            }
+            cli::DbCommand::Backups(args) => match &args.command {
+                cli::DbBackupsCommand::List(args) => {
+                    cli::db::list_backups(args).await?;
+                }
+            },
+            cli::DbCommand::Restore(args) => {
+                cli::db::restore(args).await?;
+            }
+            cli::DbCommand::RotatePasswords(args) => {
+                cli::db::rotate_passwords(args).await?;
+            }
+            cli::DbCommand::Clone(args) => {
+                cli::db::clone(args).await?;
+            }
+            cli::DbCommand::Psql(args) => {
+                cli::db::psql(args).await?;
+            }
+            cli::DbCommand::Dump(args) => {
+                cli::db::dump(args).await?;
+            }
+            cli::DbCommand::Forward(args) => {
+                cli::db::forward(args).await?;
+            }
+            cli::DbCommand::Top(args) => {
+                cli::db::top(args).await?;
+            }
+            cli::DbCommand::Migrate(args) => match &args.command {
+                cli::DbMigrateCommand::Status(args) => {
+                    cli::db::migrate_status(args).await?;
+                }
+                cli::DbMigrateCommand::New(args) => {
+                    cli::db::migrate_new(args)?;
+                }
+            },
+        },
     }
 
     Ok(())
@@ -1,6 +1,7 @@
 pub mod crd;
 mod finalizer;
-mod reconcile;
+pub(crate) mod reconcile;
+pub mod rules;
 use anyhow::Result;
 use crd::StackApp;
 use futures_util::{pin_mut, StreamExt};
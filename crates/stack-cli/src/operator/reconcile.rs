@@ -1,24 +1,34 @@
-use super::crd::{EnvVar, SecretEnvVar, ServiceSpec, StackApp, StackAppSpec};
+use super::crd::{
+    EnvVar, SecretEnvVar, ServiceDatabaseConfig, ServiceSpec, ServiceVolume, StackApp,
+    StackAppSpec, StackAppStatus,
+};
 use super::finalizer;
+use super::rules;
 use crate::error::Error;
 use crate::services::{
-    auth, database, deployment, document_engine, jwt_secrets, keycloak, mailhog, nginx,
-    oauth2_proxy, postgrest, realtime, selenium, storage,
+    auth, database, database_backup, database_cdc, database_deletion, database_extensions,
+    database_external, database_migrations, database_monitoring, database_pooler,
+    database_rotation, database_seed, database_services, database_webhooks, deployment,
+    document_engine, jwt_secrets, keycloak, mailhog, nginx, oauth2_proxy, postgrest, realtime,
+    selenium, storage,
+};
+use k8s_openapi::api::{
+    apps::v1::Deployment as KubeDeployment,
+    core::v1::{PersistentVolumeClaim, Service},
 };
-use k8s_openapi::api::{apps::v1::Deployment as KubeDeployment, core::v1::Service};
 use kube::api::{DeleteParams, Patch, PatchParams};
 use kube::{Api, Client, Resource, ResourceExt};
 use kube_runtime::controller::Action;
 use serde_json::{json, Value};
 use std::{sync::Arc, time::Duration};
 
-const DEFAULT_DB_DISK_SIZE_GB: i32 = 20;
 const DB_NODEPORT_SERVICE_NAME: &str = "postgres-development";
 const APP_NODEPORT_SERVICE_NAME: &str = "nginx-development";
 const REST_NODEPORT_SERVICE_NAME: &str = "rest-development";
 const SELENIUM_NODEPORT_SERVICE_NAME: &str = "selenium-development";
 const MAILHOG_NODEPORT_SERVICE_NAME: &str = "mailhog-development";
 const WEB_APP_REPLICAS: i32 = 1;
+const DEFAULT_SERVICE_VOLUME_SIZE: &str = "1Gi";
 /// Context injected with each `reconcile` and `on_error` method invocation.
 pub struct ContextData {
     /// Kubernetes client to make Kubernetes API requests with. Required for K8S resource management.
@@ -43,6 +53,18 @@ pub async fn reconcile(app: Arc<StackApp>, context: Arc<ContextData>) -> Result<
     let name = app.name_any();
 
     if app.meta().deletion_timestamp.is_some() {
+        // First, while the backup destination and its MinIO are still there.
+        let database_done = database_deletion::delete(
+            client.clone(),
+            &namespace,
+            &name,
+            app.spec.components.db.as_ref(),
+        )
+        .await?;
+        if !database_done {
+            // A Snapshot deletion waits for its final backup.
+            return Ok(Action::requeue(Duration::from_secs(10)));
+        }
         for name in app.spec.services.extra.keys() {
             let deployments: Api<KubeDeployment> =
                 Api::namespaced(client.clone(), namespace.as_str());
@@ -63,32 +85,88 @@ pub async fn reconcile(app: Arc<StackApp>, context: Arc<ContextData>) -> Result<
         selenium::delete(client.clone(), &namespace).await?;
         mailhog::delete(client.clone(), &namespace).await?;
         jwt_secrets::delete(client.clone(), &namespace).await?;
-        database::delete(client.clone(), &namespace, &name).await?;
+        database_backup::delete(client.clone(), &namespace, &name).await?;
+        database_cdc::delete(client.clone(), &namespace, &name).await?;
+        database_extensions::delete(client.clone(), &namespace, &name).await?;
+        database_external::delete(client.clone(), &namespace, &name).await?;
+        database_migrations::delete(client.clone(), &namespace, &name).await?;
+        database_monitoring::delete(client.clone(), &namespace, &name).await?;
+        database_rotation::delete(client.clone(), &namespace, &name).await?;
+        database_seed::delete(client.clone(), &namespace, &name).await?;
+        database_webhooks::delete(client.clone(), &namespace, &name).await?;
+        database_pooler::delete(client.clone(), &namespace, &name).await?;
         finalizer::delete(client, &name, &namespace).await?;
         return Ok(Action::await_change());
     }
 
     finalizer::add(client.clone(), &name, &namespace).await?;
 
-    let insecure_override_passwords = app
-        .spec
-        .components
-        .db
+    let manifest_changed = app
+        .status
         .as_ref()
-        .and_then(|db| db.danger_override_password.clone());
-    let database_image_name = app
+        .and_then(|status| status.observed_generation)
+        != app.metadata.generation;
+    let external_db = app
         .spec
         .components
         .db
         .as_ref()
-        .and_then(|db| db.image_name.clone());
-    database::deploy(
+        .and_then(|db| db.external.as_ref().map(|external| (db, external)));
+    database_deletion::adopt(client.clone(), &namespace, &name).await?;
+    let mut database_status = if let Some((db, external)) = external_db {
+        database_external::deploy(client.clone(), &namespace, &name, db, external).await?
+    } else {
+        database_external::delete(client.clone(), &namespace, &name).await?;
+        database::deploy(
+            client.clone(),
+            &namespace,
+            &name,
+            app.spec.components.db.as_ref(),
+            manifest_changed,
+        )
+        .await?
+    };
+    database_pooler::deploy(
+        client.clone(),
+        &namespace,
+        &name,
+        app.spec
+            .components
+            .db
+            .as_ref()
+            .and_then(|db| db.pooler.as_ref()),
+    )
+    .await?;
+    database_extensions::deploy(
+        client.clone(),
+        &namespace,
+        &name,
+        app.spec.components.db.as_ref(),
+        &mut database_status,
+    )
+    .await?;
+    database_monitoring::deploy(
         client.clone(),
         &namespace,
         &name,
-        DEFAULT_DB_DISK_SIZE_GB,
-        &database_image_name,
-        &insecure_override_passwords,
+        app.spec.components.db.as_ref(),
+        &mut database_status,
+    )
+    .await?;
+    database_rotation::deploy(
+        client.clone(),
+        &namespace,
+        &name,
+        app.spec.components.db.as_ref(),
+        &mut database_status,
+    )
+    .await?;
+    database_services::deploy(
+        client.clone(),
+        &namespace,
+        &name,
+        &app.spec,
+        &mut database_status,
     )
     .await?;
 
@@ -98,6 +176,31 @@ pub async fn reconcile(app: Arc<StackApp>, context: Arc<ContextData>) -> Result<
         storage::delete(client.clone(), &namespace).await?;
     }
 
+    database_backup::deploy(
+        client.clone(),
+        &namespace,
+        &name,
+        app.spec
+            .components
+            .db
+            .as_ref()
+            .and_then(|db| db.backup.as_ref()),
+        app.spec.components.storage.as_ref(),
+    )
+    .await?;
+    database_cdc::deploy(
+        client.clone(),
+        &namespace,
+        &name,
+        app.spec
+            .components
+            .db
+            .as_ref()
+            .and_then(|db| db.cdc.as_ref()),
+        app.spec.components.storage.as_ref(),
+    )
+    .await?;
+
     if let Some(rest_spec) = app.spec.components.rest.as_ref() {
         postgrest::deploy(client.clone(), &namespace, Some(rest_spec)).await?;
     } else {
@@ -197,14 +300,91 @@ pub async fn reconcile(app: Arc<StackApp>, context: Arc<ContextData>) -> Result<
         auth::delete(client.clone(), &namespace).await?;
     }
 
-    deploy_web_app(&client, &namespace, &app.spec, &name, web_port).await?;
+    if app.spec.migrations.is_some() && app.spec.components.db.is_none() {
+        return Err(Error::Other(
+            "spec.migrations needs components.db".to_string(),
+        ));
+    }
+    let migrated = database_migrations::deploy(
+        client.clone(),
+        &namespace,
+        &name,
+        app.spec.migrations.as_ref(),
+        &mut database_status,
+    )
+    .await?;
+    if migrated {
+        deploy_web_app(&client, &namespace, &app.spec, &name, web_port).await?;
+    }
     deploy_extra_services(&client, &namespace, &app.spec.services.extra, &name).await?;
-    let db_cluster_name = database::cluster_resource_name(&name);
+    database_seed::deploy(
+        client.clone(),
+        &namespace,
+        &name,
+        app.spec
+            .components
+            .db
+            .as_ref()
+            .and_then(|db| db.seed.as_ref()),
+        &mut database_status,
+    )
+    .await?;
+    database_webhooks::deploy(
+        client.clone(),
+        &namespace,
+        &name,
+        app.spec
+            .components
+            .db
+            .as_ref()
+            .and_then(|db| db.webhooks.as_ref()),
+        migrated,
+        &mut database_status,
+    )
+    .await?;
+    let db_cluster_name = database::active_cluster_name(client.clone(), &namespace, &name).await?;
     ensure_optional_nodeports(&client, &namespace, &app.spec, &db_cluster_name).await?;
 
+    update_status(
+        &client,
+        &namespace,
+        &app,
+        StackAppStatus {
+            observed_generation: app.metadata.generation,
+            database: Some(database_status),
+        },
+    )
+    .await?;
+
     Ok(Action::requeue(Duration::from_secs(10)))
 }
 
+/// Writes the StackApp status when it changed, so status updates do not retrigger
+/// reconciliation on every tick.
+async fn update_status(
+    client: &Client,
+    namespace: &str,
+    app: &StackApp,
+    status: StackAppStatus,
+) -> Result<(), Error> {
+    if app.status.as_ref() == Some(&status) {
+        return Ok(());
+    }
+    let api: Api<StackApp> = Api::namespaced(client.clone(), namespace);
+    let patch = json!({
+        "apiVersion": StackApp::api_version(&()),
+        "kind": StackApp::kind(&()),
+        "status": status
+    });
+    api.patch_status(
+        &app.name_any(),
+        &PatchParams::apply(crate::MANAGER).force(),
+        &Patch::Apply(patch),
+    )
+    .await?;
+    Ok(())
+}
+
 /// Actions to be taken when a reconciliation fails - for whatever reason.
 /// Prints out the error to `stderr` and requeues the resource for another reconciliation after
 /// five seconds.
@@ -225,6 +405,21 @@ async fn deploy_web_app(
     app_name: &str,
     web_port: u16,
 ) -> Result<(), Error> {
+    ensure_volume_claims(client, namespace, app_name, &spec.services.web.volumes).await?;
+    deployment::deployment(
+        client.clone(),
+        web_service_deployment(spec, app_name, web_port),
+        namespace,
+    )
+    .await
+}
+
+/// Deployment for `services.web`, named after the app.
+pub(crate) fn web_service_deployment(
+    spec: &StackAppSpec,
+    app_name: &str,
+    web_port: u16,
+) -> deployment::ServiceDeployment {
     let hostname_env = spec
         .components
         .oidc
@@ -239,7 +434,9 @@ async fn deploy_web_app(
         &spec.services.web.database_url,
         &spec.services.web.migrations_database_url,
         &spec.services.web.readonly_database_url,
+        &spec.services.web.pooled_database_url,
     );
+    append_service_database_env(&mut env, app_name, &spec.services.web.database);
     append_jwt_envs(
         &mut env,
         &spec.services.web.jwt_secret,
@@ -258,45 +455,21 @@ async fn deploy_web_app(
         &spec.services.web.secret_env,
     );
 
-    let init_containers = spec.services.web.init.as_ref().map(|init| {
-        let mut init_env = Vec::new();
-        append_db_envs(
-            &mut init_env,
-            &init.database_url,
-            &init.migrations_database_url,
-            &init.readonly_database_url,
-        );
-        append_jwt_envs(
-            &mut init_env,
-            &init.jwt_secret,
-            &init.anon_jwt,
-            &init.service_role_jwt,
-        );
-        append_env_from_spec(&mut init_env, &init.env, &init.secret_env);
+    let (volume_mounts, volumes) = service_volumes(app_name, &spec.services.web.volumes);
 
-        deployment::InitContainer {
-            image_name: init.image.clone(),
-            env: init_env,
-            command: None,
-        }
-    });
-
-    deployment::deployment(
-        client.clone(),
-        deployment::ServiceDeployment {
-            name: app_name.to_string(),
-            image_name: spec.services.web.image.clone(),
-            replicas: WEB_APP_REPLICAS,
-            port: Some(web_port),
-            env,
-            init_containers: init_containers.into_iter().collect(),
-            command: None,
-            volume_mounts: vec![],
-            volumes: vec![],
-        },
-        namespace,
-    )
-    .await
+    deployment::ServiceDeployment {
+        name: app_name.to_string(),
+        image_name: spec.services.web.image.clone(),
+        replicas: WEB_APP_REPLICAS,
+        port: Some(web_port),
+        env,
+        init_containers: service_init_container(&spec.services.web)
+            .into_iter()
+            .collect(),
+        command: service_command(&spec.services.web),
+        volume_mounts,
+        volumes,
+    }
 }
 
 async fn deploy_extra_services(
@@ -305,19 +478,7 @@ async fn deploy_extra_services(
     services: &std::collections::BTreeMap<String, ServiceSpec>,
     app_name: &str,
 ) -> Result<(), Error> {
-    let reserved = [
-        app_name,
-        nginx::NGINX_NAME,
-        postgrest::REST_NAME,
-        realtime::REALTIME_NAME,
-        storage::STORAGE_NAME,
-        document_engine::DOCUMENT_ENGINE_NAME,
-        selenium::SELENIUM_NAME,
-        mailhog::MAILHOG_NAME,
-        auth::AUTH_NAME,
-        "oauth2-proxy",
-        "minio",
-    ];
+    let reserved = rules::reserved_service_names(app_name);
     let mut seen = std::collections::HashSet::new();
 
     for (name, service) in services {
@@ -339,67 +500,165 @@ async fn deploy_extra_services(
             )));
         }
 
-        let mut env = Vec::new();
+        ensure_volume_claims(client, namespace, name, &service.volumes).await?;
+        deployment::deployment(
+            client.clone(),
+            extra_service_deployment(name, service),
+            namespace,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Deployment for an entry of `services` other than `web`.
+pub(crate) fn extra_service_deployment(
+    name: &str,
+    service: &ServiceSpec,
+) -> deployment::ServiceDeployment {
+    let mut env = Vec::new();
+
+    append_db_envs(
+        &mut env,
+        &service.database_url,
+        &service.migrations_database_url,
+        &service.readonly_database_url,
+        &service.pooled_database_url,
+    );
+    append_service_database_env(&mut env, name, &service.database);
+    append_jwt_envs(
+        &mut env,
+        &service.jwt_secret,
+        &service.anon_jwt,
+        &service.service_role_jwt,
+    );
+
+    append_env_from_spec(&mut env, &service.env, &service.secret_env);
+
+    let (volume_mounts, volumes) = service_volumes(name, &service.volumes);
 
+    deployment::ServiceDeployment {
+        name: name.to_string(),
+        image_name: service.image.clone(),
+        replicas: WEB_APP_REPLICAS,
+        port: service.port,
+        env,
+        init_containers: service_init_container(service).into_iter().collect(),
+        command: service_command(service),
+        volume_mounts,
+        volumes,
+    }
+}
+
+fn service_init_container(service: &ServiceSpec) -> Option<deployment::InitContainer> {
+    service.init.as_ref().map(|init| {
+        let mut init_env = Vec::new();
         append_db_envs(
-            &mut env,
-            &service.database_url,
-            &service.migrations_database_url,
-            &service.readonly_database_url,
+            &mut init_env,
+            &init.database_url,
+            &init.migrations_database_url,
+            &init.readonly_database_url,
+            &init.pooled_database_url,
         );
         append_jwt_envs(
-            &mut env,
-            &service.jwt_secret,
-            &service.anon_jwt,
-            &service.service_role_jwt,
+            &mut init_env,
+            &init.jwt_secret,
+            &init.anon_jwt,
+            &init.service_role_jwt,
         );
+        append_env_from_spec(&mut init_env, &init.env, &init.secret_env);
 
-        append_env_from_spec(&mut env, &service.env, &service.secret_env);
-
-        let init_containers = service.init.as_ref().map(|init| {
-            let mut init_env = Vec::new();
-            append_db_envs(
-                &mut init_env,
-                &init.database_url,
-                &init.migrations_database_url,
-                &init.readonly_database_url,
-            );
-            append_jwt_envs(
-                &mut init_env,
-                &init.jwt_secret,
-                &init.anon_jwt,
-                &init.service_role_jwt,
-            );
-            append_env_from_spec(&mut init_env, &init.env, &init.secret_env);
-
-            deployment::InitContainer {
-                image_name: init.image.clone(),
-                env: init_env,
-                command: None,
-            }
-        });
+        deployment::InitContainer {
+            image_name: init.image.clone(),
+            env: init_env,
+            command: None,
+        }
+    })
+}
 
-        deployment::deployment(
-            client.clone(),
-            deployment::ServiceDeployment {
-                name: name.clone(),
-                image_name: service.image.clone(),
-                replicas: WEB_APP_REPLICAS,
-                port: service.port,
-                env,
-                init_containers: init_containers.into_iter().collect(),
-                command: None,
-                volume_mounts: vec![],
-                volumes: vec![],
+fn service_command(service: &ServiceSpec) -> Option<deployment::Command> {
+    if service.command.is_none() && service.args.is_none() {
+        return None;
+    }
+
+    Some(deployment::Command {
+        command: service.command.clone().unwrap_or_default(),
+        args: service.args.clone().unwrap_or_default(),
+    })
+}
+
+/// Ensures a PersistentVolumeClaim for each service volume. Claims are left in place
+/// when a volume is removed from the manifest so its data is not lost.
+async fn ensure_volume_claims(
+    client: &Client,
+    namespace: &str,
+    service_name: &str,
+    service_volumes: &[ServiceVolume],
+) -> Result<(), Error> {
+    let claims: Api<PersistentVolumeClaim> = Api::namespaced(client.clone(), namespace);
+
+    for volume in service_volumes {
+        let claim_name = volume_claim_name(service_name, volume);
+        let claim = json!({
+            "apiVersion": "v1",
+            "kind": "PersistentVolumeClaim",
+            "metadata": {
+                "name": claim_name,
+                "namespace": namespace
             },
-            namespace,
-        )
-        .await?;
+            "spec": {
+                "accessModes": ["ReadWriteOnce"],
+                "resources": {
+                    "requests": {
+                        "storage": volume
+                            .size
+                            .clone()
+                            .unwrap_or_else(|| DEFAULT_SERVICE_VOLUME_SIZE.to_string())
+                    }
+                }
+            }
+        });
+        claims
+            .patch(
+                &claim_name,
+                &PatchParams::apply(crate::MANAGER).force(),
+                &Patch::Apply(claim),
+            )
+            .await?;
     }
 
     Ok(())
 }
 
+/// `volumeMounts` and `volumes` entries for the claims created by `ensure_volume_claims`.
+fn service_volumes(
+    service_name: &str,
+    service_volumes: &[ServiceVolume],
+) -> (Vec<Value>, Vec<Value>) {
+    let mut volume_mounts = Vec::new();
+    let mut volumes = Vec::new();
+
+    for volume in service_volumes {
+        volume_mounts.push(json!({
+            "name": volume.name,
+            "mountPath": volume.mount_path
+        }));
+        volumes.push(json!({
+            "name": volume.name,
+            "persistentVolumeClaim": {
+                "claimName": volume_claim_name(service_name, volume)
+            }
+        }));
+    }
+
+    (volume_mounts, volumes)
+}
+
+fn volume_claim_name(service_name: &str, volume: &ServiceVolume) -> String {
+    format!("{}-{}", service_name, volume.name)
+}
+
 async fn ensure_optional_nodeports(
     client: &Client,
     namespace: &str,
@@ -712,11 +971,31 @@ fn append_env_from_spec(
     }
 }
 
+/// URL of the service's own role, from the `{service}-db` secret.
+fn append_service_database_env(
+    env: &mut Vec<Value>,
+    service_name: &str,
+    database: &Option<ServiceDatabaseConfig>,
+) {
+    if let Some(database) = database {
+        env.push(json!({
+            "name": database_services::url_env(database),
+            "valueFrom": {
+                "secretKeyRef": {
+                    "name": database_services::secret_name(service_name),
+                    "key": "url"
+                }
+            }
+        }));
+    }
+}
+
 fn append_db_envs(
     env: &mut Vec<Value>,
     database_url: &Option<String>,
     migrations_database_url: &Option<String>,
     readonly_database_url: &Option<String>,
+    pooled_database_url: &Option<String>,
 ) {
     if let Some(db_env_name) = database_url.clone() {
         env.push(json!({
@@ -753,6 +1032,13 @@ fn append_db_envs(
             }
         }));
     }
+
+    append_secret_env_ref(
+        env,
+        pooled_database_url,
+        database::DATABASE_URLS_SECRET_NAME,
+        database::POOLED_URL_KEY,
+    );
 }
 
 fn append_jwt_envs(
@@ -0,0 +1,86 @@
+use super::crd::StackAppSpec;
+use crate::services::{
+    auth, document_engine, mailhog, nginx, postgrest, realtime, selenium, storage,
+};
+
+/// Range Kubernetes accepts for `nodePort` values with the default API server flags.
+pub const NODEPORT_RANGE: std::ops::RangeInclusive<u16> = 30000..=32767;
+
+/// Names extra services cannot use because the operator deploys its own workloads under them.
+pub fn reserved_service_names(app_name: &str) -> [&str; 11] {
+    [
+        app_name,
+        nginx::NGINX_NAME,
+        postgrest::REST_NAME,
+        realtime::REALTIME_NAME,
+        storage::STORAGE_NAME,
+        document_engine::DOCUMENT_ENGINE_NAME,
+        selenium::SELENIUM_NAME,
+        mailhog::MAILHOG_NAME,
+        auth::AUTH_NAME,
+        "oauth2-proxy",
+        "minio",
+    ]
+}
+
+/// Every NodePort requested by the spec, keyed by the manifest path that sets it.
+pub fn node_ports(spec: &StackAppSpec) -> Vec<(&'static str, u16)> {
+    let components = &spec.components;
+    let candidates = [
+        (
+            "spec.components.db.expose_db_port",
+            components.db.as_ref().and_then(|db| db.expose_db_port),
+        ),
+        (
+            "spec.components.oidc.expose_auth_port",
+            components
+                .oidc
+                .as_ref()
+                .and_then(|oidc| oidc.expose_auth_port),
+        ),
+        (
+            "spec.components.ingress.port",
+            components.ingress.as_ref().and_then(|ingress| ingress.port),
+        ),
+        (
+            "spec.components.rest.expose_rest_port",
+            components
+                .rest
+                .as_ref()
+                .and_then(|rest| rest.expose_rest_port),
+        ),
+        (
+            "spec.components.selenium.expose_webdriver_port",
+            components
+                .selenium
+                .as_ref()
+                .and_then(|selenium| selenium.expose_webdriver_port),
+        ),
+        (
+            "spec.components.selenium.expose_vnc_port",
+            components
+                .selenium
+                .as_ref()
+                .and_then(|selenium| selenium.expose_vnc_port),
+        ),
+        (
+            "spec.components.mailhog.expose_smtp_port",
+            components
+                .mailhog
+                .as_ref()
+                .and_then(|mailhog| mailhog.expose_smtp_port),
+        ),
+        (
+            "spec.components.mailhog.expose_web_port",
+            components
+                .mailhog
+                .as_ref()
+                .and_then(|mailhog| mailhog.expose_web_port),
+        ),
+    ];
+
+    candidates
+        .into_iter()
+        .filter_map(|(path, port)| port.map(|port| (path, port)))
+        .collect()
+}
@@ -1,18 +1,22 @@
 use crate::error::Error;
 use crate::operator::crd::SupabaseAuthConfig;
-use crate::services::{database, deployment, jwt_secrets};
+use crate::services::{credentials, database, deployment, jwt_secrets};
 use k8s_openapi::api::apps::v1::Deployment as KubeDeployment;
-use k8s_openapi::api::core::v1::Service;
-use kube::api::DeleteParams;
+use k8s_openapi::api::core::v1::{Secret, Service};
+use kube::api::{DeleteParams, Patch, PatchParams};
 use kube::{Api, Client};
 use serde_json::json;
+use std::collections::BTreeMap;
 
 pub const AUTH_NAME: &str = "auth";
 pub const AUTH_IMAGE: &str = "supabase/gotrue:v2.185.0";
 pub const AUTH_PORT: u16 = 9999;
 const AUTH_INIT_IMAGE: &str = "postgres:16-alpine";
 const AUTH_ADMIN_USER: &str = "supabase_auth_admin";
-const AUTH_ADMIN_PASSWORD: &str = "testpassword";
+/// Login of the `supabase_auth_admin` role GoTrue connects as.
+pub const AUTH_ADMIN_SECRET_NAME: &str = "auth-db-admin";
+pub const AUTH_ADMIN_USERNAME_KEY: &str = "username";
+pub const AUTH_ADMIN_PASSWORD_KEY: &str = "password";
 
 pub async fn deploy(
     client: Client,
@@ -21,12 +25,80 @@ pub async fn deploy(
     config: &SupabaseAuthConfig,
 ) -> Result<(), Error> {
     jwt_secrets::ensure_secret(client.clone(), namespace).await?;
+    ensure_secret(client.clone(), namespace).await?;
+    deployment::deployment(client, service_deployment(app_name, config), namespace).await
+}
+
+/// Creates the `auth-db-admin` secret, keeping the password it already has.
+async fn ensure_secret(client: Client, namespace: &str) -> Result<(), Error> {
+    let secret_api: Api<Secret> = Api::namespaced(client, namespace);
+    let existing = secret_api.get_opt(AUTH_ADMIN_SECRET_NAME).await?;
+
+    let string_data = secret_data(|key| {
+        existing
+            .as_ref()
+            .and_then(|secret| read_secret_field(secret, key))
+    });
+
+    let secret_manifest = json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": {
+            "name": AUTH_ADMIN_SECRET_NAME,
+            "namespace": namespace
+        },
+        "stringData": string_data
+    });
+
+    secret_api
+        .patch(
+            AUTH_ADMIN_SECRET_NAME,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(secret_manifest),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Contents of the `auth-db-admin` secret, keeping the password `existing` already has.
+pub fn secret_data(existing: impl Fn(&str) -> Option<String>) -> BTreeMap<&'static str, String> {
+    BTreeMap::from([
+        (AUTH_ADMIN_USERNAME_KEY, AUTH_ADMIN_USER.to_string()),
+        (
+            AUTH_ADMIN_PASSWORD_KEY,
+            existing(AUTH_ADMIN_PASSWORD_KEY).unwrap_or_else(credentials::password),
+        ),
+    ])
+}
+
+fn admin_password_env(name: &str) -> serde_json::Value {
+    json!({
+        "name": name,
+        "valueFrom": {
+            "secretKeyRef": {
+                "name": AUTH_ADMIN_SECRET_NAME,
+                "key": AUTH_ADMIN_PASSWORD_KEY
+            }
+        }
+    })
+}
 
-    let cluster_rw_service = database::cluster_rw_service_name(app_name);
+/// GoTrue deployment, with an init container that creates its admin role and schema and
+/// sets the role's password from `auth-db-admin`, so installs that predate the secret
+/// move off the old fixed password on their next rollout.
+pub fn service_deployment(
+    app_name: &str,
+    config: &SupabaseAuthConfig,
+) -> deployment::ServiceDeployment {
     let db_name = database::database_name(app_name);
 
     let confirm_email = config.confirm_email.unwrap_or(true);
     let mut env = vec![
+        database::host_env("GOTRUE_DB_HOST"),
+        database::port_env("GOTRUE_DB_PORT"),
+        database::sslmode_env("GOTRUE_DB_SSLMODE"),
+        admin_password_env("GOTRUE_DB_PASSWORD"),
         json!({"name": "GOTRUE_API_PORT", "value": AUTH_PORT.to_string()}),
         json!({"name": "GOTRUE_DB_DRIVER", "value": "postgres"}),
         json!({"name": "API_EXTERNAL_URL", "value": config.api_external_url.clone()}),
@@ -37,10 +109,8 @@ pub async fn deploy(
         json!({
             "name": "GOTRUE_DB_DATABASE_URL",
             "value": format!(
-                "postgres://{}:{}@{}:5432/{}?sslmode=disable",
+                "postgres://{}:$(GOTRUE_DB_PASSWORD)@$(GOTRUE_DB_HOST):$(GOTRUE_DB_PORT)/{}?sslmode=$(GOTRUE_DB_SSLMODE)",
                 AUTH_ADMIN_USER,
-                AUTH_ADMIN_PASSWORD,
-                cluster_rw_service,
                 db_name
             )
         }),
@@ -62,8 +132,8 @@ pub async fn deploy(
     let init_container = deployment::InitContainer {
         image_name: AUTH_INIT_IMAGE.to_string(),
         env: vec![
-            json!({"name": "PGHOST", "value": cluster_rw_service}),
-            json!({"name": "PGPORT", "value": "5432"}),
+            database::host_env("PGHOST"),
+            database::port_env("PGPORT"),
             json!({"name": "PGDATABASE", "value": db_name}),
             json!({
                 "name": "PGUSER",
@@ -83,39 +153,35 @@ pub async fn deploy(
                     }
                 }
             }),
+            admin_password_env("AUTH_ADMIN_PASSWORD"),
         ],
         command: Some(deployment::Command {
             command: vec!["/bin/sh".to_string(), "-c".to_string()],
             args: vec![
                 format!(
-                    "psql -v ON_ERROR_STOP=1 -c \"DO \\$\\$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = '{user}') THEN CREATE USER {user} NOINHERIT CREATEROLE LOGIN NOREPLICATION PASSWORD '{password}'; END IF; END \\$\\$;\" \
+                    "psql -v ON_ERROR_STOP=1 -c \"DO \\$\\$ BEGIN IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = '{user}') THEN CREATE USER {user} NOINHERIT CREATEROLE LOGIN NOREPLICATION; END IF; END \\$\\$;\" \
+                     -c \"ALTER USER {user} WITH PASSWORD '$AUTH_ADMIN_PASSWORD';\" \
                      -c \"CREATE SCHEMA IF NOT EXISTS auth AUTHORIZATION {user};\" \
                      -c \"GRANT CREATE ON DATABASE \\\"{db}\\\" TO {user};\" \
                      -c \"ALTER USER {user} SET search_path = 'auth';\"",
                     user = AUTH_ADMIN_USER,
-                    password = AUTH_ADMIN_PASSWORD,
                     db = db_name
                 ),
             ],
         }),
     };
 
-    deployment::deployment(
-        client,
-        deployment::ServiceDeployment {
-            name: AUTH_NAME.to_string(),
-            image_name: AUTH_IMAGE.to_string(),
-            replicas: 1,
-            port: Some(AUTH_PORT),
-            env,
-            init_containers: vec![init_container],
-            command: None,
-            volume_mounts: vec![],
-            volumes: vec![],
-        },
-        namespace,
-    )
-    .await
+    deployment::ServiceDeployment {
+        name: AUTH_NAME.to_string(),
+        image_name: AUTH_IMAGE.to_string(),
+        replicas: 1,
+        port: Some(AUTH_PORT),
+        env,
+        init_containers: vec![init_container],
+        command: None,
+        volume_mounts: vec![],
+        volumes: vec![],
+    }
 }
 
 pub async fn delete(client: Client, namespace: &str) -> Result<(), Error> {
@@ -126,10 +192,32 @@ pub async fn delete(client: Client, namespace: &str) -> Result<(), Error> {
             .await?;
     }
 
-    let services: Api<Service> = Api::namespaced(client, namespace);
+    let services: Api<Service> = Api::namespaced(client.clone(), namespace);
     if services.get(AUTH_NAME).await.is_ok() {
         services.delete(AUTH_NAME, &DeleteParams::default()).await?;
     }
 
+    let secrets: Api<Secret> = Api::namespaced(client, namespace);
+    if secrets.get(AUTH_ADMIN_SECRET_NAME).await.is_ok() {
+        secrets
+            .delete(AUTH_ADMIN_SECRET_NAME, &DeleteParams::default())
+            .await?;
+    }
+
     Ok(())
 }
+
+fn read_secret_field(secret: &Secret, key: &str) -> Option<String> {
+    if let Some(data) = &secret.data {
+        if let Some(value) = data.get(key) {
+            if let Ok(val) = String::from_utf8(value.0.clone()) {
+                return Some(val);
+            }
+        }
+    }
+
+    secret
+        .string_data
+        .as_ref()
+        .and_then(|map| map.get(key).cloned())
+}
@@ -0,0 +1,14 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Hex-encoded SHA-256 of `value` in its JSON form.
+///
+/// These hashes are stored in annotations and compared across operator releases, so they
+/// cannot come from `DefaultHasher`, whose output may change with the Rust version.
+pub fn content_hash<T: Serialize + ?Sized>(value: &T) -> String {
+    let bytes = serde_json::to_vec(value).unwrap_or_default();
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,37 @@
+use rand::Rng;
+
+/// `A-Z`, `a-z` and `0-9`: safe inside connection URLs, SQL literals and shell strings
+/// without any quoting.
+pub const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Length and alphabet of a generated credential.
+#[derive(Clone, Copy)]
+pub struct CredentialSpec {
+    pub length: usize,
+    pub alphabet: &'static [u8],
+}
+
+/// Passwords, signing secrets and access keys: 32 alphanumeric characters, about 190 bits.
+pub const PASSWORD: CredentialSpec = CredentialSpec {
+    length: 32,
+    alphabet: ALPHANUMERIC,
+};
+
+/// Draws `spec.length` characters uniformly from `spec.alphabet` using the thread-local
+/// generator, a ChaCha-based CSPRNG seeded from the operating system.
+pub fn generate(spec: CredentialSpec) -> String {
+    let mut rng = rand::rng();
+    (0..spec.length)
+        .map(|_| char::from(spec.alphabet[rng.random_range(0..spec.alphabet.len())]))
+        .collect()
+}
+
+pub fn password() -> String {
+    generate(PASSWORD)
+}
+
+/// Whether `password` came from the generator used before this one: five random bytes
+/// written out as decimal numbers, so 5 to 15 digits.
+pub fn is_legacy_password(password: &str) -> bool {
+    (5..=15).contains(&password.len()) && password.bytes().all(|byte| byte.is_ascii_digit())
+}
@@ -1,19 +1,28 @@
 use std::collections::BTreeMap;
 
 use crate::error::Error;
+use crate::operator::crd::{DataDurability, DatabaseStatus, DbConfig, SynchronousMethod};
+use crate::services::{
+    credentials, database_cdc, database_extensions, database_monitoring, database_parameters,
+    database_pooler,
+};
 use k8s_openapi::api::core::v1::Secret;
-use kube::api::{DeleteParams, ObjectMeta};
-use kube::CustomResource;
+use kube::api::{DeleteParams, ObjectMeta, Patch, PatchParams};
 use kube::{
     api::{Api, PostParams},
     Client,
 };
+use kube::{CustomResource, ResourceExt};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 
 #[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct BootstrapSpec {
-    pub initdb: InitDBSpec,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initdb: Option<InitDBSpec>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery: Option<RecoverySpec>,
 }
 
 #[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
@@ -27,16 +36,103 @@ pub struct InitDBSpec {
     pub post_init_application_sql: Option<Vec<String>>,
 }
 
+/// Bootstraps a cluster from an existing backup instead of running initdb.
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct RecoverySpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<BackupReference>,
+    /// Name of an entry in `externalClusters` whose WAL archive is replayed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(rename = "recoveryTarget", skip_serializing_if = "Option::is_none")]
+    pub recovery_target: Option<RecoveryTarget>,
+    pub database: String,
+    pub owner: String,
+    pub secret: SecretSpec,
+}
+
+/// Where WAL replay stops during recovery.
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct RecoveryTarget {
+    /// RFC 3339 timestamp.
+    #[serde(rename = "targetTime")]
+    pub target_time: Option<String>,
+}
+
+/// Another cluster's object store, used as a recovery source.
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ExternalCluster {
+    pub name: String,
+    #[serde(rename = "barmanObjectStore")]
+    pub barman_object_store: Option<BarmanObjectStoreConfiguration>,
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BackupReference {
+    pub name: String,
+}
+
 #[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct SecretSpec {
     pub name: String,
 }
 
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct SecretKeySelector {
+    pub name: String,
+    pub key: String,
+}
+
+/// Continuous WAL archiving and base backups to S3-compatible object storage.
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BackupConfiguration {
+    #[serde(rename = "barmanObjectStore")]
+    pub barman_object_store: BarmanObjectStoreConfiguration,
+    #[serde(rename = "retentionPolicy")]
+    pub retention_policy: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BarmanObjectStoreConfiguration {
+    #[serde(rename = "destinationPath")]
+    pub destination_path: String,
+    #[serde(rename = "endpointURL")]
+    pub endpoint_url: Option<String>,
+    /// Folder under `destinationPath`; defaults to the cluster name.
+    #[serde(rename = "serverName")]
+    pub server_name: Option<String>,
+    #[serde(rename = "s3Credentials")]
+    pub s3_credentials: S3Credentials,
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct S3Credentials {
+    #[serde(rename = "accessKeyId")]
+    pub access_key_id: SecretKeySelector,
+    #[serde(rename = "secretAccessKey")]
+    pub secret_access_key: SecretKeySelector,
+    pub region: Option<SecretKeySelector>,
+}
+
 #[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct StorageSpec {
     pub size: String,
+    #[serde(rename = "storageClass", skip_serializing_if = "Option::is_none")]
+    pub storage_class: Option<String>,
 }
 
+pub const DATABASE_URLS_SECRET_NAME: &str = "database-urls";
+/// Key in `database-urls` holding the read-write host, so services follow a restored cluster.
+pub const DATABASE_HOST_KEY: &str = "host";
+/// Keys in `database-urls` holding the port and libpq sslmode the database is reached with.
+pub const DATABASE_PORT_KEY: &str = "port";
+pub const DATABASE_SSLMODE_KEY: &str = "sslmode";
+pub const DEFAULT_DB_PORT: u16 = 5432;
+const DEFAULT_SSLMODE: &str = "disable";
+const DEFAULT_DB_STORAGE_SIZE: &str = "20Gi";
+/// Key in `database-urls` holding the application URL through the connection pooler.
+pub const POOLED_URL_KEY: &str = "application-pooled-url";
+
 pub const CNPG_INSTALL_HINT: &str = "CloudNativePG operator is not installed. Run `stack-cli init` or apply `crates/stack-cli/config/cnpg-1.28.1.yaml` before reconciling.";
 
 pub fn cluster_resource_name(app_name: &str) -> String {
@@ -47,10 +143,65 @@ pub fn cluster_rw_service_name(app_name: &str) -> String {
     format!("{}-rw", cluster_resource_name(app_name))
 }
 
+/// Host for `readonly-url`: the replicas once there are any, otherwise the primary.
+pub fn readonly_host(cluster_name: &str, instances: i32) -> String {
+    if instances > 1 {
+        format!("{}-ro", cluster_name)
+    } else {
+        format!("{}-rw", cluster_name)
+    }
+}
+
 pub fn database_name(app_name: &str) -> String {
     app_name.to_string()
 }
 
+/// Env var reading the read-write database host from `database-urls`.
+pub fn host_env(name: &str) -> Value {
+    database_urls_env(name, DATABASE_HOST_KEY)
+}
+
+/// Env var reading the database port from `database-urls`.
+pub fn port_env(name: &str) -> Value {
+    database_urls_env(name, DATABASE_PORT_KEY)
+}
+
+/// Env var reading the libpq sslmode from `database-urls`.
+pub fn sslmode_env(name: &str) -> Value {
+    database_urls_env(name, DATABASE_SSLMODE_KEY)
+}
+
+fn database_urls_env(name: &str, key: &str) -> Value {
+    json!({
+        "name": name,
+        "valueFrom": {
+            "secretKeyRef": {
+                "name": DATABASE_URLS_SECRET_NAME,
+                "key": key
+            }
+        }
+    })
+}
+
+/// The cluster `database-urls` points at. This is the app cluster unless a
+/// point-in-time restore has switched the app over to a recovered one.
+pub async fn active_cluster_name(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+) -> Result<String, Error> {
+    let secret_api: Api<Secret> = Api::namespaced(client, namespace);
+    let host = secret_api
+        .get_opt(DATABASE_URLS_SECRET_NAME)
+        .await?
+        .and_then(|secret| read_secret_field(&secret, DATABASE_HOST_KEY));
+    Ok(host
+        .as_deref()
+        .and_then(|host| host.strip_suffix("-rw"))
+        .map(str::to_string)
+        .unwrap_or_else(|| cluster_resource_name(app_name)))
+}
+
 /// Corresponds to the Cluster resource
 #[derive(CustomResource, Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
 #[kube(
@@ -58,6 +209,7 @@ pub fn database_name(app_name: &str) -> String {
     version = "v1",
     kind = "Cluster",
     plural = "clusters",
+    status = "ClusterStatus",
     derive = "PartialEq",
     namespaced
 )]
@@ -67,30 +219,291 @@ pub struct ClusterSpec {
     pub instances: i32,
     pub bootstrap: BootstrapSpec,
     pub storage: StorageSpec,
+    #[serde(rename = "walStorage", skip_serializing_if = "Option::is_none")]
+    pub wal_storage: Option<StorageSpec>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<BackupConfiguration>,
+    #[serde(rename = "externalClusters", skip_serializing_if = "Option::is_none")]
+    pub external_clusters: Option<Vec<ExternalCluster>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub postgresql: Option<PostgresConfiguration>,
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema, Default)]
+pub struct PostgresConfiguration {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub synchronous: Option<SynchronousReplicaConfiguration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared_preload_libraries: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pg_hba: Option<Vec<String>>,
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct SynchronousReplicaConfiguration {
+    pub method: String,
+    pub number: i32,
+    #[serde(rename = "dataDurability")]
+    pub data_durability: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema, Default)]
+pub struct ClusterStatus {
+    pub phase: Option<String>,
+    pub instances: Option<i32>,
+    #[serde(rename = "readyInstances")]
+    pub ready_instances: Option<i32>,
+    #[serde(rename = "currentPrimary")]
+    pub current_primary: Option<String>,
+    #[serde(rename = "instanceNames", default)]
+    pub instance_names: Vec<String>,
+    /// Image the instances run, which trails `imageName` during a rolling update.
+    pub image: Option<String>,
+}
+
+/// Data and optional WAL volumes for the app cluster.
+pub fn storage_settings(
+    config: Option<&DbConfig>,
+) -> Result<(StorageSpec, Option<StorageSpec>), Error> {
+    let storage_config = config.and_then(|db| db.storage.clone()).unwrap_or_default();
+    let size = storage_config
+        .size
+        .unwrap_or_else(|| DEFAULT_DB_STORAGE_SIZE.to_string());
+    validate_quantity("components.db.storage.size", &size)?;
+    let storage = StorageSpec {
+        size,
+        storage_class: storage_config.storage_class.clone(),
+    };
+
+    let wal_storage = match storage_config.wal_size {
+        Some(wal_size) => {
+            validate_quantity("components.db.storage.wal_size", &wal_size)?;
+            Some(StorageSpec {
+                size: wal_size,
+                storage_class: storage_config
+                    .wal_storage_class
+                    .or(storage_config.storage_class),
+            })
+        }
+        None => None,
+    };
+
+    Ok((storage, wal_storage))
+}
+
+/// Whether `desired` is a volume expansion of `current`. Volumes cannot shrink or
+/// move to another storage class in place.
+fn grows(field: &str, current: &StorageSpec, desired: &StorageSpec) -> Result<bool, Error> {
+    if desired.storage_class.is_some() && desired.storage_class != current.storage_class {
+        return Err(Error::Other(format!(
+            "components.db.{}storage_class cannot change after the cluster is created (it is {})",
+            field,
+            current
+                .storage_class
+                .as_deref()
+                .unwrap_or("the default class")
+        )));
+    }
+    let current_bytes = quantity_bytes(&current.size);
+    let desired_bytes = quantity_bytes(&desired.size);
+    match (current_bytes, desired_bytes) {
+        (Some(current_bytes), Some(desired_bytes)) if desired_bytes < current_bytes => {
+            Err(Error::Other(format!(
+                "components.db.{}size cannot shrink from {} to {}; volumes can only grow. Restore into a new cluster to use a smaller volume",
+                field,
+                current.size,
+                desired.size
+            )))
+        }
+        (Some(current_bytes), Some(desired_bytes)) => Ok(desired_bytes > current_bytes),
+        _ => Ok(false),
+    }
+}
+
+fn validate_quantity(field: &str, value: &str) -> Result<(), Error> {
+    match quantity_bytes(value) {
+        Some(bytes) if bytes > 0 => Ok(()),
+        _ => Err(Error::Other(format!(
+            "{} must be a size such as 20Gi or 500Mi, got {}",
+            field, value
+        ))),
+    }
+}
+
+/// Bytes in a Kubernetes quantity with a binary or decimal suffix, e.g. 20Gi or 10G.
+fn quantity_bytes(value: &str) -> Option<u128> {
+    let value = value.trim();
+    let split_at = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split_at);
+    let amount = amount.parse::<u128>().ok()?;
+    let multiplier: u128 = match unit {
+        "" => 1,
+        "k" => 1_000,
+        "M" => 1_000_000,
+        "G" => 1_000_000_000,
+        "T" => 1_000_000_000_000,
+        "P" => 1_000_000_000_000_000,
+        "Ki" => 1 << 10,
+        "Mi" => 1 << 20,
+        "Gi" => 1 << 30,
+        "Ti" => 1 << 40,
+        "Pi" => 1 << 50,
+        _ => return None,
+    };
+    amount.checked_mul(multiplier)
+}
+
+/// Instance count and synchronous replication settings for the app cluster.
+pub fn replication_settings(
+    config: Option<&DbConfig>,
+) -> Result<(i32, Option<SynchronousReplicaConfiguration>), Error> {
+    let instances = config.and_then(|db| db.instances).unwrap_or(1);
+    if instances < 1 {
+        return Err(Error::Other(
+            "components.db.instances must be at least 1".to_string(),
+        ));
+    }
+    let Some(sync) = config.and_then(|db| db.synchronous_replication.as_ref()) else {
+        return Ok((instances, None));
+    };
+
+    let number = sync.number.unwrap_or(1);
+    if number < 1 || number >= instances {
+        return Err(Error::Other(format!(
+            "components.db.synchronous_replication.number must be between 1 and {} (instances - 1), got {}",
+            instances - 1,
+            number
+        )));
+    }
+    let method = match sync.method.unwrap_or_default() {
+        SynchronousMethod::Any => "any",
+        SynchronousMethod::First => "first",
+    };
+    let data_durability = match sync.data_durability.unwrap_or_default() {
+        DataDurability::Required => "required",
+        DataDurability::Preferred => "preferred",
+    };
+
+    Ok((
+        instances,
+        Some(SynchronousReplicaConfiguration {
+            method: method.to_string(),
+            number,
+            data_durability: Some(data_durability.to_string()),
+        }),
+    ))
+}
+
+/// The `postgresql` section of the app cluster: synchronous replication plus the
+/// parameters, preload libraries and pg_hba lines from `components.db.postgresql`, and
+/// the parameters `components.db.monitoring` needs.
+pub fn postgresql_settings(
+    config: Option<&DbConfig>,
+    synchronous: Option<SynchronousReplicaConfiguration>,
+) -> Result<Option<PostgresConfiguration>, Error> {
+    let mut settings = config
+        .and_then(|db| db.postgresql.clone())
+        .unwrap_or_default();
+    let problems = database_parameters::problems(&settings);
+    if !problems.is_empty() {
+        let messages: Vec<String> = problems.into_iter().map(|(_, message)| message).collect();
+        return Err(Error::Other(format!(
+            "components.db.postgresql is invalid: {}",
+            messages.join("; ")
+        )));
+    }
+    for (name, value) in database_monitoring::parameters(config)
+        .into_iter()
+        .chain(database_cdc::parameters(config))
+    {
+        settings.parameters.entry(name).or_insert(value);
+    }
+
+    let postgresql = PostgresConfiguration {
+        synchronous,
+        parameters: (!settings.parameters.is_empty()).then_some(settings.parameters),
+        shared_preload_libraries: (!settings.shared_preload_libraries.is_empty())
+            .then_some(settings.shared_preload_libraries),
+        pg_hba: (!settings.pg_hba.is_empty()).then_some(settings.pg_hba),
+    };
+    if postgresql == PostgresConfiguration::default() {
+        return Ok(None);
+    }
+    Ok(Some(postgresql))
 }
 
+/// Creates the app cluster, or brings the settings that can change after creation in
+/// line with the manifest, and reports how the cluster is doing.
+///
+/// `manifest_changed` tells whether the StackApp changed since it was last reconciled;
+/// when it has not, any difference from the live cluster is drift and gets reported.
 pub async fn deploy(
     client: Client,
     namespace: &str,
     app_name: &str,
-    disk_size: i32,
-    image_name: &Option<String>,
-    insecure_override_passwords: &Option<String>,
-) -> Result<Option<String>, Error> {
-    // If the cluster config exists, then do nothing.
+    config: Option<&DbConfig>,
+    manifest_changed: bool,
+) -> Result<DatabaseStatus, Error> {
+    let image_name = config.and_then(|db| db.image_name.clone());
+    let insecure_override_passwords = config.and_then(|db| db.danger_override_password.clone());
+    let (instances, synchronous) = replication_settings(config)?;
+    let postgresql = postgresql_settings(config, synchronous)?;
+    let (extensions, _) = database_extensions::extensions(config)?;
+    let (storage, wal_storage) = storage_settings(config)?;
+
     let cluster_api: Api<Cluster> = Api::namespaced(client.clone(), namespace);
-    let cluster_name = cluster_resource_name(app_name);
+    let secret_api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let cluster_name = active_cluster_name(client, namespace, app_name).await?;
     let db_name = database_name(app_name);
-    let cluster_rw_service = cluster_rw_service_name(app_name);
+    let pooled_host = database_pooler::pooled_host(
+        app_name,
+        &cluster_name,
+        config.and_then(|db| db.pooler.as_ref()),
+    );
     let cluster = cluster_api.get(cluster_name.as_str()).await;
-    if cluster.is_ok() {
-        return Ok(None);
+    if let Ok(cluster) = cluster {
+        let desired = ClusterSpec {
+            image_name,
+            instances,
+            storage,
+            wal_storage,
+            postgresql,
+            ..cluster.spec.clone()
+        };
+        let status = reconcile_cluster(&cluster_api, &cluster, &desired, manifest_changed).await?;
+        ensure_urls(&secret_api, &cluster_name, instances, &pooled_host).await?;
+        return Ok(status);
+    }
+
+    // Credentials without a cluster means the cluster was deleted from under the app;
+    // running initdb now would create roles that no longer match the stored passwords.
+    if secret_api.get(DATABASE_URLS_SECRET_NAME).await.is_ok() {
+        let message = format!(
+            "Cluster {} is missing but {} exists; delete the secret to start with an empty database",
+            cluster_name, DATABASE_URLS_SECRET_NAME
+        );
+        eprintln!("{}", message);
+        return Ok(DatabaseStatus {
+            cluster: cluster_name,
+            instances,
+            message: Some(message),
+            ..Default::default()
+        });
     }
-    let app_database_password: String = insecure_override_passwords.clone().unwrap_or(rand_hex());
-    let readonly_database_password: String =
-        insecure_override_passwords.clone().unwrap_or(rand_hex());
-    let dbowner_password: String = insecure_override_passwords.clone().unwrap_or(rand_hex());
-    let authenticator_password: String = insecure_override_passwords.clone().unwrap_or(rand_hex());
+
+    let password = || {
+        insecure_override_passwords
+            .clone()
+            .unwrap_or_else(credentials::password)
+    };
+    let app_database_password = password();
+    let readonly_database_password = password();
+    let dbowner_password = password();
+    let authenticator_password = password();
 
     let cluster = Cluster {
         metadata: ObjectMeta {
@@ -99,45 +512,31 @@ pub async fn deploy(
             ..Default::default()
         },
         spec: ClusterSpec {
-            image_name: image_name.clone(),
-            instances: 1,
+            image_name,
+            instances,
             bootstrap: BootstrapSpec {
-                initdb: InitDBSpec {
+                initdb: Some(InitDBSpec {
                     database: db_name.clone(),
                     owner: "db-owner".to_string(),
                     secret: SecretSpec {
                         name: "db-owner".to_string(),
                     },
-                    post_init_application_sql: Some(vec![
-                        "CREATE EXTENSION IF NOT EXISTS vector".to_string()
-                    ]),
-                    post_init_sql: Some(vec![
-                        "ALTER ROLE \"db-owner\" WITH SUPERUSER CREATEROLE CREATEDB".to_string(),
-                        "CREATE SCHEMA IF NOT EXISTS realtime".to_string(),
-                        "ALTER SCHEMA realtime OWNER TO \"db-owner\"".to_string(),
-                        format!(
-                            "CREATE ROLE application_user LOGIN ENCRYPTED PASSWORD '{}'",
-                            app_database_password
-                        ),
-                        format!(
-                            "CREATE ROLE application_readonly LOGIN ENCRYPTED PASSWORD '{}'",
-                            readonly_database_password
-                        ),
-                        "CREATE ROLE authenticated NOLOGIN".to_string(),
-                        "CREATE ROLE anon NOLOGIN".to_string(),
-                        "CREATE ROLE service_role NOLOGIN NOINHERIT BYPASSRLS".to_string(),
-                        format!(
-                            "CREATE ROLE authenticator LOGIN ENCRYPTED PASSWORD '{}'",
-                            authenticator_password
-                        ),
-                        "GRANT anon TO authenticator".to_string(),
-                    ]),
-                },
-            },
-            storage: StorageSpec {
-                size: format!("{}Gi", disk_size),
+                    post_init_application_sql: Some(post_init_application_sql(&extensions)),
+                    post_init_sql: Some(post_init_sql(
+                        &app_database_password,
+                        &readonly_database_password,
+                        &authenticator_password,
+                    )),
+                }),
+                recovery: None,
             },
+            storage,
+            wal_storage,
+            backup: None,
+            external_clusters: None,
+            postgresql,
         },
+        status: None,
     };
 
     match cluster_api.create(&PostParams::default(), &cluster).await {
@@ -148,39 +547,21 @@ pub async fn deploy(
         Err(err) => return Err(err.into()),
     }
 
-    let mut secret_data = BTreeMap::new();
-    secret_data.insert(
-        "migrations-url".to_string(),
-        format!(
-            "postgres://db-owner:{}@{}:5432/{}?sslmode=disable",
-            dbowner_password, cluster_rw_service, db_name
-        ),
-    );
-    secret_data.insert(
-        "application-url".to_string(),
-        format!(
-            "postgres://application_user:{}@{}:5432/{}?sslmode=disable",
-            app_database_password, cluster_rw_service, db_name
-        ),
-    );
-    secret_data.insert(
-        "readonly-url".to_string(),
-        format!(
-            "postgres://application_readonly:{}@{}:5432/{}?sslmode=disable",
-            readonly_database_password, cluster_rw_service, db_name
-        ),
-    );
-    secret_data.insert(
-        "authenticator-url".to_string(),
-        format!(
-            "postgres://authenticator:{}@{}:5432/{}?sslmode=disable",
-            authenticator_password, cluster_rw_service, db_name
-        ),
+    let secret_data = database_urls(
+        &DatabaseEndpoint::cluster(&cluster_name, instances, &pooled_host),
+        &db_name,
+        &RoleCredentials {
+            dbowner_user: "db-owner".to_string(),
+            dbowner_password: dbowner_password.clone(),
+            application_password: app_database_password,
+            readonly_password: readonly_database_password,
+            authenticator_password: authenticator_password.clone(),
+        },
     );
 
     let db_urls_secret = Secret {
         metadata: ObjectMeta {
-            name: Some("database-urls".to_string()),
+            name: Some(DATABASE_URLS_SECRET_NAME.to_string()),
             namespace: Some(namespace.to_string()),
             ..ObjectMeta::default()
         },
@@ -216,7 +597,6 @@ pub async fn deploy(
         ..Default::default()
     };
 
-    let secret_api: Api<Secret> = Api::namespaced(client, namespace);
     secret_api
         .create(&PostParams::default(), &dbowner_secret)
         .await?;
@@ -227,26 +607,372 @@ pub async fn deploy(
         .create(&PostParams::default(), &db_urls_secret)
         .await?;
 
-    Ok(Some(readonly_database_password))
+    Ok(DatabaseStatus {
+        cluster: cluster_name,
+        instances,
+        message: Some("Cluster created".to_string()),
+        ..Default::default()
+    })
+}
+
+/// Server-side applies the settings stack-cli manages on an existing cluster. Image
+/// changes roll out through CloudNativePG's rolling update, replicas first.
+async fn reconcile_cluster(
+    cluster_api: &Api<Cluster>,
+    cluster: &Cluster,
+    desired: &ClusterSpec,
+    manifest_changed: bool,
+) -> Result<DatabaseStatus, Error> {
+    let cluster_name = cluster.name_any();
+    let live = &cluster.spec;
+
+    let storage_grows = grows("storage.", &live.storage, &desired.storage)?;
+    let wal_grows = match (&live.wal_storage, &desired.wal_storage) {
+        (Some(current), Some(wanted)) => grows("storage.wal_", current, wanted)?,
+        (None, Some(_)) => true,
+        (Some(_), None) => {
+            return Err(Error::Other(format!(
+                "Cluster {} has a separate WAL volume; components.db.storage.wal_size cannot be removed",
+                cluster_name
+            )));
+        }
+        (None, None) => false,
+    };
+
+    let mut differences = Vec::new();
+    if live.instances != desired.instances {
+        differences.push(format!(
+            "instances is {}, expected {}",
+            live.instances, desired.instances
+        ));
+    }
+    if desired.image_name.is_some() && live.image_name != desired.image_name {
+        differences.push(format!(
+            "imageName is {}, expected {}",
+            live.image_name.as_deref().unwrap_or("the operator default"),
+            desired.image_name.as_deref().unwrap_or_default()
+        ));
+    }
+    let live_postgresql = live.postgresql.clone().unwrap_or_default();
+    let desired_postgresql = desired.postgresql.clone().unwrap_or_default();
+    if live_postgresql.synchronous != desired_postgresql.synchronous {
+        differences.push("postgresql.synchronous differs from the manifest".to_string());
+    }
+    // CloudNativePG fills in parameters of its own, so only the manifest's are compared.
+    let live_parameters = live_postgresql.parameters.unwrap_or_default();
+    for (name, value) in desired_postgresql.parameters.iter().flatten() {
+        if live_parameters.get(name) != Some(value) {
+            differences.push(format!(
+                "postgresql.parameters.{} is {}, expected {}",
+                name,
+                live_parameters
+                    .get(name)
+                    .map(String::as_str)
+                    .unwrap_or("unset"),
+                value
+            ));
+        }
+    }
+    if live_postgresql.shared_preload_libraries.unwrap_or_default()
+        != desired_postgresql
+            .shared_preload_libraries
+            .clone()
+            .unwrap_or_default()
+    {
+        differences
+            .push("postgresql.shared_preload_libraries differs from the manifest".to_string());
+    }
+    if live_postgresql.pg_hba.unwrap_or_default()
+        != desired_postgresql.pg_hba.clone().unwrap_or_default()
+    {
+        differences.push("postgresql.pg_hba differs from the manifest".to_string());
+    }
+    if storage_grows {
+        differences.push(format!(
+            "storage.size is {}, expected {}",
+            live.storage.size, desired.storage.size
+        ));
+    }
+    if wal_grows {
+        differences.push("walStorage differs from the manifest".to_string());
+    }
+
+    // With an unchanged manifest, differences were made to the Cluster directly.
+    let drift = if manifest_changed {
+        Vec::new()
+    } else {
+        differences.clone()
+    };
+    for difference in &drift {
+        eprintln!(
+            "Cluster {} drifted: {}; reverting",
+            cluster_name, difference
+        );
+    }
+
+    // Applying on every manifest change also drops parameters removed from the manifest.
+    if manifest_changed || !differences.is_empty() {
+        let mut spec = Map::new();
+        spec.insert("instances".to_string(), json!(desired.instances));
+        if let Some(image_name) = &desired.image_name {
+            spec.insert("imageName".to_string(), json!(image_name));
+        }
+        if desired_postgresql != PostgresConfiguration::default() {
+            spec.insert("postgresql".to_string(), json!(desired_postgresql));
+        }
+        spec.insert(
+            "storage".to_string(),
+            json!({ "size": desired.storage.size }),
+        );
+        if let Some(wal_storage) = &desired.wal_storage {
+            spec.insert("walStorage".to_string(), json!(wal_storage));
+        }
+        let patch = json!({
+            "apiVersion": "postgresql.cnpg.io/v1",
+            "kind": "Cluster",
+            "metadata": { "name": cluster_name },
+            "spec": spec
+        });
+        cluster_api
+            .patch(
+                &cluster_name,
+                &PatchParams::apply(crate::MANAGER).force(),
+                &Patch::Apply(patch),
+            )
+            .await?;
+    }
+
+    let status = cluster.status.clone().unwrap_or_default();
+    let ready = status.ready_instances.unwrap_or(0);
+    let message = match &desired.image_name {
+        Some(image) if status.image.as_ref() != Some(image) => Some(format!(
+            "Rolling update to {}: {}/{} instances ready",
+            image, ready, desired.instances
+        )),
+        _ if ready < desired.instances => {
+            Some(format!("{}/{} instances ready", ready, desired.instances))
+        }
+        _ => None,
+    };
+
+    Ok(DatabaseStatus {
+        cluster: cluster_name,
+        phase: status.phase,
+        instances: desired.instances,
+        ready_instances: ready,
+        image: status.image,
+        primary: status.current_primary,
+        drift,
+        missing_extensions: Vec::new(),
+        message,
+    })
+}
+
+/// Statements run by initdb as the superuser once the cluster is created.
+pub fn post_init_sql(
+    app_database_password: &str,
+    readonly_database_password: &str,
+    authenticator_password: &str,
+) -> Vec<String> {
+    vec![
+        "ALTER ROLE \"db-owner\" WITH SUPERUSER CREATEROLE CREATEDB".to_string(),
+        "CREATE SCHEMA IF NOT EXISTS realtime".to_string(),
+        "ALTER SCHEMA realtime OWNER TO \"db-owner\"".to_string(),
+        format!(
+            "CREATE ROLE application_user LOGIN ENCRYPTED PASSWORD '{}'",
+            app_database_password
+        ),
+        format!(
+            "CREATE ROLE application_readonly LOGIN ENCRYPTED PASSWORD '{}'",
+            readonly_database_password
+        ),
+        "CREATE ROLE authenticated NOLOGIN".to_string(),
+        "CREATE ROLE anon NOLOGIN".to_string(),
+        "CREATE ROLE service_role NOLOGIN NOINHERIT BYPASSRLS".to_string(),
+        format!(
+            "CREATE ROLE authenticator LOGIN ENCRYPTED PASSWORD '{}'",
+            authenticator_password
+        ),
+        "GRANT anon TO authenticator".to_string(),
+    ]
+}
+
+/// Statements run by initdb inside the application database, creating the manifest's
+/// extensions up front so they exist before the first services connect.
+pub fn post_init_application_sql(extensions: &[String]) -> Vec<String> {
+    vec![database_extensions::extensions_sql(extensions, &[])]
+}
+
+/// Where the application database is reachable.
+pub struct DatabaseEndpoint {
+    /// Read-write host.
+    pub host: String,
+    /// Host serving read-only queries.
+    pub readonly_host: String,
+    /// Host of the connection pooler.
+    pub pooled_host: String,
+    pub port: u16,
+    pub sslmode: String,
+}
+
+impl DatabaseEndpoint {
+    /// Endpoint of a CloudNativePG cluster, which listens without TLS inside the namespace.
+    pub fn cluster(cluster_name: &str, instances: i32, pooled_host: &str) -> Self {
+        DatabaseEndpoint {
+            host: format!("{}-rw", cluster_name),
+            readonly_host: readonly_host(cluster_name, instances),
+            pooled_host: pooled_host.to_string(),
+            port: DEFAULT_DB_PORT,
+            sslmode: DEFAULT_SSLMODE.to_string(),
+        }
+    }
+}
+
+/// Login credentials of the roles `database-urls` connects as.
+pub struct RoleCredentials {
+    /// Role behind `migrations-url`: `db-owner`, or the superuser of an external database.
+    pub dbowner_user: String,
+    pub dbowner_password: String,
+    pub application_password: String,
+    pub readonly_password: String,
+    pub authenticator_password: String,
+}
+
+/// Contents of the `database-urls` secret.
+pub fn database_urls(
+    endpoint: &DatabaseEndpoint,
+    db_name: &str,
+    credentials: &RoleCredentials,
+) -> BTreeMap<String, String> {
+    let url_at = |host: &str, user: &str, password: &str| {
+        format!(
+            "postgres://{}:{}@{}:{}/{}?sslmode={}",
+            user, password, host, endpoint.port, db_name, endpoint.sslmode
+        )
+    };
+    let url = |user: &str, password: &str| url_at(&endpoint.host, user, password);
+
+    let mut secret_data = BTreeMap::new();
+    secret_data.insert(DATABASE_HOST_KEY.to_string(), endpoint.host.clone());
+    secret_data.insert(DATABASE_PORT_KEY.to_string(), endpoint.port.to_string());
+    secret_data.insert(DATABASE_SSLMODE_KEY.to_string(), endpoint.sslmode.clone());
+    secret_data.insert(
+        "migrations-url".to_string(),
+        url(&credentials.dbowner_user, &credentials.dbowner_password),
+    );
+    secret_data.insert(
+        "application-url".to_string(),
+        url("application_user", &credentials.application_password),
+    );
+    secret_data.insert(
+        POOLED_URL_KEY.to_string(),
+        url_at(
+            &endpoint.pooled_host,
+            "application_user",
+            &credentials.application_password,
+        ),
+    );
+    secret_data.insert(
+        "readonly-url".to_string(),
+        url_at(
+            &endpoint.readonly_host,
+            "application_readonly",
+            &credentials.readonly_password,
+        ),
+    );
+    secret_data.insert(
+        "authenticator-url".to_string(),
+        url("authenticator", &credentials.authenticator_password),
+    );
+    secret_data
+}
+
+/// `url` with the host between the credentials and the port replaced.
+fn with_host(url: &str, host: &str) -> String {
+    let Some(at) = url.rfind('@') else {
+        return url.to_string();
+    };
+    let Some(port) = url[at..].find(':') else {
+        return url.to_string();
+    };
+    format!("{}{}{}", &url[..=at], host, &url[at + port..])
 }
 
-pub fn rand_hex() -> String {
-    (0..5).map(|_| rand::random::<u8>().to_string()).collect()
+/// Keeps `database-urls` in line with the running cluster: secrets created before it
+/// carried the host get it added, `readonly-url` follows replicas coming and going and
+/// `application-pooled-url` follows the pooler.
+async fn ensure_urls(
+    secret_api: &Api<Secret>,
+    cluster_name: &str,
+    instances: i32,
+    pooled_host: &str,
+) -> Result<(), Error> {
+    let Some(secret) = secret_api.get_opt(DATABASE_URLS_SECRET_NAME).await? else {
+        return Ok(());
+    };
+    let mut string_data = BTreeMap::new();
+    if read_secret_field(&secret, DATABASE_HOST_KEY).is_none() {
+        string_data.insert(DATABASE_HOST_KEY, format!("{}-rw", cluster_name));
+    }
+    if read_secret_field(&secret, DATABASE_PORT_KEY).is_none() {
+        string_data.insert(DATABASE_PORT_KEY, DEFAULT_DB_PORT.to_string());
+    }
+    if read_secret_field(&secret, DATABASE_SSLMODE_KEY).is_none() {
+        string_data.insert(DATABASE_SSLMODE_KEY, DEFAULT_SSLMODE.to_string());
+    }
+    if let Some(readonly_url) = read_secret_field(&secret, "readonly-url") {
+        let wanted = format!("@{}:", readonly_host(cluster_name, instances));
+        let updated = [
+            format!("@{}-rw:", cluster_name),
+            format!("@{}-ro:", cluster_name),
+        ]
+        .iter()
+        .fold(readonly_url.clone(), |url, current| {
+            url.replace(current, &wanted)
+        });
+        if updated != readonly_url {
+            string_data.insert("readonly-url", updated);
+        }
+    }
+    let pooled_url = read_secret_field(&secret, POOLED_URL_KEY);
+    if let Some(url) = pooled_url
+        .clone()
+        .or_else(|| read_secret_field(&secret, "application-url"))
+    {
+        let updated = with_host(&url, pooled_host);
+        if pooled_url.as_ref() != Some(&updated) {
+            string_data.insert(POOLED_URL_KEY, updated);
+        }
+    }
+    if string_data.is_empty() {
+        return Ok(());
+    }
+    let patch = json!({ "stringData": string_data });
+    secret_api
+        .patch(
+            DATABASE_URLS_SECRET_NAME,
+            &PatchParams::default(),
+            &Patch::Merge(&patch),
+        )
+        .await?;
+    Ok(())
 }
 
 pub async fn delete(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
     // Remove deployments
     let api: Api<Cluster> = Api::namespaced(client.clone(), namespace);
+    let active_cluster = active_cluster_name(client.clone(), namespace, app_name).await?;
     let cluster_name = cluster_resource_name(app_name);
-    if api.get(cluster_name.as_str()).await.is_ok() {
-        api.delete(cluster_name.as_str(), &DeleteParams::default())
-            .await?;
+    for name in [cluster_name, active_cluster] {
+        if api.get(name.as_str()).await.is_ok() {
+            api.delete(name.as_str(), &DeleteParams::default()).await?;
+        }
     }
 
     let secret_api: Api<Secret> = Api::namespaced(client, namespace);
-    if secret_api.get("database-urls").await.is_ok() {
+    if secret_api.get(DATABASE_URLS_SECRET_NAME).await.is_ok() {
         secret_api
-            .delete("database-urls", &DeleteParams::default())
+            .delete(DATABASE_URLS_SECRET_NAME, &DeleteParams::default())
             .await?;
     }
     if secret_api.get("db-owner").await.is_ok() {
@@ -262,3 +988,18 @@ pub async fn delete(client: Client, namespace: &str, app_name: &str) -> Result<(
 
     Ok(())
 }
+
+fn read_secret_field(secret: &Secret, key: &str) -> Option<String> {
+    if let Some(data) = &secret.data {
+        if let Some(value) = data.get(key) {
+            if let Ok(val) = String::from_utf8(value.0.clone()) {
+                return Some(val);
+            }
+        }
+    }
+
+    secret
+        .string_data
+        .as_ref()
+        .and_then(|map| map.get(key).cloned())
+}
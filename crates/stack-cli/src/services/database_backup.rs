@@ -0,0 +1,702 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::operator::crd::{DbBackupConfig, StorageConfig};
+use crate::services::database::{
+    self, BackupConfiguration, BackupReference, BarmanObjectStoreConfiguration, BootstrapSpec,
+    Cluster, ClusterSpec, ExternalCluster, RecoverySpec, RecoveryTarget, S3Credentials,
+    SecretKeySelector, SecretSpec,
+};
+use crate::services::{deployment, storage};
+use k8s_openapi::api::core::v1::Secret;
+use kube::api::{DeleteParams, ListParams, ObjectMeta, Patch, PatchParams, PostParams};
+use kube::{Api, Client, CustomResource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+const DEFAULT_SCHEDULE: &str = "0 0 2 * * *";
+const DEFAULT_RETENTION: &str = "30d";
+const BACKUP_PREFIX: &str = "db-backups";
+const CLUSTER_DELETE_TIMEOUT_SECS: u64 = 300;
+/// Name of the `externalClusters` entry a point-in-time restore replays WAL from.
+const RECOVERY_SOURCE: &str = "origin";
+
+/// Corresponds to the CloudNativePG ScheduledBackup resource
+#[derive(CustomResource, Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+#[kube(
+    group = "postgresql.cnpg.io",
+    version = "v1",
+    kind = "ScheduledBackup",
+    plural = "scheduledbackups",
+    derive = "PartialEq",
+    namespaced
+)]
+pub struct ScheduledBackupSpec {
+    pub schedule: String,
+    pub cluster: ClusterReference,
+    pub immediate: Option<bool>,
+}
+
+/// Corresponds to the CloudNativePG Backup resource
+#[derive(CustomResource, Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+#[kube(
+    group = "postgresql.cnpg.io",
+    version = "v1",
+    kind = "Backup",
+    plural = "backups",
+    status = "BackupStatus",
+    derive = "PartialEq",
+    namespaced
+)]
+pub struct BackupSpec {
+    pub cluster: ClusterReference,
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ClusterReference {
+    pub name: String,
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema, Default)]
+pub struct BackupStatus {
+    pub phase: Option<String>,
+    #[serde(rename = "startedAt")]
+    pub started_at: Option<String>,
+    #[serde(rename = "stoppedAt")]
+    pub stopped_at: Option<String>,
+    #[serde(rename = "backupId")]
+    pub backup_id: Option<String>,
+    pub error: Option<String>,
+}
+
+pub fn scheduled_backup_name(app_name: &str) -> String {
+    format!("{}-backup", database::cluster_resource_name(app_name))
+}
+
+/// Configures WAL archiving and scheduled base backups on the app cluster, or removes
+/// them again when `components.db.backup` is unset.
+pub async fn deploy(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    config: Option<&DbBackupConfig>,
+    storage_config: Option<&StorageConfig>,
+) -> Result<(), Error> {
+    let cluster_api: Api<Cluster> = Api::namespaced(client.clone(), namespace);
+    let scheduled_api: Api<ScheduledBackup> = Api::namespaced(client.clone(), namespace);
+    let cluster_name = database::active_cluster_name(client.clone(), namespace, app_name).await?;
+    let scheduled_name = scheduled_backup_name(app_name);
+
+    let Some(config) = config else {
+        if scheduled_api.get(&scheduled_name).await.is_ok() {
+            scheduled_api
+                .delete(&scheduled_name, &DeleteParams::default())
+                .await?;
+        }
+        if let Ok(cluster) = cluster_api.get(&cluster_name).await {
+            if cluster.spec.backup.is_some() {
+                let patch = json!({ "spec": { "backup": null } });
+                cluster_api
+                    .patch(
+                        &cluster_name,
+                        &PatchParams::default(),
+                        &Patch::Merge(&patch),
+                    )
+                    .await?;
+            }
+        }
+        return Ok(());
+    };
+
+    // The cluster may not exist yet, or may be in the middle of a restore.
+    let Ok(cluster) = cluster_api.get(&cluster_name).await else {
+        return Ok(());
+    };
+
+    let server_name = cluster
+        .spec
+        .backup
+        .as_ref()
+        .and_then(|backup| backup.barman_object_store.server_name.clone());
+    let backup = backup_configuration(
+        client.clone(),
+        namespace,
+        config,
+        storage_config,
+        server_name,
+    )
+    .await?;
+    if cluster.spec.backup.as_ref() != Some(&backup) {
+        let patch = json!({ "spec": { "backup": backup } });
+        cluster_api
+            .patch(
+                &cluster_name,
+                &PatchParams::default(),
+                &Patch::Merge(&patch),
+            )
+            .await?;
+    }
+
+    let scheduled_backup = json!({
+        "apiVersion": "postgresql.cnpg.io/v1",
+        "kind": "ScheduledBackup",
+        "metadata": {
+            "name": scheduled_name,
+            "namespace": namespace
+        },
+        "spec": {
+            "schedule": cron_schedule(config.schedule.as_deref().unwrap_or(DEFAULT_SCHEDULE)),
+            "cluster": { "name": cluster_name },
+            "immediate": true
+        }
+    });
+    scheduled_api
+        .patch(
+            &scheduled_name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(scheduled_backup),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    let api: Api<ScheduledBackup> = Api::namespaced(client, namespace);
+    let name = scheduled_backup_name(app_name);
+    if api.get(&name).await.is_ok() {
+        api.delete(&name, &DeleteParams::default()).await?;
+    }
+    Ok(())
+}
+
+/// Starts an on-demand base backup and returns the name of the Backup resource.
+pub async fn create_backup(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+) -> Result<String, Error> {
+    let cluster_api: Api<Cluster> = Api::namespaced(client.clone(), namespace);
+    let cluster_name = database::active_cluster_name(client.clone(), namespace, app_name).await?;
+    let cluster = cluster_api.get(&cluster_name).await?;
+    if cluster.spec.backup.is_none() {
+        return Err(Error::Other(format!(
+            "Cluster {} has no backup destination; set components.db.backup and run `stack deploy`",
+            cluster_name
+        )));
+    }
+
+    let backup_name = format!("{}-{}", cluster_name, unix_seconds());
+    let backup = Backup {
+        metadata: ObjectMeta {
+            name: Some(backup_name.clone()),
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        },
+        spec: BackupSpec {
+            cluster: ClusterReference { name: cluster_name },
+        },
+        status: None,
+    };
+    let backup_api: Api<Backup> = Api::namespaced(client, namespace);
+    backup_api.create(&PostParams::default(), &backup).await?;
+
+    Ok(backup_name)
+}
+
+/// Starts the backup a `Snapshot` deletion waits for, unless it exists already, and
+/// returns it. `None` when there is no cluster to back up. The name carries part of the
+/// cluster UID, so a later cluster under the same name takes its own final backup.
+pub async fn final_backup(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+) -> Result<Option<Backup>, Error> {
+    let cluster_api: Api<Cluster> = Api::namespaced(client.clone(), namespace);
+    let cluster_name = database::active_cluster_name(client.clone(), namespace, app_name).await?;
+    let Some(cluster) = cluster_api.get_opt(&cluster_name).await? else {
+        return Ok(None);
+    };
+    if cluster.spec.backup.is_none() {
+        return Err(Error::Other(format!(
+            "components.db.deletion_policy is Snapshot but cluster {} has no backup destination; set components.db.backup or pick another policy",
+            cluster_name
+        )));
+    }
+
+    let uid: String = cluster
+        .metadata
+        .uid
+        .as_deref()
+        .unwrap_or_default()
+        .chars()
+        .take(8)
+        .collect();
+    let backup_name = format!("{}-final-{}", cluster_name, uid);
+    let backup_api: Api<Backup> = Api::namespaced(client, namespace);
+    if let Some(backup) = backup_api.get_opt(&backup_name).await? {
+        return Ok(Some(backup));
+    }
+    let backup = Backup {
+        metadata: ObjectMeta {
+            name: Some(backup_name),
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        },
+        spec: BackupSpec {
+            cluster: ClusterReference { name: cluster_name },
+        },
+        status: None,
+    };
+    Ok(Some(
+        backup_api.create(&PostParams::default(), &backup).await?,
+    ))
+}
+
+/// Backups of the app cluster and any cluster recovered from it, oldest first.
+pub async fn list_backups(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+) -> Result<Vec<Backup>, Error> {
+    let backup_api: Api<Backup> = Api::namespaced(client, namespace);
+    let mut backups: Vec<Backup> = backup_api
+        .list(&ListParams::default())
+        .await?
+        .items
+        .into_iter()
+        .filter(|backup| belongs_to_app(&backup.spec.cluster.name, app_name))
+        .collect();
+    backups.sort_by(|a, b| {
+        a.metadata
+            .creation_timestamp
+            .as_ref()
+            .map(|t| t.0)
+            .cmp(&b.metadata.creation_timestamp.as_ref().map(|t| t.0))
+    });
+    Ok(backups)
+}
+
+pub async fn get_backup(
+    client: Client,
+    namespace: &str,
+    backup_name: &str,
+) -> Result<Backup, Error> {
+    let backup_api: Api<Backup> = Api::namespaced(client, namespace);
+    backup_api
+        .get(backup_name)
+        .await
+        .map_err(|_| Error::Other(format!("Backup {} not found in {}", backup_name, namespace)))
+}
+
+pub fn backup_phase(backup: &Backup) -> &str {
+    backup
+        .status
+        .as_ref()
+        .and_then(|status| status.phase.as_deref())
+        .unwrap_or("pending")
+}
+
+/// Creates a new cluster bootstrapped from `backup_name` and returns its name.
+///
+/// The active cluster is left running until [`switch_cluster`] has repointed
+/// `database-urls`, so a failed recovery loses nothing. The role passwords carry
+/// over because they are part of the backup.
+pub async fn restore(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    backup_name: &str,
+) -> Result<String, Error> {
+    let backup = get_backup(client.clone(), namespace, backup_name).await?;
+    if backup_phase(&backup) != "completed" {
+        return Err(Error::Other(format!(
+            "Backup {} is {}; only completed backups can be restored",
+            backup_name,
+            backup_phase(&backup)
+        )));
+    }
+
+    let cluster_api: Api<Cluster> = Api::namespaced(client.clone(), namespace);
+    let cluster_name = database::active_cluster_name(client, namespace, app_name).await?;
+    if !belongs_to_app(&backup.spec.cluster.name, app_name) {
+        return Err(Error::Other(format!(
+            "Backup {} belongs to cluster {}, not {}",
+            backup_name, backup.spec.cluster.name, cluster_name
+        )));
+    }
+
+    let template = cluster_api.get(&cluster_name).await?.spec;
+    let recovered_name = format!(
+        "{}-restore-{}",
+        database::cluster_resource_name(app_name),
+        unix_seconds()
+    );
+    let spec = recovery_cluster_spec(app_name, &cluster_name, template, backup_name)?;
+    let cluster = Cluster {
+        metadata: ObjectMeta {
+            name: Some(recovered_name.clone()),
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        },
+        spec,
+        status: None,
+    };
+    cluster_api.create(&PostParams::default(), &cluster).await?;
+
+    Ok(recovered_name)
+}
+
+/// Deletes a cluster the app no longer uses, once [`switch_cluster`] has moved it
+/// to the recovered one.
+pub async fn delete_cluster(
+    client: Client,
+    namespace: &str,
+    cluster_name: &str,
+) -> Result<(), Error> {
+    let cluster_api: Api<Cluster> = Api::namespaced(client, namespace);
+    if cluster_api.get_opt(cluster_name).await?.is_some() {
+        cluster_api
+            .delete(cluster_name, &DeleteParams::default())
+            .await?;
+        wait_for_cluster_deletion(&cluster_api, cluster_name).await?;
+    }
+    Ok(())
+}
+
+fn recovery_cluster_spec(
+    app_name: &str,
+    cluster_name: &str,
+    template: ClusterSpec,
+    backup_name: &str,
+) -> Result<ClusterSpec, Error> {
+    let mut backup = template.backup.ok_or_else(|| {
+        Error::Other(format!(
+            "Cluster {} has no backup destination to restore from",
+            cluster_name
+        ))
+    })?;
+    // The recovered cluster archives under its own name, next to the source.
+    backup.barman_object_store.server_name = None;
+
+    Ok(ClusterSpec {
+        image_name: template.image_name,
+        instances: template.instances,
+        bootstrap: BootstrapSpec {
+            initdb: None,
+            recovery: Some(RecoverySpec {
+                backup: Some(BackupReference {
+                    name: backup_name.to_string(),
+                }),
+                source: None,
+                recovery_target: None,
+                database: database::database_name(app_name),
+                owner: "db-owner".to_string(),
+                secret: SecretSpec {
+                    name: "db-owner".to_string(),
+                },
+            }),
+        },
+        storage: template.storage,
+        wal_storage: template.wal_storage,
+        backup: Some(backup),
+        external_clusters: None,
+        postgresql: template.postgresql,
+    })
+}
+
+/// Creates a new cluster that replays the active cluster's WAL archive up to
+/// `target_time` (RFC 3339) and returns its name.
+///
+/// The active cluster is left running; the app keeps using it until
+/// [`switch_cluster`] repoints `database-urls`.
+pub async fn restore_to_time(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    target_time: &str,
+) -> Result<String, Error> {
+    if !valid_rfc3339(target_time) {
+        return Err(Error::Other(format!(
+            "{} is not an RFC 3339 timestamp like 2026-10-18T10:32:00Z",
+            target_time
+        )));
+    }
+
+    let cluster_api: Api<Cluster> = Api::namespaced(client.clone(), namespace);
+    let source_name = database::active_cluster_name(client, namespace, app_name).await?;
+    let source = cluster_api.get(&source_name).await?;
+    let backup = source.spec.backup.clone().ok_or_else(|| {
+        Error::Other(format!(
+            "Cluster {} has no WAL archive; set components.db.backup and run `stack deploy`",
+            source_name
+        ))
+    })?;
+
+    let mut origin = backup.barman_object_store.clone();
+    origin.server_name = Some(origin.server_name.unwrap_or_else(|| source_name.clone()));
+    // The recovered cluster archives under its own name, next to the source.
+    let mut recovered_backup = backup;
+    recovered_backup.barman_object_store.server_name = None;
+
+    let recovered_name = format!(
+        "{}-pitr-{}",
+        database::cluster_resource_name(app_name),
+        unix_seconds()
+    );
+    let cluster = Cluster {
+        metadata: ObjectMeta {
+            name: Some(recovered_name.clone()),
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        },
+        spec: ClusterSpec {
+            image_name: source.spec.image_name,
+            instances: source.spec.instances,
+            bootstrap: BootstrapSpec {
+                initdb: None,
+                recovery: Some(RecoverySpec {
+                    backup: None,
+                    source: Some(RECOVERY_SOURCE.to_string()),
+                    recovery_target: Some(RecoveryTarget {
+                        target_time: Some(target_time.to_string()),
+                    }),
+                    database: database::database_name(app_name),
+                    owner: "db-owner".to_string(),
+                    secret: SecretSpec {
+                        name: "db-owner".to_string(),
+                    },
+                }),
+            },
+            storage: source.spec.storage,
+            wal_storage: source.spec.wal_storage,
+            backup: Some(recovered_backup),
+            external_clusters: Some(vec![ExternalCluster {
+                name: RECOVERY_SOURCE.to_string(),
+                barman_object_store: Some(origin),
+            }]),
+            postgresql: source.spec.postgresql,
+        },
+        status: None,
+    };
+    cluster_api.create(&PostParams::default(), &cluster).await?;
+
+    Ok(recovered_name)
+}
+
+/// Repoints `database-urls` at `cluster_name` and restarts every deployment that
+/// reads it, returning the restarted deployment names.
+pub async fn switch_cluster(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    cluster_name: &str,
+) -> Result<Vec<String>, Error> {
+    let secret_api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let secret = secret_api.get(database::DATABASE_URLS_SECRET_NAME).await?;
+    let old_cluster = database::active_cluster_name(client.clone(), namespace, app_name).await?;
+    let new_host = format!("{}-rw", cluster_name);
+
+    // Both the primary (-rw) and replica (-ro) hosts move to the new cluster.
+    let mut string_data = BTreeMap::new();
+    for key in secret.data.iter().flat_map(|data| data.keys()) {
+        if let Some(value) = read_secret_field(&secret, key) {
+            let value = ["-rw", "-ro"].iter().fold(value, |value, suffix| {
+                value.replace(
+                    &format!("@{}{}:", old_cluster, suffix),
+                    &format!("@{}{}:", cluster_name, suffix),
+                )
+            });
+            string_data.insert(key.clone(), value);
+        }
+    }
+    string_data.insert(database::DATABASE_HOST_KEY.to_string(), new_host);
+    let patch = json!({ "stringData": string_data });
+    secret_api
+        .patch(
+            database::DATABASE_URLS_SECRET_NAME,
+            &PatchParams::default(),
+            &Patch::Merge(&patch),
+        )
+        .await?;
+
+    deployment::restart_secret_consumers(client, namespace, &[database::DATABASE_URLS_SECRET_NAME])
+        .await
+}
+
+/// Backups taken from the app cluster or from a cluster recovered out of it.
+fn belongs_to_app(cluster_name: &str, app_name: &str) -> bool {
+    let app_cluster = database::cluster_resource_name(app_name);
+    cluster_name == app_cluster
+        || cluster_name
+            .strip_prefix(&app_cluster)
+            .is_some_and(|rest| rest.starts_with("-pitr-") || rest.starts_with("-restore-"))
+}
+
+/// Accepts `YYYY-MM-DDTHH:MM:SS`, optional fractional seconds, then `Z` or `±HH:MM`.
+fn valid_rfc3339(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    let digits = |range: std::ops::Range<usize>| {
+        bytes
+            .get(range)
+            .is_some_and(|part| part.iter().all(u8::is_ascii_digit))
+    };
+    if bytes.len() < 20
+        || !digits(0..4)
+        || bytes[4] != b'-'
+        || !digits(5..7)
+        || bytes[7] != b'-'
+        || !digits(8..10)
+        || !matches!(bytes[10], b'T' | b't')
+        || !digits(11..13)
+        || bytes[13] != b':'
+        || !digits(14..16)
+        || bytes[16] != b':'
+        || !digits(17..19)
+    {
+        return false;
+    }
+
+    let mut rest = &value[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if len == 0 {
+            return false;
+        }
+        rest = &fraction[len..];
+    }
+    match rest.as_bytes() {
+        [b'Z' | b'z'] => true,
+        [b'+' | b'-', h1, h2, b':', m1, m2] => [h1, h2, m1, m2].iter().all(|b| b.is_ascii_digit()),
+        _ => false,
+    }
+}
+
+async fn wait_for_cluster_deletion(cluster_api: &Api<Cluster>, name: &str) -> Result<(), Error> {
+    let mut waited = 0;
+    while cluster_api.get(name).await.is_ok() {
+        if waited >= CLUSTER_DELETE_TIMEOUT_SECS {
+            return Err(Error::Other(format!(
+                "Timed out waiting for cluster {} to be deleted",
+                name
+            )));
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        waited += 2;
+    }
+    Ok(())
+}
+
+async fn backup_configuration(
+    client: Client,
+    namespace: &str,
+    config: &DbBackupConfig,
+    storage_config: Option<&StorageConfig>,
+    server_name: Option<String>,
+) -> Result<BackupConfiguration, Error> {
+    let secret_name = match config.s3_secret_name.as_ref() {
+        Some(name) => name.clone(),
+        None if storage_config.is_some() && storage::installs_minio(storage_config) => {
+            storage::s3_secret_name(storage_config)
+        }
+        None => {
+            return Err(Error::Other(
+                "components.db.backup needs s3_secret_name or components.storage with the bundled MinIO"
+                    .to_string(),
+            ))
+        }
+    };
+
+    let retention = config.retention.as_deref().unwrap_or(DEFAULT_RETENTION);
+    if !valid_retention(retention) {
+        return Err(Error::Other(format!(
+            "components.db.backup.retention must look like 30d, 4w or 6m, got {}",
+            retention
+        )));
+    }
+
+    let secret_api: Api<Secret> = Api::namespaced(client, namespace);
+    let secret = secret_api
+        .get(&secret_name)
+        .await
+        .map_err(|_| Error::Other(format!("Backup secret {} not found", secret_name)))?;
+    let bucket = read_secret_field(&secret, storage::STORAGE_S3_BUCKET_KEY).ok_or_else(|| {
+        Error::Other(format!(
+            "Backup secret {} is missing {}",
+            secret_name,
+            storage::STORAGE_S3_BUCKET_KEY
+        ))
+    })?;
+    let endpoint_url = read_secret_field(&secret, storage::STORAGE_S3_ENDPOINT_KEY)
+        .filter(|endpoint| !endpoint.is_empty());
+    let region =
+        read_secret_field(&secret, storage::STORAGE_S3_REGION_KEY).map(|_| SecretKeySelector {
+            name: secret_name.clone(),
+            key: storage::STORAGE_S3_REGION_KEY.to_string(),
+        });
+
+    Ok(BackupConfiguration {
+        barman_object_store: BarmanObjectStoreConfiguration {
+            destination_path: format!("s3://{}/{}", bucket, BACKUP_PREFIX),
+            endpoint_url,
+            server_name,
+            s3_credentials: S3Credentials {
+                access_key_id: SecretKeySelector {
+                    name: secret_name.clone(),
+                    key: storage::AWS_ACCESS_KEY_ID_KEY.to_string(),
+                },
+                secret_access_key: SecretKeySelector {
+                    name: secret_name,
+                    key: storage::AWS_SECRET_ACCESS_KEY_KEY.to_string(),
+                },
+                region,
+            },
+        },
+        retention_policy: Some(retention.to_string()),
+    })
+}
+
+/// CloudNativePG schedules use six fields; accept the usual five-field form too.
+fn cron_schedule(schedule: &str) -> String {
+    let schedule = schedule.trim();
+    if schedule.split_whitespace().count() == 5 {
+        format!("0 {}", schedule)
+    } else {
+        schedule.to_string()
+    }
+}
+
+fn valid_retention(retention: &str) -> bool {
+    let Some(unit) = retention.chars().last() else {
+        return false;
+    };
+    let amount = &retention[..retention.len() - unit.len_utf8()];
+    matches!(unit, 'd' | 'w' | 'm')
+        && !amount.is_empty()
+        && !amount.starts_with('0')
+        && amount.chars().all(|c| c.is_ascii_digit())
+}
+
+fn unix_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn read_secret_field(secret: &Secret, key: &str) -> Option<String> {
+    if let Some(data) = &secret.data {
+        if let Some(value) = data.get(key) {
+            if let Ok(val) = String::from_utf8(value.0.clone()) {
+                return Some(val);
+            }
+        }
+    }
+
+    secret
+        .string_data
+        .as_ref()
+        .and_then(|map| map.get(key).cloned())
+}
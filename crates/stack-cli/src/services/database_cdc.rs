@@ -0,0 +1,621 @@
+use std::collections::BTreeMap;
+
+use crate::error::Error;
+use crate::operator::crd::{DbCdcConfig, DbConfig, StorageConfig};
+use crate::services::content_hash::content_hash;
+use crate::services::{database, storage};
+use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::api::{DeleteParams, Patch, PatchParams};
+use kube::{Api, Client};
+use serde_json::{json, Value};
+
+const RELAY_IMAGE: &str = "quay.io/debezium/server:3.0.7.Final";
+const SQL_IMAGE: &str = "postgres:16-alpine";
+/// Receives events from the relay over HTTP and uploads them in batches, as Debezium
+/// Server has no S3 sink.
+const STORAGE_WRITER_IMAGE: &str = "python:3.12-alpine";
+/// Debezium Server listens on 8080 itself, so the writer takes another port of the pod.
+const STORAGE_WRITER_PORT: u16 = 8090;
+const DEFAULT_FLUSH_INTERVAL_SECS: u32 = 60;
+/// WAL a stopped or lagging relay can hold back before Postgres invalidates its slot.
+const DEFAULT_MAX_SLOT_WAL_KEEP_SIZE: &str = "10GB";
+const PROPERTIES_KEY: &str = "application.properties";
+const STORAGE_WRITER_KEY: &str = "storage_writer.py";
+const CONFIG_PATH: &str = "/debezium/config";
+const DATA_PATH: &str = "/debezium/data";
+const STORAGE_WRITER_PATH: &str = "/writer";
+
+/// Name of the relay Deployment, and of the ConfigMap holding its configuration.
+pub fn relay_name(app_name: &str) -> String {
+    format!("{app_name}-cdc-relay")
+}
+
+/// Name of the Job dropping the slot and publication once `cdc` is removed.
+pub fn cleanup_job_name(app_name: &str) -> String {
+    format!("{app_name}-db-cdc-cleanup")
+}
+
+/// Name of both the publication and the replication slot. Slots are per server, so the
+/// app name keeps apps sharing an external server apart.
+pub fn slot_name(app_name: &str) -> String {
+    let app: String = app_name.replace('-', "_").chars().take(59).collect();
+    format!("{app}_cdc")
+}
+
+/// Parameters CDC adds to the cluster; values from `components.db.postgresql` win.
+pub fn parameters(config: Option<&DbConfig>) -> BTreeMap<String, String> {
+    let mut parameters = BTreeMap::new();
+    if config.is_some_and(|db| db.cdc.is_some()) {
+        parameters.insert(
+            "max_slot_wal_keep_size".to_string(),
+            DEFAULT_MAX_SLOT_WAL_KEEP_SIZE.to_string(),
+        );
+    }
+    parameters
+}
+
+/// Problems with `components.db.cdc`.
+pub fn problems(config: &DbCdcConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+    let sinks = [
+        config.http.is_some(),
+        config.nats.is_some(),
+        config.storage.is_some(),
+    ];
+    if sinks.iter().filter(|set| **set).count() != 1 {
+        problems.push("set exactly one of http, nats or storage".to_string());
+    }
+    for table in &config.tables {
+        let parts: Vec<&str> = table.split('.').collect();
+        if parts.len() > 2 || !parts.iter().all(|part| is_identifier(part)) {
+            problems.push(format!(
+                "table {} should be a table name, optionally with its schema, e.g. billing.invoices",
+                table
+            ));
+        }
+    }
+    if let Some(http) = &config.http {
+        if !(http.url.starts_with("http://") || http.url.starts_with("https://")) {
+            problems.push(format!(
+                "http.url {} should be an http:// or https:// URL",
+                http.url
+            ));
+        }
+    }
+    if let Some(nats) = &config.nats {
+        if !(nats.url.starts_with("nats://") || nats.url.starts_with("tls://")) {
+            problems.push(format!(
+                "nats.url {} should be a nats:// or tls:// URL",
+                nats.url
+            ));
+        }
+        let valid_subject = nats.subject.split('.').all(|token| {
+            !token.is_empty()
+                && token
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+        });
+        if !valid_subject {
+            problems.push(format!(
+                "nats.subject {} should be dot-separated letters, digits, `_` or `-`, without wildcards",
+                nats.subject
+            ));
+        }
+    }
+    if let Some(sink) = &config.storage {
+        if sink
+            .prefix
+            .as_deref()
+            .is_some_and(|prefix| prefix.is_empty() || prefix.starts_with('/'))
+        {
+            problems.push("storage.prefix cannot be empty or start with /".to_string());
+        }
+        if sink.flush_interval_seconds == Some(0) {
+            problems.push("storage.flush_interval_seconds must be at least 1".to_string());
+        }
+    }
+    problems
+}
+
+fn is_identifier(value: &str) -> bool {
+    value.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_')
+        && value
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// Runs the relay streaming the app's publication to the configured sink. Its init
+/// container creates the publication and the slot when missing, so a relay restarting
+/// after a failover gets a slot on the new primary. Removing `cdc` drops both, as an
+/// abandoned slot keeps WAL from being recycled.
+pub async fn deploy(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    config: Option<&DbCdcConfig>,
+    storage_config: Option<&StorageConfig>,
+) -> Result<(), Error> {
+    let name = relay_name(app_name);
+    let deployment_api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+    let job_api: Api<Job> = Api::namespaced(client.clone(), namespace);
+    let cleanup_name = cleanup_job_name(app_name);
+
+    let Some(config) = config else {
+        if deployment_api.get_opt(&name).await?.is_some() {
+            delete_relay(client.clone(), namespace, app_name).await?;
+            cleanup(client, namespace, app_name).await?;
+        }
+        return Ok(());
+    };
+    if let Some(problem) = problems(config).into_iter().next() {
+        return Err(Error::Other(format!("components.db.cdc: {}", problem)));
+    }
+    if job_api.get_opt(&cleanup_name).await?.is_some() {
+        job_api
+            .delete(&cleanup_name, &DeleteParams::background())
+            .await?;
+    }
+
+    let slot = slot_name(app_name);
+    let (topic_prefix, sink) = if let Some(http) = &config.http {
+        (
+            app_name.to_string(),
+            format!(
+                "debezium.sink.type=http\ndebezium.sink.http.url={}\n",
+                http.url
+            ),
+        )
+    } else if let Some(nats) = &config.nats {
+        (
+            nats.subject.clone(),
+            format!(
+                "debezium.sink.type=nats-jetstream\n\
+                 debezium.sink.nats-jetstream.url={}\n\
+                 debezium.sink.nats-jetstream.create-stream=true\n\
+                 debezium.sink.nats-jetstream.subjects={}.>\n",
+                nats.url, nats.subject
+            ),
+        )
+    } else {
+        (
+            app_name.to_string(),
+            format!(
+                "debezium.sink.type=http\ndebezium.sink.http.url=http://127.0.0.1:{}/\n",
+                STORAGE_WRITER_PORT
+            ),
+        )
+    };
+    // `${...}` is expanded by Debezium Server from the container environment.
+    let properties = format!(
+        "debezium.source.connector.class=io.debezium.connector.postgresql.PostgresConnector\n\
+         debezium.source.plugin.name=pgoutput\n\
+         debezium.source.slot.name={slot}\n\
+         debezium.source.publication.name={slot}\n\
+         debezium.source.publication.autocreate.mode=disabled\n\
+         debezium.source.snapshot.mode=no_data\n\
+         debezium.source.database.hostname=${{DB_HOST}}\n\
+         debezium.source.database.port=${{DB_PORT}}\n\
+         debezium.source.database.user=${{DB_USER}}\n\
+         debezium.source.database.password=${{DB_PASSWORD}}\n\
+         debezium.source.database.dbname=${{DB_NAME}}\n\
+         debezium.source.database.sslmode=${{DB_SSLMODE}}\n\
+         debezium.source.topic.prefix={topic_prefix}\n\
+         debezium.source.offset.storage.file.filename=data/offsets.dat\n\
+         debezium.source.offset.flush.interval.ms=0\n\
+         debezium.format.key=json\n\
+         debezium.format.key.schemas.enable=false\n\
+         debezium.format.value=json\n\
+         debezium.format.value.schemas.enable=false\n\
+         {sink}",
+        slot = slot,
+        topic_prefix = topic_prefix,
+        sink = sink
+    );
+
+    let mut data = BTreeMap::new();
+    data.insert(PROPERTIES_KEY.to_string(), properties);
+    if config.storage.is_some() {
+        data.insert(
+            STORAGE_WRITER_KEY.to_string(),
+            STORAGE_WRITER_SCRIPT.to_string(),
+        );
+    }
+    let config_hash = content_hash(&(&data, &config.tables));
+    let config_map = json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": {
+            "name": name,
+            "namespace": namespace
+        },
+        "data": data
+    });
+    let config_map_api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    config_map_api
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(config_map),
+        )
+        .await?;
+
+    let mut relay_env = vec![
+        database::host_env("DB_HOST"),
+        database::port_env("DB_PORT"),
+        database::sslmode_env("DB_SSLMODE"),
+        json!({ "name": "DB_NAME", "value": database::database_name(app_name) }),
+        db_owner_env("DB_USER", "username"),
+        db_owner_env("DB_PASSWORD", "password"),
+    ];
+    // Rolls the relay when its configuration changes.
+    relay_env.push(json!({ "name": "CDC_HASH", "value": config_hash }));
+    let mut containers = vec![json!({
+        "name": "relay",
+        "image": RELAY_IMAGE,
+        "imagePullPolicy": "IfNotPresent",
+        "env": relay_env,
+        "volumeMounts": [
+            {
+                "name": "config",
+                "mountPath": CONFIG_PATH,
+                "readOnly": true
+            },
+            {
+                "name": "data",
+                "mountPath": DATA_PATH
+            }
+        ]
+    })];
+    if let Some(sink) = &config.storage {
+        let secret_name = match sink.s3_secret_name.as_ref() {
+            Some(name) => name.clone(),
+            None if storage_config.is_some() && storage::installs_minio(storage_config) => {
+                storage::s3_secret_name(storage_config)
+            }
+            None => {
+                return Err(Error::Other(
+                    "components.db.cdc.storage needs s3_secret_name or components.storage with the bundled MinIO"
+                        .to_string(),
+                ))
+            }
+        };
+        let s3_env = |key: &str, optional: bool| {
+            json!({
+                "name": key,
+                "valueFrom": {
+                    "secretKeyRef": {
+                        "name": secret_name,
+                        "key": key,
+                        "optional": optional
+                    }
+                }
+            })
+        };
+        containers.push(json!({
+            "name": "storage-writer",
+            "image": STORAGE_WRITER_IMAGE,
+            "imagePullPolicy": "IfNotPresent",
+            "command": ["python", format!("{}/{}", STORAGE_WRITER_PATH, STORAGE_WRITER_KEY)],
+            "env": [
+                s3_env(storage::STORAGE_S3_BUCKET_KEY, false),
+                s3_env(storage::STORAGE_S3_ENDPOINT_KEY, false),
+                s3_env(storage::STORAGE_S3_REGION_KEY, true),
+                s3_env(storage::AWS_ACCESS_KEY_ID_KEY, false),
+                s3_env(storage::AWS_SECRET_ACCESS_KEY_KEY, false),
+                {
+                    "name": "CDC_PREFIX",
+                    "value": sink.prefix.clone().unwrap_or_else(|| format!("cdc/{}", app_name))
+                },
+                {
+                    "name": "CDC_FLUSH_SECONDS",
+                    "value": sink
+                        .flush_interval_seconds
+                        .unwrap_or(DEFAULT_FLUSH_INTERVAL_SECS)
+                        .to_string()
+                },
+                { "name": "CDC_PORT", "value": STORAGE_WRITER_PORT.to_string() }
+            ],
+            "volumeMounts": [{
+                "name": "config",
+                "mountPath": STORAGE_WRITER_PATH,
+                "readOnly": true
+            }]
+        }));
+    }
+
+    let labels = json!({ "app": name, "component": name });
+    let relay = json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+            "labels": labels
+        },
+        "spec": {
+            "replicas": 1,
+            // Two relays cannot read the same slot.
+            "strategy": { "type": "Recreate" },
+            "selector": { "matchLabels": labels },
+            "template": {
+                "metadata": { "labels": labels },
+                "spec": {
+                    "initContainers": [{
+                        "name": "publication",
+                        "image": SQL_IMAGE,
+                        "imagePullPolicy": "IfNotPresent",
+                        "command": ["/bin/sh", "-c"],
+                        "args": [publication_script(&slot, &config.tables)],
+                        "env": pg_env(app_name)
+                    }],
+                    "containers": containers,
+                    "volumes": [
+                        {
+                            "name": "config",
+                            "configMap": { "name": name }
+                        },
+                        {
+                            "name": "data",
+                            "emptyDir": {}
+                        }
+                    ]
+                }
+            }
+        }
+    });
+    deployment_api
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(relay),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Creates the publication and the slot when missing and brings the publication's table
+/// list in line with `tables`. A publication switching between every table and a list
+/// is recreated, as `ALTER PUBLICATION` cannot do that.
+fn publication_script(slot: &str, tables: &[String]) -> String {
+    let quoted: Vec<String> = tables
+        .iter()
+        .map(|table| {
+            table
+                .split('.')
+                .map(|part| format!("\"{}\"", part))
+                .collect::<Vec<_>>()
+                .join(".")
+        })
+        .collect();
+    let all_tables = quoted.is_empty();
+    let target = if all_tables {
+        "FOR ALL TABLES".to_string()
+    } else {
+        format!("FOR TABLE {}", quoted.join(", "))
+    };
+    let mut sql = format!(
+        "DO $$ BEGIN\n\
+         IF EXISTS (SELECT 1 FROM pg_publication WHERE pubname = '{slot}' AND puballtables <> {all_tables}) THEN DROP PUBLICATION \"{slot}\"; END IF;\n\
+         IF NOT EXISTS (SELECT 1 FROM pg_publication WHERE pubname = '{slot}') THEN CREATE PUBLICATION \"{slot}\" {target}; END IF;\n\
+         END $$;\n",
+        slot = slot,
+        all_tables = all_tables,
+        target = target
+    );
+    if !all_tables {
+        sql.push_str(&format!(
+            "ALTER PUBLICATION \"{}\" SET TABLE {};\n",
+            slot,
+            quoted.join(", ")
+        ));
+    }
+    sql.push_str(&format!(
+        "SELECT pg_create_logical_replication_slot('{slot}', 'pgoutput') \
+         WHERE NOT EXISTS (SELECT 1 FROM pg_replication_slots WHERE slot_name = '{slot}');\n",
+        slot = slot
+    ));
+    format!("psql -v ON_ERROR_STOP=1 -q <<'SQL'\n{}SQL\n", sql)
+}
+
+/// libpq environment connecting as `db-owner`, which may create slots and publications.
+fn pg_env(app_name: &str) -> Vec<Value> {
+    vec![
+        database::host_env("PGHOST"),
+        database::port_env("PGPORT"),
+        database::sslmode_env("PGSSLMODE"),
+        json!({ "name": "PGDATABASE", "value": database::database_name(app_name) }),
+        db_owner_env("PGUSER", "username"),
+        db_owner_env("PGPASSWORD", "password"),
+    ]
+}
+
+fn db_owner_env(name: &str, key: &str) -> Value {
+    json!({
+        "name": name,
+        "valueFrom": {
+            "secretKeyRef": {
+                "name": "db-owner",
+                "key": key
+            }
+        }
+    })
+}
+
+/// Drops the slot and the publication through a Job. It fails while the relay still
+/// holds the slot and is retried until the relay is gone.
+async fn cleanup(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    let name = cleanup_job_name(app_name);
+    let slot = slot_name(app_name);
+    let job = json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": {
+            "name": name,
+            "namespace": namespace
+        },
+        "spec": {
+            "backoffLimit": 6,
+            "template": {
+                "spec": {
+                    "restartPolicy": "Never",
+                    "containers": [{
+                        "name": "cleanup",
+                        "image": SQL_IMAGE,
+                        "imagePullPolicy": "IfNotPresent",
+                        "command": ["/bin/sh", "-c"],
+                        "args": [format!(
+                            "psql -v ON_ERROR_STOP=1 -q \
+                             -c \"SELECT pg_drop_replication_slot(slot_name) FROM pg_replication_slots WHERE slot_name = '{slot}'\" \
+                             -c 'DROP PUBLICATION IF EXISTS \"{slot}\"'",
+                            slot = slot
+                        )],
+                        "env": pg_env(app_name)
+                    }]
+                }
+            }
+        }
+    });
+    let job_api: Api<Job> = Api::namespaced(client, namespace);
+    job_api
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(job),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn delete_relay(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    let name = relay_name(app_name);
+    let deployment_api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+    if deployment_api.get_opt(&name).await?.is_some() {
+        deployment_api
+            .delete(&name, &DeleteParams::default())
+            .await?;
+    }
+    let config_map_api: Api<ConfigMap> = Api::namespaced(client, namespace);
+    if config_map_api.get_opt(&name).await?.is_some() {
+        config_map_api
+            .delete(&name, &DeleteParams::default())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Removes the relay and the cleanup Job. The slot and publication go with the cluster,
+/// or, when the database is retained, stay until `max_slot_wal_keep_size` invalidates
+/// the slot.
+pub async fn delete(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    delete_relay(client.clone(), namespace, app_name).await?;
+    let name = cleanup_job_name(app_name);
+    let job_api: Api<Job> = Api::namespaced(client, namespace);
+    if job_api.get_opt(&name).await?.is_some() {
+        job_api.delete(&name, &DeleteParams::background()).await?;
+    }
+    Ok(())
+}
+
+/// Collects the events the relay POSTs and writes them every CDC_FLUSH_SECONDS as one
+/// newline-delimited JSON object, signed with AWS Signature Version 4. A failed upload
+/// is retried with the next batch; events still buffered when the pod stops are lost.
+const STORAGE_WRITER_SCRIPT: &str = r#"import datetime
+import hashlib
+import hmac
+import os
+import threading
+import time
+import urllib.parse
+import urllib.request
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+ENDPOINT = os.environ["STORAGE_S3_ENDPOINT"].rstrip("/")
+BUCKET = os.environ["STORAGE_S3_BUCKET"]
+REGION = os.environ.get("STORAGE_S3_REGION") or "us-east-1"
+ACCESS_KEY = os.environ["AWS_ACCESS_KEY_ID"]
+SECRET_KEY = os.environ["AWS_SECRET_ACCESS_KEY"]
+PREFIX = os.environ["CDC_PREFIX"].strip("/")
+FLUSH_SECONDS = int(os.environ["CDC_FLUSH_SECONDS"])
+PORT = int(os.environ["CDC_PORT"])
+
+lock = threading.Lock()
+events = []
+
+
+def hmac_sha256(key, message):
+    return hmac.new(key, message.encode(), hashlib.sha256).digest()
+
+
+def put_object(key, body):
+    now = datetime.datetime.now(datetime.timezone.utc)
+    amz_date = now.strftime("%Y%m%dT%H%M%SZ")
+    day = now.strftime("%Y%m%d")
+    url = urllib.parse.urlsplit(f"{ENDPOINT}/{BUCKET}/{key}")
+    payload_hash = hashlib.sha256(body).hexdigest()
+    headers = {
+        "host": url.netloc,
+        "x-amz-content-sha256": payload_hash,
+        "x-amz-date": amz_date,
+    }
+    signed_headers = ";".join(sorted(headers))
+    canonical_request = "\n".join(
+        ["PUT", urllib.parse.quote(url.path), ""]
+        + [f"{name}:{headers[name]}" for name in sorted(headers)]
+        + ["", signed_headers, payload_hash]
+    )
+    scope = f"{day}/{REGION}/s3/aws4_request"
+    string_to_sign = "\n".join(
+        [
+            "AWS4-HMAC-SHA256",
+            amz_date,
+            scope,
+            hashlib.sha256(canonical_request.encode()).hexdigest(),
+        ]
+    )
+    signing_key = ("AWS4" + SECRET_KEY).encode()
+    for part in (day, REGION, "s3", "aws4_request"):
+        signing_key = hmac_sha256(signing_key, part)
+    signature = hmac.new(signing_key, string_to_sign.encode(), hashlib.sha256).hexdigest()
+    headers["authorization"] = (
+        f"AWS4-HMAC-SHA256 Credential={ACCESS_KEY}/{scope}, "
+        f"SignedHeaders={signed_headers}, Signature={signature}"
+    )
+    del headers["host"]
+    request = urllib.request.Request(url.geturl(), data=body, method="PUT", headers=headers)
+    urllib.request.urlopen(request, timeout=30).close()
+
+
+def flush():
+    while True:
+        time.sleep(FLUSH_SECONDS)
+        with lock:
+            batch = events[:]
+            events.clear()
+        if not batch:
+            continue
+        key = time.strftime(f"{PREFIX}/%Y/%m/%d/", time.gmtime()) + f"{time.time_ns()}.ndjson"
+        try:
+            put_object(key, ("\n".join(batch) + "\n").encode())
+        except Exception as error:
+            print(f"Writing {key} failed: {error}", flush=True)
+            with lock:
+                events[:0] = batch
+
+
+class Handler(BaseHTTPRequestHandler):
+    def do_POST(self):
+        body = self.rfile.read(int(self.headers.get("Content-Length", 0)))
+        with lock:
+            events.append(body.decode().replace("\n", " "))
+        self.send_response(200)
+        self.end_headers()
+
+    def log_message(self, *args):
+        pass
+
+
+threading.Thread(target=flush, daemon=True).start()
+ThreadingHTTPServer(("127.0.0.1", PORT), Handler).serve_forever()
+"#;
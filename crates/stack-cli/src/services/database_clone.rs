@@ -0,0 +1,374 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::services::database::{self, Cluster, DATABASE_URLS_SECRET_NAME};
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::{Secret, ServiceAccount};
+use k8s_openapi::api::rbac::v1::{Role, RoleBinding};
+use kube::api::{DeleteParams, ObjectMeta, Patch, PatchParams, PostParams};
+use kube::{Api, Client};
+use serde_json::json;
+
+/// Used when the source is not a CloudNativePG cluster whose image could be borrowed.
+const CLONE_IMAGE: &str = "postgres:17-alpine";
+const SOURCE_URL_KEY: &str = "source-url";
+const MASK_SQL_KEY: &str = "mask.sql";
+const CLONE_MOUNT_PATH: &str = "/clone";
+const JOB_DELETE_TIMEOUT_SECS: u64 = 120;
+/// Finished clone Jobs are kept this long for their logs.
+const JOB_TTL_SECS: u32 = 3600;
+const CURL_IMAGE: &str = "curlimages/curl:8.10.1";
+const CLONE_INPUT_PATH: &str = "/input";
+const SERVICE_ACCOUNT_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// Where a clone Job stands.
+pub enum CloneState {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// The database a clone copies from.
+pub struct CloneSource {
+    /// `migrations-url` of the source app, with the host qualified by its namespace.
+    pub url: String,
+    /// Image of the source cluster, whose `pg_dump` matches the server version.
+    pub image: Option<String>,
+}
+
+/// Name of the clone Job, and of the Secret carrying the source URL and masking SQL,
+/// in the target namespace.
+pub fn job_name(app_name: &str) -> String {
+    format!("{app_name}-db-clone")
+}
+
+/// Reads the connection details of `app_name` in `namespace` to copy from.
+pub async fn source(client: Client, namespace: &str, app_name: &str) -> Result<CloneSource, Error> {
+    let secret_api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let url = secret_api
+        .get_opt(DATABASE_URLS_SECRET_NAME)
+        .await?
+        .and_then(|secret| read_secret_field(&secret, "migrations-url"))
+        .ok_or_else(|| {
+            Error::Other(format!(
+                "{} has no {} secret; deploy it before cloning from it",
+                namespace, DATABASE_URLS_SECRET_NAME
+            ))
+        })?;
+
+    let cluster_api: Api<Cluster> = Api::namespaced(client.clone(), namespace);
+    let cluster_name = database::active_cluster_name(client, namespace, app_name).await?;
+    let image = cluster_api
+        .get_opt(&cluster_name)
+        .await?
+        .and_then(|cluster| cluster.status.and_then(|status| status.image));
+
+    Ok(CloneSource {
+        url: qualify_host(&url, namespace),
+        image,
+    })
+}
+
+/// Starts a Job in the target namespace that dumps the source database and restores it
+/// over the target's application database, then runs `mask_sql`.
+///
+/// Object ownership is kept, so tables owned by `supabase_auth_admin` and the other
+/// platform roles stay theirs; the target needs the same components as the source.
+/// Roles are not part of the dump, so the target keeps its own role passwords.
+pub async fn start(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    source: &CloneSource,
+    mask_sql: Option<&str>,
+) -> Result<(), Error> {
+    let name = job_name(app_name);
+    let job_api: Api<Job> = Api::namespaced(client.clone(), namespace);
+    if let Some(job) = job_api.get_opt(&name).await? {
+        if let CloneState::Running = job_state(&job) {
+            return Err(Error::Other(format!(
+                "A clone into {} is already running; see `kubectl -n {} logs job/{}`",
+                namespace, namespace, name
+            )));
+        }
+        // Foreground, so the ServiceAccount and Role it owns are gone before they are
+        // applied again for the new Job.
+        job_api.delete(&name, &DeleteParams::foreground()).await?;
+        let mut waited = 0;
+        while job_api.get_opt(&name).await?.is_some() {
+            if waited >= JOB_DELETE_TIMEOUT_SECS {
+                return Err(Error::Other(format!(
+                    "Timed out waiting for the previous clone Job {} to be deleted",
+                    name
+                )));
+            }
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            waited += 2;
+        }
+    }
+
+    let mut data = BTreeMap::new();
+    data.insert(SOURCE_URL_KEY.to_string(), source.url.clone());
+    if let Some(mask_sql) = mask_sql {
+        data.insert(MASK_SQL_KEY.to_string(), mask_sql.to_string());
+    }
+    let secret = Secret {
+        metadata: ObjectMeta {
+            name: Some(name.clone()),
+            namespace: Some(namespace.to_string()),
+            ..ObjectMeta::default()
+        },
+        string_data: Some(data),
+        ..Default::default()
+    };
+    let secret_api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    if secret_api.get_opt(&name).await?.is_some() {
+        secret_api.delete(&name, &DeleteParams::default()).await?;
+    }
+    secret_api.create(&PostParams::default(), &secret).await?;
+
+    // Lets the Job delete the Secret carrying the source credentials, and nothing else.
+    let params = PatchParams::apply(crate::MANAGER).force();
+    let service_account_api: Api<ServiceAccount> = Api::namespaced(client.clone(), namespace);
+    let service_account = json!({
+        "apiVersion": "v1",
+        "kind": "ServiceAccount",
+        "metadata": { "name": name, "namespace": namespace }
+    });
+    service_account_api
+        .patch(&name, &params, &Patch::Apply(service_account))
+        .await?;
+    let role_api: Api<Role> = Api::namespaced(client.clone(), namespace);
+    let role = json!({
+        "apiVersion": "rbac.authorization.k8s.io/v1",
+        "kind": "Role",
+        "metadata": { "name": name, "namespace": namespace },
+        "rules": [{
+            "apiGroups": [""],
+            "resources": ["secrets"],
+            "resourceNames": [name],
+            "verbs": ["delete"]
+        }]
+    });
+    role_api.patch(&name, &params, &Patch::Apply(role)).await?;
+    let role_binding_api: Api<RoleBinding> = Api::namespaced(client.clone(), namespace);
+    let role_binding = json!({
+        "apiVersion": "rbac.authorization.k8s.io/v1",
+        "kind": "RoleBinding",
+        "metadata": { "name": name, "namespace": namespace },
+        "roleRef": {
+            "apiGroup": "rbac.authorization.k8s.io",
+            "kind": "Role",
+            "name": name
+        },
+        "subjects": [{
+            "kind": "ServiceAccount",
+            "name": name,
+            "namespace": namespace
+        }]
+    });
+    role_binding_api
+        .patch(&name, &params, &Patch::Apply(role_binding))
+        .await?;
+
+    // The init container moves the Secret's files into an emptyDir and deletes it, so the
+    // source credentials are gone from the namespace before the dump starts.
+    let take_input = format!(
+        "set -e\n\
+         cp {input}/* {work}/\n\
+         curl --fail --silent --show-error --cacert {sa}/ca.crt \
+         -H \"Authorization: Bearer $(cat {sa}/token)\" -X DELETE \
+         https://kubernetes.default.svc/api/v1/namespaces/{namespace}/secrets/{name} > /dev/null",
+        input = CLONE_INPUT_PATH,
+        work = CLONE_MOUNT_PATH,
+        sa = SERVICE_ACCOUNT_PATH,
+        namespace = namespace,
+        name = name
+    );
+    let script = format!(
+        "set -e\n\
+         SOURCE_URL=\"$(cat {mount}/{source})\"\n\
+         echo \"Dumping the source database\"\n\
+         pg_dump \"$SOURCE_URL\" --format=custom --file=/tmp/clone.dump\n\
+         echo \"Restoring into the target database\"\n\
+         pg_restore --dbname=\"$TARGET_URL\" --clean --if-exists --single-transaction /tmp/clone.dump\n\
+         if [ -f {mount}/{mask} ]; then\n\
+           echo \"Running masking SQL\"\n\
+           psql \"$TARGET_URL\" -v ON_ERROR_STOP=1 --single-transaction -f {mount}/{mask}\n\
+         fi",
+        mount = CLONE_MOUNT_PATH,
+        source = SOURCE_URL_KEY,
+        mask = MASK_SQL_KEY
+    );
+    let job = json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": {
+            "name": name,
+            "namespace": namespace
+        },
+        "spec": {
+            "backoffLimit": 0,
+            "ttlSecondsAfterFinished": JOB_TTL_SECS,
+            "template": {
+                "spec": {
+                    "restartPolicy": "Never",
+                    "serviceAccountName": name,
+                    "initContainers": [{
+                        "name": "take-input",
+                        "image": CURL_IMAGE,
+                        "imagePullPolicy": "IfNotPresent",
+                        "command": ["/bin/sh", "-c"],
+                        "args": [take_input],
+                        "volumeMounts": [
+                            {
+                                "name": "input",
+                                "mountPath": CLONE_INPUT_PATH,
+                                "readOnly": true
+                            },
+                            {
+                                "name": "clone",
+                                "mountPath": CLONE_MOUNT_PATH
+                            }
+                        ]
+                    }],
+                    "containers": [{
+                        "name": "clone",
+                        "image": source.image.as_deref().unwrap_or(CLONE_IMAGE),
+                        "imagePullPolicy": "IfNotPresent",
+                        "command": ["/bin/sh", "-c"],
+                        "args": [script],
+                        "env": [
+                            {
+                                "name": "TARGET_URL",
+                                "valueFrom": {
+                                    "secretKeyRef": {
+                                        "name": DATABASE_URLS_SECRET_NAME,
+                                        "key": "migrations-url"
+                                    }
+                                }
+                            }
+                        ],
+                        "volumeMounts": [{
+                            "name": "clone",
+                            "mountPath": CLONE_MOUNT_PATH,
+                            "readOnly": true
+                        }]
+                    }],
+                    "volumes": [
+                        {
+                            "name": "input",
+                            "secret": { "secretName": name }
+                        },
+                        {
+                            "name": "clone",
+                            "emptyDir": { "medium": "Memory" }
+                        }
+                    ]
+                }
+            }
+        }
+    });
+    let job = job_api.patch(&name, &params, &Patch::Apply(job)).await?;
+
+    // Everything created for the clone goes when the Job's TTL removes it.
+    let owner_patch = json!({
+        "metadata": {
+            "ownerReferences": [{
+                "apiVersion": "batch/v1",
+                "kind": "Job",
+                "name": name,
+                "uid": job.metadata.uid
+            }]
+        }
+    });
+    let owner_patch = Patch::Merge(&owner_patch);
+    if secret_api.get_opt(&name).await?.is_some() {
+        // Already gone if the Job was quick.
+        let _ = secret_api
+            .patch(&name, &PatchParams::default(), &owner_patch)
+            .await;
+    }
+    service_account_api
+        .patch(&name, &PatchParams::default(), &owner_patch)
+        .await?;
+    role_api
+        .patch(&name, &PatchParams::default(), &owner_patch)
+        .await?;
+    role_binding_api
+        .patch(&name, &PatchParams::default(), &owner_patch)
+        .await?;
+    Ok(())
+}
+
+/// State of the clone Job. The Job deletes the Secret holding the source URL itself; it
+/// is removed here too in case the Job failed before it got that far. The Job stays for an
+/// hour for its logs, and takes its ServiceAccount and Role with it.
+pub async fn state(client: Client, namespace: &str, app_name: &str) -> Result<CloneState, Error> {
+    let name = job_name(app_name);
+    let job_api: Api<Job> = Api::namespaced(client.clone(), namespace);
+    let job = job_api.get(&name).await?;
+    let state = job_state(&job);
+    if !matches!(state, CloneState::Running) {
+        let secret_api: Api<Secret> = Api::namespaced(client, namespace);
+        if secret_api.get_opt(&name).await?.is_some() {
+            secret_api.delete(&name, &DeleteParams::default()).await?;
+        }
+    }
+    Ok(state)
+}
+
+fn job_state(job: &Job) -> CloneState {
+    let status = job.status.clone().unwrap_or_default();
+    if status.succeeded.unwrap_or(0) > 0 {
+        CloneState::Succeeded
+    } else if status
+        .conditions
+        .iter()
+        .flatten()
+        .any(|condition| condition.type_ == "Failed" && condition.status == "True")
+    {
+        CloneState::Failed
+    } else {
+        CloneState::Running
+    }
+}
+
+/// Services inside the cluster are addressed by bare name within their namespace; the
+/// Job runs in another one, so the host gets the namespace appended.
+fn qualify_host(url: &str, namespace: &str) -> String {
+    let Some(at) = url.rfind('@') else {
+        return url.to_string();
+    };
+    let host_end = url[at..]
+        .find([':', '/'])
+        .map(|offset| at + offset)
+        .unwrap_or(url.len());
+    let host = &url[at + 1..host_end];
+    if host.contains('.') {
+        return url.to_string();
+    }
+    format!(
+        "{}{}.{}.svc{}",
+        &url[..=at],
+        host,
+        namespace,
+        &url[host_end..]
+    )
+}
+
+fn read_secret_field(secret: &Secret, key: &str) -> Option<String> {
+    if let Some(data) = &secret.data {
+        if let Some(value) = data.get(key) {
+            if let Ok(val) = String::from_utf8(value.0.clone()) {
+                return Some(val);
+            }
+        }
+    }
+
+    secret
+        .string_data
+        .as_ref()
+        .and_then(|map| map.get(key).cloned())
+}
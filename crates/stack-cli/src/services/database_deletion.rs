@@ -0,0 +1,154 @@
+use crate::error::Error;
+use crate::operator::crd::{DbConfig, DbDeletionPolicy};
+use crate::services::database::{self, Cluster, DATABASE_URLS_SECRET_NAME};
+use crate::services::{database_backup, database_services, storage};
+use k8s_openapi::api::core::v1::Secret;
+use kube::api::{ListParams, Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::{json, Value};
+
+/// Set to the app name on the cluster and secrets a `Retain` deletion leaves behind.
+const RETAINED_LABEL: &str = "stack-cli.dev/retained-from";
+/// Secrets holding the credentials of the cluster roles.
+const CREDENTIAL_SECRETS: [&str; 3] = [DATABASE_URLS_SECRET_NAME, "db-owner", "db-authenticator"];
+
+/// Applies `components.db.deletion_policy` while the StackApp is being deleted. Returns
+/// whether the database has been dealt with, so the finalizer can go; `Snapshot` waits
+/// for its final backup before deleting anything.
+pub async fn delete(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    config: Option<&DbConfig>,
+) -> Result<bool, Error> {
+    match config.and_then(|db| db.deletion_policy).unwrap_or_default() {
+        DbDeletionPolicy::Delete => {}
+        DbDeletionPolicy::Retain => {
+            database_services::delete_job(client.clone(), namespace, app_name).await?;
+            let patch = json!({ "metadata": { "labels": { RETAINED_LABEL: app_name } } });
+            label(client.clone(), namespace, app_name, &patch).await?;
+            detach_bundled_backup(client, namespace, app_name).await?;
+            return Ok(true);
+        }
+        DbDeletionPolicy::Snapshot => {
+            let backup = database_backup::final_backup(client.clone(), namespace, app_name).await?;
+            if let Some(backup) = backup {
+                match database_backup::backup_phase(&backup) {
+                    "completed" => {}
+                    "failed" => {
+                        let reason = backup
+                            .status
+                            .as_ref()
+                            .and_then(|status| status.error.clone())
+                            .unwrap_or_else(|| "no error reported".to_string());
+                        return Err(Error::Other(format!(
+                            "Final backup {} failed: {}; the database is kept until it succeeds or deletion_policy changes",
+                            backup.name_any(),
+                            reason
+                        )));
+                    }
+                    _ => return Ok(false),
+                }
+            }
+        }
+    }
+
+    database_services::delete(client.clone(), namespace, app_name).await?;
+    database::delete(client, namespace, app_name).await?;
+    Ok(true)
+}
+
+/// Takes back a database an earlier StackApp of the same name retained, by removing
+/// the label from it.
+pub async fn adopt(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    let secret_api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let retained = secret_api
+        .get_opt(DATABASE_URLS_SECRET_NAME)
+        .await?
+        .is_some_and(|secret| secret.labels().contains_key(RETAINED_LABEL));
+    if !retained {
+        return Ok(());
+    }
+    let patch = json!({ "metadata": { "labels": { RETAINED_LABEL: null } } });
+    label(client, namespace, app_name, &patch).await
+}
+
+/// The app's cluster and, after a restore, the one it runs on now.
+async fn cluster_names(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+) -> Result<Vec<String>, Error> {
+    let active_cluster = database::active_cluster_name(client, namespace, app_name).await?;
+    let mut cluster_names = vec![database::cluster_resource_name(app_name)];
+    if !cluster_names.contains(&active_cluster) {
+        cluster_names.push(active_cluster);
+    }
+    Ok(cluster_names)
+}
+
+/// Removes the backup configuration from retained clusters that archive to the bundled
+/// MinIO, which goes with the app. Backups to a bucket of your own carry on.
+async fn detach_bundled_backup(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+) -> Result<(), Error> {
+    let cluster_api: Api<Cluster> = Api::namespaced(client.clone(), namespace);
+    for name in cluster_names(client, namespace, app_name).await? {
+        let Some(cluster) = cluster_api.get_opt(&name).await? else {
+            continue;
+        };
+        let bundled = cluster.spec.backup.as_ref().is_some_and(|backup| {
+            backup.barman_object_store.s3_credentials.access_key_id.name
+                == storage::STORAGE_S3_SECRET_NAME
+        });
+        if bundled {
+            let patch = json!({ "spec": { "backup": null } });
+            cluster_api
+                .patch(&name, &PatchParams::default(), &Patch::Merge(&patch))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Merges `patch` into the app's clusters and the secrets with their credentials.
+async fn label(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    patch: &Value,
+) -> Result<(), Error> {
+    let params = PatchParams::default();
+    let cluster_api: Api<Cluster> = Api::namespaced(client.clone(), namespace);
+    for name in cluster_names(client.clone(), namespace, app_name).await? {
+        if cluster_api.get_opt(&name).await?.is_some() {
+            cluster_api
+                .patch(&name, &params, &Patch::Merge(patch))
+                .await?;
+        }
+    }
+
+    let secret_api: Api<Secret> = Api::namespaced(client, namespace);
+    let mut secret_names: Vec<String> = CREDENTIAL_SECRETS
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    secret_names.extend(
+        secret_api
+            .list(&ListParams::default().labels(database_services::SERVICE_LABEL))
+            .await?
+            .items
+            .iter()
+            .map(ResourceExt::name_any),
+    );
+    for name in secret_names {
+        if secret_api.get_opt(&name).await?.is_some() {
+            secret_api
+                .patch(&name, &params, &Patch::Merge(patch))
+                .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,296 @@
+use std::collections::BTreeSet;
+
+use crate::error::Error;
+use crate::operator::crd::{DatabaseStatus, DbConfig, DbExtension};
+use crate::services::content_hash::content_hash;
+use crate::services::{database, database_monitoring};
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{DeleteParams, ListParams, LogParams, Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::json;
+
+/// Extensions created when the manifest does not list any.
+pub const DEFAULT_EXTENSIONS: &[&str] = &["vector"];
+const EXTENSIONS_IMAGE: &str = "postgres:16-alpine";
+const EXTENSIONS_HASH_ANNOTATION: &str = "stack-cli.dev/extensions-hash";
+const MISSING_PREFIX: &str = "missing:";
+
+pub fn job_name(app_name: &str) -> String {
+    format!("{app_name}-db-extensions")
+}
+
+/// Problems with `components.db.extensions`, keyed by list index.
+pub fn problems(config: &DbConfig) -> Vec<(usize, String)> {
+    let mut problems = Vec::new();
+    let mut seen = BTreeSet::new();
+    let preload_libraries = config
+        .postgresql
+        .as_ref()
+        .map(|postgresql| postgresql.shared_preload_libraries.as_slice())
+        .unwrap_or_default();
+
+    for (index, extension) in config.extensions.iter().flatten().enumerate() {
+        let name = extension.name();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+        {
+            problems.push((
+                index,
+                format!(
+                    "extension name '{}' may only contain lowercase letters, digits, '_' and '-'",
+                    name
+                ),
+            ));
+        } else if !seen.insert(name) {
+            problems.push((index, format!("extension '{}' is listed twice", name)));
+        } else if name == "pg_cron"
+            && !extension.is_dropped()
+            && !preload_libraries.iter().any(|library| library == "pg_cron")
+        {
+            problems.push((
+                index,
+                "pg_cron needs pg_cron in components.db.postgresql.shared_preload_libraries"
+                    .to_string(),
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Extensions to create and extensions to drop, in manifest order, followed by
+/// `pg_stat_statements` when `components.db.monitoring` is set and the list does not
+/// drop it.
+pub fn extensions(config: Option<&DbConfig>) -> Result<(Vec<String>, Vec<String>), Error> {
+    let (mut create, drop) = listed_extensions(config)?;
+    let statements = database_monitoring::STATEMENTS_EXTENSION.to_string();
+    if config.is_some_and(|db| db.monitoring.is_some())
+        && !create.contains(&statements)
+        && !drop.contains(&statements)
+    {
+        create.push(statements);
+    }
+    Ok((create, drop))
+}
+
+fn listed_extensions(config: Option<&DbConfig>) -> Result<(Vec<String>, Vec<String>), Error> {
+    let Some(listed) = config.and_then(|db| db.extensions.as_ref()) else {
+        let defaults = DEFAULT_EXTENSIONS.iter().map(|name| name.to_string());
+        return Ok((defaults.collect(), Vec::new()));
+    };
+    if let Some((_, message)) = config.map(problems).unwrap_or_default().into_iter().next() {
+        return Err(Error::Other(format!(
+            "components.db.extensions: {}",
+            message
+        )));
+    }
+
+    let (dropped, created): (Vec<&DbExtension>, Vec<&DbExtension>) =
+        listed.iter().partition(|extension| extension.is_dropped());
+    let names = |extensions: Vec<&DbExtension>| -> Vec<String> {
+        extensions
+            .into_iter()
+            .map(|extension| extension.name().to_string())
+            .collect()
+    };
+    Ok((names(created), names(dropped)))
+}
+
+fn sql_array(names: &[String]) -> String {
+    let quoted: Vec<String> = names.iter().map(|name| format!("'{}'", name)).collect();
+    format!("ARRAY[{}]::text[]", quoted.join(", "))
+}
+
+/// Creates the `create` extensions the server has available and drops the `drop` ones.
+/// Unavailable extensions are skipped so one missing package does not block the rest.
+pub fn extensions_sql(create: &[String], drop: &[String]) -> String {
+    format!(
+        "DO $$ DECLARE ext text; BEGIN \
+         FOREACH ext IN ARRAY {} LOOP \
+         IF EXISTS (SELECT 1 FROM pg_available_extensions WHERE name = ext) THEN \
+         EXECUTE format('CREATE EXTENSION IF NOT EXISTS %I CASCADE', ext); \
+         END IF; END LOOP; \
+         FOREACH ext IN ARRAY {} LOOP \
+         EXECUTE format('DROP EXTENSION IF EXISTS %I', ext); \
+         END LOOP; END $$",
+        sql_array(create),
+        sql_array(drop)
+    )
+}
+
+/// Prints `missing:<name>` for every extension the server does not ship.
+fn missing_sql(create: &[String]) -> String {
+    format!(
+        "SELECT '{}' || name FROM unnest({}) AS name \
+         WHERE name NOT IN (SELECT name FROM pg_available_extensions)",
+        MISSING_PREFIX,
+        sql_array(create)
+    )
+}
+
+/// Brings the extensions in the application database in line with the manifest through a
+/// Job running as `db-owner`, and records the ones the running image lacks in `status`.
+///
+/// The Job is replaced whenever the extension list, the cluster or its image changes, so
+/// an image update or a restore re-applies the list.
+pub async fn deploy(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    config: Option<&DbConfig>,
+    status: &mut DatabaseStatus,
+) -> Result<(), Error> {
+    let (create, drop) = extensions(config)?;
+    if status.ready_instances < 1 {
+        return Ok(());
+    }
+
+    let name = job_name(app_name);
+    let desired_hash = extensions_hash(&create, &drop, &status.cluster, status.image.as_deref());
+    let job_api: Api<Job> = Api::namespaced(client.clone(), namespace);
+
+    if let Some(job) = job_api.get_opt(&name).await? {
+        let existing_hash = job
+            .annotations()
+            .get(EXTENSIONS_HASH_ANNOTATION)
+            .map(String::as_str);
+        if existing_hash != Some(desired_hash.as_str()) {
+            // The replacement is created on the next reconcile, once this one is gone.
+            job_api.delete(&name, &DeleteParams::background()).await?;
+            return Ok(());
+        }
+
+        let job_status = job.status.unwrap_or_default();
+        if job_status.succeeded.unwrap_or(0) > 0 {
+            status.missing_extensions = missing_extensions(client, namespace, &name).await?;
+            if !status.missing_extensions.is_empty() && status.message.is_none() {
+                status.message = Some(format!(
+                    "Extensions not available in the database image: {}",
+                    status.missing_extensions.join(", ")
+                ));
+            }
+        } else if job_status
+            .conditions
+            .iter()
+            .flatten()
+            .any(|condition| condition.type_ == "Failed" && condition.status == "True")
+        {
+            status.message = Some(format!(
+                "Applying extensions failed; see `kubectl -n {} logs job/{}`",
+                namespace, name
+            ));
+        }
+        return Ok(());
+    }
+
+    let job = json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+            "annotations": {
+                EXTENSIONS_HASH_ANNOTATION: desired_hash
+            }
+        },
+        "spec": {
+            "backoffLimit": 4,
+            "template": {
+                "spec": {
+                    "restartPolicy": "Never",
+                    "containers": [{
+                        "name": "extensions",
+                        "image": EXTENSIONS_IMAGE,
+                        "imagePullPolicy": "IfNotPresent",
+                        "env": [
+                            database::host_env("PGHOST"),
+                            database::port_env("PGPORT"),
+                            {"name": "PGDATABASE", "value": database::database_name(app_name)},
+                            {
+                                "name": "PGUSER",
+                                "valueFrom": {
+                                    "secretKeyRef": {
+                                        "name": "db-owner",
+                                        "key": "username"
+                                    }
+                                }
+                            },
+                            {
+                                "name": "PGPASSWORD",
+                                "valueFrom": {
+                                    "secretKeyRef": {
+                                        "name": "db-owner",
+                                        "key": "password"
+                                    }
+                                }
+                            },
+                            {"name": "MISSING_SQL", "value": missing_sql(&create)},
+                            {"name": "EXTENSIONS_SQL", "value": extensions_sql(&create, &drop)}
+                        ],
+                        "command": ["/bin/sh", "-c"],
+                        "args": [
+                            "psql -v ON_ERROR_STOP=1 -At -c \"$MISSING_SQL\" -c \"$EXTENSIONS_SQL\""
+                        ]
+                    }]
+                }
+            }
+        }
+    });
+
+    job_api
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(job),
+        )
+        .await?;
+    Ok(())
+}
+
+fn extensions_hash(
+    create: &[String],
+    drop: &[String],
+    cluster: &str,
+    image: Option<&str>,
+) -> String {
+    content_hash(&(create, drop, cluster, image))
+}
+
+/// Reads the `missing:` lines printed by the Job's successful pod.
+async fn missing_extensions(
+    client: Client,
+    namespace: &str,
+    job_name: &str,
+) -> Result<Vec<String>, Error> {
+    let pod_api: Api<Pod> = Api::namespaced(client, namespace);
+    let pods = pod_api
+        .list(&ListParams::default().labels(&format!("job-name={}", job_name)))
+        .await?;
+    let Some(pod) = pods.items.iter().find(|pod| {
+        pod.status
+            .as_ref()
+            .and_then(|status| status.phase.as_deref())
+            == Some("Succeeded")
+    }) else {
+        return Ok(Vec::new());
+    };
+
+    let logs = pod_api.logs(&pod.name_any(), &LogParams::default()).await?;
+    Ok(logs
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(MISSING_PREFIX))
+        .map(str::to_string)
+        .collect())
+}
+
+pub async fn delete(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    let job_api: Api<Job> = Api::namespaced(client, namespace);
+    let name = job_name(app_name);
+    if job_api.get_opt(&name).await?.is_some() {
+        job_api.delete(&name, &DeleteParams::background()).await?;
+    }
+    Ok(())
+}
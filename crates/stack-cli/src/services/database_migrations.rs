@@ -0,0 +1,250 @@
+use crate::error::Error;
+use crate::operator::crd::{DatabaseStatus, MigrationsConfig};
+use crate::services::content_hash::content_hash;
+use crate::services::database::DATABASE_URLS_SECRET_NAME;
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::api::{DeleteParams, Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::json;
+
+const MIGRATIONS_IMAGE: &str = "postgres:16-alpine";
+const MIGRATIONS_HASH_ANNOTATION: &str = "stack-cli.dev/migrations-hash";
+const MIGRATIONS_MOUNT_PATH: &str = "/migrations";
+pub const DEFAULT_IMAGE_PATH: &str = "/migrations";
+/// Schema and table recording applied migrations, one row per file name without `.sql`.
+pub const TRACKING_TABLE: &str = "stack_migrations.applied";
+
+/// Name of the migrations Job.
+pub fn job_name(app_name: &str) -> String {
+    format!("{app_name}-db-migrate")
+}
+
+/// Name of the ConfigMap `stack deploy` packages `sql_dir` into.
+pub fn config_map_name(app_name: &str) -> String {
+    format!("{app_name}-migrations")
+}
+
+/// Whether `file_name` can be a migration: digits first, then characters that are safe
+/// inside a ConfigMap key, a shell word and a SQL literal, ending in `.sql`.
+pub fn is_migration_file(file_name: &str) -> bool {
+    file_name.len() > ".sql".len()
+        && file_name.ends_with(".sql")
+        && file_name.starts_with(|ch: char| ch.is_ascii_digit())
+        && file_name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || "_-.".contains(ch))
+}
+
+/// Problems with `spec.migrations`.
+pub fn problems(config: &MigrationsConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+    if config.sql_dir.is_some() == config.image.is_some() {
+        problems.push("set exactly one of sql_dir or image".to_string());
+    }
+    if config.path.is_some() && config.image.is_none() {
+        problems.push("path only applies together with image".to_string());
+    }
+    problems
+}
+
+/// Applies `spec.migrations` through a Job once the database is ready. Returns whether
+/// every migration has been applied, so the web service can roll out; while the Job
+/// runs or after it failed the web Deployment keeps its previous revision.
+pub async fn deploy(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    config: Option<&MigrationsConfig>,
+    status: &mut DatabaseStatus,
+) -> Result<bool, Error> {
+    let Some(config) = config else {
+        delete(client, namespace, app_name).await?;
+        return Ok(true);
+    };
+    if let Some(problem) = problems(config).into_iter().next() {
+        return Err(Error::Other(format!("spec.migrations: {}", problem)));
+    }
+    if status.ready_instances < 1 {
+        return Ok(false);
+    }
+
+    let name = job_name(app_name);
+    let config_map_name = config_map_name(app_name);
+    let migrations_hash = if config.sql_dir.is_some() {
+        let config_map_api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+        let Some(config_map) = config_map_api.get_opt(&config_map_name).await? else {
+            status.message.get_or_insert_with(|| {
+                format!(
+                    "Migrations ConfigMap {} does not exist; run `stack deploy` to package sql_dir",
+                    config_map_name
+                )
+            });
+            return Ok(false);
+        };
+        content_hash(&config_map.data)
+    } else {
+        content_hash(&(&config.image, &config.path))
+    };
+
+    let job_api: Api<Job> = Api::namespaced(client, namespace);
+    if let Some(job) = job_api.get_opt(&name).await? {
+        let job_hash = job.annotations().get(MIGRATIONS_HASH_ANNOTATION).cloned();
+        if job_hash.as_deref() != Some(migrations_hash.as_str()) {
+            // The replacement is created on the next reconcile, once this one is gone.
+            job_api.delete(&name, &DeleteParams::background()).await?;
+            return Ok(false);
+        }
+
+        let job_status = job.status.unwrap_or_default();
+        if job_status.succeeded.unwrap_or(0) > 0 {
+            return Ok(true);
+        }
+        if job_status
+            .conditions
+            .iter()
+            .flatten()
+            .any(|condition| condition.type_ == "Failed" && condition.status == "True")
+        {
+            status.message.get_or_insert_with(|| {
+                format!(
+                    "Applying migrations failed; see `kubectl -n {} logs job/{}`",
+                    namespace, name
+                )
+            });
+        } else {
+            status
+                .message
+                .get_or_insert_with(|| "Applying migrations".to_string());
+        }
+        return Ok(false);
+    }
+
+    let mut init_containers = Vec::new();
+    let mut volumes = Vec::new();
+    if let Some(image) = &config.image {
+        let path = config.path.as_deref().unwrap_or(DEFAULT_IMAGE_PATH);
+        init_containers.push(json!({
+            "name": "copy-migrations",
+            "image": image,
+            "imagePullPolicy": "IfNotPresent",
+            "command": ["sh", "-c"],
+            "args": [format!("cp {}/*.sql {}/", path.trim_end_matches('/'), MIGRATIONS_MOUNT_PATH)],
+            "volumeMounts": [{
+                "name": "migrations",
+                "mountPath": MIGRATIONS_MOUNT_PATH
+            }]
+        }));
+        volumes.push(json!({
+            "name": "migrations",
+            "emptyDir": {}
+        }));
+    } else {
+        volumes.push(json!({
+            "name": "migrations",
+            "configMap": { "name": config_map_name }
+        }));
+    }
+
+    let job = json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+            "annotations": {
+                MIGRATIONS_HASH_ANNOTATION: migrations_hash
+            }
+        },
+        "spec": {
+            "backoffLimit": 2,
+            "template": {
+                "spec": {
+                    "restartPolicy": "Never",
+                    "initContainers": init_containers,
+                    "containers": [{
+                        "name": "migrate",
+                        "image": MIGRATIONS_IMAGE,
+                        "imagePullPolicy": "IfNotPresent",
+                        "command": ["/bin/sh", "-c"],
+                        "args": [runner_script()],
+                        "env": [{
+                            "name": "DATABASE_URL",
+                            "valueFrom": {
+                                "secretKeyRef": {
+                                    "name": DATABASE_URLS_SECRET_NAME,
+                                    "key": "migrations-url"
+                                }
+                            }
+                        }],
+                        "volumeMounts": [{
+                            "name": "migrations",
+                            "mountPath": MIGRATIONS_MOUNT_PATH,
+                            "readOnly": true
+                        }]
+                    }],
+                    "volumes": volumes
+                }
+            }
+        }
+    });
+    job_api
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(job),
+        )
+        .await?;
+    status
+        .message
+        .get_or_insert_with(|| "Applying migrations".to_string());
+    Ok(false)
+}
+
+/// Applies each file not yet in the tracking table in its own transaction, together with
+/// its tracking row. A file whose checksum no longer matches its row stops the run, as
+/// applied migrations must not be edited. So does a file named against
+/// [`is_migration_file`], which an image can ship: its name ends up in SQL literals.
+fn runner_script() -> String {
+    format!(
+        "set -e\n\
+         psql \"$DATABASE_URL\" -v ON_ERROR_STOP=1 -q -c \"CREATE SCHEMA IF NOT EXISTS stack_migrations\" \
+           -c \"CREATE TABLE IF NOT EXISTS {table} (version text PRIMARY KEY, checksum text NOT NULL, applied_at timestamptz NOT NULL DEFAULT now())\"\n\
+         for file in {mount}/*.sql; do\n\
+           [ -e \"$file\" ] || continue\n\
+           case \"$(basename \"$file\")\" in\n\
+             [0-9]*[!A-Za-z0-9_.-]*.sql|[!0-9]*) echo \"$file should be named like 0001_create_todos.sql: digits first, then letters, digits, _, - or .\" >&2; exit 1 ;;\n\
+           esac\n\
+           version=$(basename \"$file\" .sql)\n\
+           checksum=$(sha256sum \"$file\" | cut -d' ' -f1)\n\
+           applied=$(psql \"$DATABASE_URL\" -v ON_ERROR_STOP=1 -At -c \"SELECT checksum FROM {table} WHERE version = '$version'\")\n\
+           if [ -z \"$applied\" ]; then\n\
+             echo \"Applying $version\"\n\
+             psql \"$DATABASE_URL\" -v ON_ERROR_STOP=1 --single-transaction -f \"$file\" \
+               -c \"INSERT INTO {table} (version, checksum) VALUES ('$version', '$checksum')\"\n\
+           elif [ \"$applied\" != \"$checksum\" ]; then\n\
+             echo \"$version was edited after it was applied; add a new migration instead\" >&2\n\
+             exit 1\n\
+           fi\n\
+         done",
+        table = TRACKING_TABLE,
+        mount = MIGRATIONS_MOUNT_PATH
+    )
+}
+
+/// Removes the Job and the packaged ConfigMap. The tracking table stays with the data.
+pub async fn delete(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    let name = job_name(app_name);
+    let job_api: Api<Job> = Api::namespaced(client.clone(), namespace);
+    if job_api.get_opt(&name).await?.is_some() {
+        job_api.delete(&name, &DeleteParams::background()).await?;
+    }
+    let config_map_name = config_map_name(app_name);
+    let config_map_api: Api<ConfigMap> = Api::namespaced(client, namespace);
+    if config_map_api.get_opt(&config_map_name).await?.is_some() {
+        config_map_api
+            .delete(&config_map_name, &DeleteParams::default())
+            .await?;
+    }
+    Ok(())
+}
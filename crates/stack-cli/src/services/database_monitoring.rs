@@ -0,0 +1,102 @@
+use crate::error::Error;
+use crate::operator::crd::{DatabaseStatus, DbConfig};
+use crate::services::database;
+use kube::api::{DeleteParams, Patch, PatchParams};
+use kube::core::dynamic::{ApiResource, DynamicObject};
+use kube::core::gvk::GroupVersionKind;
+use kube::{Api, Client};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Created in the application database whenever `components.db.monitoring` is set.
+pub const STATEMENTS_EXTENSION: &str = "pg_stat_statements";
+/// Port CloudNativePG's exporter listens on in every instance pod.
+const METRICS_PORT_NAME: &str = "metrics";
+
+/// Name of the PodMonitor. It stays the same when a restore moves the app to another
+/// cluster; only its selector follows.
+pub fn pod_monitor_name(app_name: &str) -> String {
+    format!("{app_name}-db")
+}
+
+/// Parameters monitoring adds to the cluster. Setting a `pg_stat_statements.*` parameter
+/// makes CloudNativePG preload the library; values from `components.db.postgresql` win.
+pub fn parameters(config: Option<&DbConfig>) -> BTreeMap<String, String> {
+    let mut parameters = BTreeMap::new();
+    if config.is_some_and(|db| db.monitoring.is_some()) {
+        parameters.insert("pg_stat_statements.track".to_string(), "top".to_string());
+    }
+    parameters
+}
+
+/// Scrapes the cluster's metrics exporter through a Prometheus Operator PodMonitor, or
+/// removes it when monitoring or `pod_monitor` is off. Without the PodMonitor CRD the
+/// exporter is still there, which `status` points out.
+pub async fn deploy(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    config: Option<&DbConfig>,
+    status: &mut DatabaseStatus,
+) -> Result<(), Error> {
+    let wants_pod_monitor = config
+        .and_then(|db| db.monitoring.as_ref())
+        .is_some_and(|monitoring| monitoring.pod_monitor.unwrap_or(true));
+    if !wants_pod_monitor {
+        return delete(client, namespace, app_name).await;
+    }
+
+    let name = pod_monitor_name(app_name);
+    let cluster_name = database::active_cluster_name(client.clone(), namespace, app_name).await?;
+    let pod_monitor = json!({
+        "apiVersion": "monitoring.coreos.com/v1",
+        "kind": "PodMonitor",
+        "metadata": {
+            "name": name,
+            "namespace": namespace
+        },
+        "spec": {
+            "selector": {
+                "matchLabels": {
+                    "cnpg.io/cluster": cluster_name
+                }
+            },
+            "podMetricsEndpoints": [{
+                "port": METRICS_PORT_NAME
+            }]
+        }
+    });
+    match pod_monitor_api(client, namespace)
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(pod_monitor),
+        )
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(err)) if err.code == 404 => {
+            status.message.get_or_insert_with(|| {
+                "PodMonitor CRD is not installed; install the Prometheus Operator or scrape port 9187 of the database pods yourself".to_string()
+            });
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub async fn delete(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    let api = pod_monitor_api(client, namespace);
+    let name = pod_monitor_name(app_name);
+    // Also None when the PodMonitor CRD is not installed.
+    if api.get_opt(&name).await?.is_some() {
+        api.delete(&name, &DeleteParams::default()).await?;
+    }
+    Ok(())
+}
+
+fn pod_monitor_api(client: Client, namespace: &str) -> Api<DynamicObject> {
+    let gvk = GroupVersionKind::gvk("monitoring.coreos.com", "v1", "PodMonitor");
+    let resource = ApiResource::from_gvk(&gvk);
+    Api::namespaced_with(client, namespace, &resource)
+}
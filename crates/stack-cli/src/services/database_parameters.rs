@@ -0,0 +1,487 @@
+use std::net::IpAddr;
+
+use crate::operator::crd::DbPostgresqlConfig;
+
+/// How a Postgres parameter value is written.
+#[derive(Clone, Copy)]
+enum Kind {
+    Integer,
+    Real,
+    Bool,
+    /// Integer with an optional kB, MB, GB or TB unit.
+    Memory,
+    /// Integer with an optional us, ms, s, min, h or d unit.
+    Duration,
+    Enum(&'static [&'static str]),
+    Text,
+}
+
+/// Parameters that can be set from the manifest. CloudNativePG adds the library behind
+/// `pg_stat_statements.*`, `auto_explain.*` and `pgaudit.*` to shared_preload_libraries itself.
+const PARAMETERS: &[(&str, Kind)] = &[
+    ("max_connections", Kind::Integer),
+    ("superuser_reserved_connections", Kind::Integer),
+    ("shared_buffers", Kind::Memory),
+    ("effective_cache_size", Kind::Memory),
+    ("work_mem", Kind::Memory),
+    ("hash_mem_multiplier", Kind::Real),
+    ("maintenance_work_mem", Kind::Memory),
+    ("autovacuum_work_mem", Kind::Memory),
+    ("logical_decoding_work_mem", Kind::Memory),
+    ("temp_buffers", Kind::Memory),
+    ("temp_file_limit", Kind::Memory),
+    ("wal_buffers", Kind::Memory),
+    ("min_wal_size", Kind::Memory),
+    ("max_wal_size", Kind::Memory),
+    ("wal_keep_size", Kind::Memory),
+    ("max_slot_wal_keep_size", Kind::Memory),
+    (
+        "wal_compression",
+        Kind::Enum(&["on", "off", "pglz", "lz4", "zstd"]),
+    ),
+    ("wal_writer_delay", Kind::Duration),
+    ("checkpoint_timeout", Kind::Duration),
+    ("checkpoint_completion_target", Kind::Real),
+    ("huge_pages", Kind::Enum(&["on", "off", "try"])),
+    ("max_worker_processes", Kind::Integer),
+    ("max_parallel_workers", Kind::Integer),
+    ("max_parallel_workers_per_gather", Kind::Integer),
+    ("max_parallel_maintenance_workers", Kind::Integer),
+    ("max_locks_per_transaction", Kind::Integer),
+    ("max_prepared_transactions", Kind::Integer),
+    ("max_replication_slots", Kind::Integer),
+    ("max_wal_senders", Kind::Integer),
+    ("effective_io_concurrency", Kind::Integer),
+    ("random_page_cost", Kind::Real),
+    ("seq_page_cost", Kind::Real),
+    ("cpu_tuple_cost", Kind::Real),
+    ("default_statistics_target", Kind::Integer),
+    ("jit", Kind::Bool),
+    ("jit_above_cost", Kind::Real),
+    ("bgwriter_delay", Kind::Duration),
+    ("bgwriter_lru_maxpages", Kind::Integer),
+    ("deadlock_timeout", Kind::Duration),
+    ("statement_timeout", Kind::Duration),
+    ("lock_timeout", Kind::Duration),
+    ("idle_in_transaction_session_timeout", Kind::Duration),
+    ("idle_session_timeout", Kind::Duration),
+    ("tcp_keepalives_idle", Kind::Duration),
+    ("wal_sender_timeout", Kind::Duration),
+    ("wal_receiver_timeout", Kind::Duration),
+    (
+        "synchronous_commit",
+        Kind::Enum(&["on", "off", "local", "remote_write", "remote_apply"]),
+    ),
+    (
+        "default_transaction_isolation",
+        Kind::Enum(&[
+            "serializable",
+            "repeatable read",
+            "read committed",
+            "read uncommitted",
+        ]),
+    ),
+    ("autovacuum", Kind::Bool),
+    ("autovacuum_max_workers", Kind::Integer),
+    ("autovacuum_naptime", Kind::Duration),
+    ("autovacuum_vacuum_scale_factor", Kind::Real),
+    ("autovacuum_analyze_scale_factor", Kind::Real),
+    ("autovacuum_vacuum_cost_limit", Kind::Integer),
+    ("log_min_duration_statement", Kind::Duration),
+    ("log_autovacuum_min_duration", Kind::Duration),
+    ("log_temp_files", Kind::Memory),
+    ("log_checkpoints", Kind::Bool),
+    ("log_connections", Kind::Bool),
+    ("log_disconnections", Kind::Bool),
+    ("log_lock_waits", Kind::Bool),
+    ("log_statement", Kind::Enum(&["none", "ddl", "mod", "all"])),
+    (
+        "log_min_messages",
+        Kind::Enum(&[
+            "debug5", "debug4", "debug3", "debug2", "debug1", "info", "notice", "warning", "error",
+            "log", "fatal", "panic",
+        ]),
+    ),
+    ("track_io_timing", Kind::Bool),
+    ("timezone", Kind::Text),
+    ("default_text_search_config", Kind::Text),
+    ("pg_stat_statements.max", Kind::Integer),
+    (
+        "pg_stat_statements.track",
+        Kind::Enum(&["none", "top", "all"]),
+    ),
+    ("pg_stat_statements.track_utility", Kind::Bool),
+    ("auto_explain.log_min_duration", Kind::Duration),
+    ("auto_explain.log_analyze", Kind::Bool),
+    ("auto_explain.log_buffers", Kind::Bool),
+    ("auto_explain.log_nested_statements", Kind::Bool),
+    ("pgaudit.log", Kind::Text),
+    ("pgaudit.log_relation", Kind::Bool),
+    ("cron.database_name", Kind::Text),
+];
+
+/// Parameters CloudNativePG sets itself and refuses to take from the Cluster spec.
+const MANAGED_PARAMETERS: &[&str] = &[
+    "listen_addresses",
+    "port",
+    "unix_socket_directories",
+    "data_directory",
+    "hba_file",
+    "ident_file",
+    "config_file",
+    "archive_mode",
+    "archive_command",
+    "restore_command",
+    "primary_conninfo",
+    "primary_slot_name",
+    "hot_standby",
+    "wal_level",
+    "wal_log_hints",
+    "full_page_writes",
+    "logging_collector",
+    "log_destination",
+    "log_directory",
+    "log_filename",
+    "ssl",
+    "ssl_cert_file",
+    "ssl_key_file",
+    "ssl_ca_file",
+    "synchronous_standby_names",
+];
+
+/// Libraries that can go into shared_preload_libraries. They must also be in the image.
+const PRELOAD_LIBRARIES: &[&str] = &[
+    "pg_stat_statements",
+    "auto_explain",
+    "pgaudit",
+    "pg_cron",
+    "pg_failover_slots",
+    "pg_partman_bgw",
+    "pg_squeeze",
+    "pg_stat_kcache",
+    "pg_wait_sampling",
+    "pgsodium",
+    "timescaledb",
+];
+
+const HBA_TYPES: &[&str] = &[
+    "local",
+    "host",
+    "hostssl",
+    "hostnossl",
+    "hostgssenc",
+    "hostnogssenc",
+];
+
+const HBA_METHODS: &[&str] = &[
+    "trust",
+    "reject",
+    "scram-sha-256",
+    "md5",
+    "password",
+    "gss",
+    "sspi",
+    "ident",
+    "peer",
+    "ldap",
+    "radius",
+    "cert",
+    "pam",
+    "bsd",
+];
+
+/// Where in `components.db.postgresql` a problem was found.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Field {
+    Parameter(String),
+    SharedPreloadLibrary(usize),
+    PgHba(usize),
+}
+
+/// Everything wrong with the Postgres settings in the manifest.
+pub fn problems(config: &DbPostgresqlConfig) -> Vec<(Field, String)> {
+    let mut problems = Vec::new();
+
+    for (name, value) in &config.parameters {
+        if let Err(message) = check_parameter(name, value) {
+            problems.push((Field::Parameter(name.clone()), message));
+        }
+    }
+
+    for (index, library) in config.shared_preload_libraries.iter().enumerate() {
+        if !PRELOAD_LIBRARIES.contains(&library.as_str()) {
+            problems.push((
+                Field::SharedPreloadLibrary(index),
+                format!(
+                    "unknown preload library '{}'; expected one of {}",
+                    library,
+                    PRELOAD_LIBRARIES.join(", ")
+                ),
+            ));
+        }
+    }
+
+    for (index, line) in config.pg_hba.iter().enumerate() {
+        if let Err(message) = check_hba_line(line) {
+            problems.push((Field::PgHba(index), message));
+        }
+    }
+
+    problems
+}
+
+fn check_parameter(name: &str, value: &str) -> Result<(), String> {
+    if name == "shared_preload_libraries" {
+        return Err(
+            "list preload libraries under components.db.postgresql.shared_preload_libraries"
+                .to_string(),
+        );
+    }
+    if MANAGED_PARAMETERS.contains(&name) {
+        return Err(format!(
+            "{} is managed by CloudNativePG and cannot be set",
+            name
+        ));
+    }
+    let Some((_, kind)) = PARAMETERS.iter().find(|(known, _)| *known == name) else {
+        return Err(match closest_parameter(name) {
+            Some(suggestion) => format!(
+                "unknown Postgres parameter '{}'; did you mean '{}'?",
+                name, suggestion
+            ),
+            None => format!("unknown Postgres parameter '{}'", name),
+        });
+    };
+
+    let value = value.trim();
+    let valid = match kind {
+        Kind::Integer => value.parse::<i64>().is_ok(),
+        Kind::Real => value.parse::<f64>().is_ok(),
+        Kind::Bool => matches!(
+            value.to_ascii_lowercase().as_str(),
+            "on" | "off" | "true" | "false" | "yes" | "no" | "1" | "0"
+        ),
+        Kind::Memory => with_unit(value, &["", "kB", "MB", "GB", "TB"]),
+        Kind::Duration => with_unit(value, &["", "us", "ms", "s", "min", "h", "d"]),
+        Kind::Enum(allowed) => allowed.contains(&value.to_ascii_lowercase().as_str()),
+        Kind::Text => !value.is_empty(),
+    };
+    if valid {
+        return Ok(());
+    }
+
+    let expected = match kind {
+        Kind::Integer => "a whole number".to_string(),
+        Kind::Real => "a number".to_string(),
+        Kind::Bool => "on or off".to_string(),
+        Kind::Memory => "a size such as 256MB or 4GB".to_string(),
+        Kind::Duration => "a duration such as 500ms, 30s or 5min".to_string(),
+        Kind::Enum(allowed) => format!("one of {}", allowed.join(", ")),
+        Kind::Text => "a value".to_string(),
+    };
+    Err(format!("{} must be {}, got '{}'", name, expected, value))
+}
+
+/// Whether `value` is a number, optionally negative, followed by one of `units`.
+/// Postgres rounds a fractional amount such as 1.5GB, so one is allowed with a unit.
+fn with_unit(value: &str, units: &[&str]) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let split_at = digits
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(digits.len());
+    let (amount, unit) = digits.split_at(split_at);
+    let unit = unit.trim();
+    let whole = amount.bytes().all(|byte| byte.is_ascii_digit());
+    amount.parse::<f64>().is_ok()
+        && !amount.starts_with('.')
+        && !amount.ends_with('.')
+        && (whole || !unit.is_empty())
+        && units.contains(&unit)
+}
+
+/// The known parameter one or two edits away from `name`, for typo hints.
+fn closest_parameter(name: &str) -> Option<&'static str> {
+    PARAMETERS
+        .iter()
+        .map(|(known, _)| (*known, edit_distance(name, known)))
+        .filter(|(_, distance)| *distance <= 2)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(known, _)| known)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Checks the shape of a pg_hba.conf line: type, database, user, address (except for
+/// `local`), then a known auth method and optional `key=value` options.
+fn check_hba_line(line: &str) -> Result<(), String> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let Some(kind) = fields.first() else {
+        return Err("pg_hba line is empty".to_string());
+    };
+    if !HBA_TYPES.contains(kind) {
+        return Err(format!(
+            "pg_hba line must start with one of {}, got '{}'",
+            HBA_TYPES.join(", "),
+            kind
+        ));
+    }
+
+    let mut method_at = if *kind == "local" { 3 } else { 4 };
+    // An address can be written as `address mask` instead of CIDR.
+    if *kind != "local"
+        && fields
+            .get(method_at)
+            .is_some_and(|field| field.parse::<IpAddr>().is_ok())
+    {
+        method_at += 1;
+    }
+    let Some(method) = fields.get(method_at) else {
+        return Err(format!(
+            "pg_hba line '{}' is missing fields; expected `{}`",
+            line,
+            if *kind == "local" {
+                "local DATABASE USER METHOD"
+            } else {
+                "TYPE DATABASE USER ADDRESS METHOD"
+            }
+        ));
+    };
+    if !HBA_METHODS.contains(method) {
+        return Err(format!(
+            "unknown pg_hba auth method '{}'; expected one of {}",
+            method,
+            HBA_METHODS.join(", ")
+        ));
+    }
+    if let Some(option) = fields[method_at + 1..]
+        .iter()
+        .find(|option| !option.contains('='))
+    {
+        return Err(format!(
+            "pg_hba option '{}' must be written as key=value",
+            option
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(parameters: &[(&str, &str)], pg_hba: &[&str]) -> DbPostgresqlConfig {
+        DbPostgresqlConfig {
+            parameters: parameters
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            shared_preload_libraries: vec![],
+            pg_hba: pg_hba.iter().map(|line| line.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn memory_values() {
+        for value in ["-1", "256MB", "1.5GB", "8 kB", "1024"] {
+            assert!(check_parameter("work_mem", value).is_ok(), "{}", value);
+        }
+        for value in ["", "MB", "1.5", "256mb", "1..5GB", ".5GB", "10PB"] {
+            assert!(check_parameter("work_mem", value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn duration_values() {
+        for value in ["-1", "500ms", "30s", "5min", "0.5h", "0"] {
+            assert!(
+                check_parameter("statement_timeout", value).is_ok(),
+                "{}",
+                value
+            );
+        }
+        for value in ["5m", "30 seconds", "1.5"] {
+            assert!(
+                check_parameter("statement_timeout", value).is_err(),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn other_kinds() {
+        assert!(check_parameter("max_connections", "200").is_ok());
+        assert!(check_parameter("max_connections", "lots").is_err());
+        assert!(check_parameter("jit", "OFF").is_ok());
+        assert!(check_parameter("jit", "maybe").is_err());
+        assert!(check_parameter("synchronous_commit", "remote_apply").is_ok());
+        assert!(check_parameter("synchronous_commit", "sometimes").is_err());
+    }
+
+    #[test]
+    fn managed_parameters_are_rejected() {
+        let message = check_parameter("wal_level", "logical").unwrap_err();
+        assert!(message.contains("managed by CloudNativePG"), "{}", message);
+        let message = check_parameter("shared_preload_libraries", "pgaudit").unwrap_err();
+        assert!(message.contains("shared_preload_libraries"), "{}", message);
+    }
+
+    #[test]
+    fn typos_get_a_suggestion() {
+        let message = check_parameter("shared_buffer", "256MB").unwrap_err();
+        assert!(
+            message.contains("did you mean 'shared_buffers'"),
+            "{}",
+            message
+        );
+        let message = check_parameter("completely_unknown", "1").unwrap_err();
+        assert!(!message.contains("did you mean"), "{}", message);
+    }
+
+    #[test]
+    fn hba_lines() {
+        for line in [
+            "host all all 10.0.0.0/8 scram-sha-256",
+            "host app app 10.0.0.0 255.0.0.0 md5",
+            "hostssl all all ::1/128 cert clientcert=verify-full",
+            "local all postgres peer",
+        ] {
+            assert!(check_hba_line(line).is_ok(), "{}", line);
+        }
+        for line in [
+            "",
+            "remote all all 10.0.0.0/8 md5",
+            "host all all 10.0.0.0/8",
+            "host all all 10.0.0.0 255.0.0.0",
+            "host all all 10.0.0.0/8 letmein",
+            "host all all 10.0.0.0/8 md5 verbose",
+        ] {
+            assert!(check_hba_line(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn problems_point_at_the_field() {
+        let problems = problems(&config(
+            &[("work_mem", "lots"), ("shared_buffers", "1GB")],
+            &["host all all 10.0.0.0/8 md5", "host all all"],
+        ));
+        let fields: Vec<Field> = problems.into_iter().map(|(field, _)| field).collect();
+        assert_eq!(
+            fields,
+            vec![Field::Parameter("work_mem".to_string()), Field::PgHba(1)]
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use crate::error::Error;
+use crate::operator::crd::{DbPoolerConfig, PoolMode};
+use crate::services::database;
+use crate::services::database_backup::ClusterReference;
+use kube::api::{DeleteParams, Patch, PatchParams};
+use kube::{Api, Client, CustomResource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+const DEFAULT_POOL_SIZE: i32 = 20;
+const DEFAULT_MAX_CLIENT_CONNECTIONS: i32 = 1000;
+
+/// Corresponds to the CloudNativePG Pooler resource
+#[derive(CustomResource, Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+#[kube(
+    group = "postgresql.cnpg.io",
+    version = "v1",
+    kind = "Pooler",
+    plural = "poolers",
+    derive = "PartialEq",
+    namespaced
+)]
+pub struct PoolerSpec {
+    pub cluster: ClusterReference,
+    pub instances: i32,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub pgbouncer: PgBouncerSpec,
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct PgBouncerSpec {
+    #[serde(rename = "poolMode")]
+    pub pool_mode: String,
+    pub parameters: BTreeMap<String, String>,
+}
+
+/// Name of the Pooler and of the Service in front of it. It stays the same when a
+/// restore moves the app to another cluster.
+pub fn pooler_name(app_name: &str) -> String {
+    format!("{app_name}-db-pooler")
+}
+
+/// Host for `application-pooled-url`: the pooler when there is one, otherwise the primary.
+pub fn pooled_host(app_name: &str, cluster_name: &str, config: Option<&DbPoolerConfig>) -> String {
+    if config.is_some() {
+        pooler_name(app_name)
+    } else {
+        format!("{}-rw", cluster_name)
+    }
+}
+
+/// The Pooler spec for `components.db.pooler`.
+pub fn pooler_spec(cluster_name: &str, config: &DbPoolerConfig) -> Result<PoolerSpec, Error> {
+    let instances = config.instances.unwrap_or(1);
+    let pool_size = config.pool_size.unwrap_or(DEFAULT_POOL_SIZE);
+    let max_client_connections = config
+        .max_client_connections
+        .unwrap_or(DEFAULT_MAX_CLIENT_CONNECTIONS);
+    for (field, value) in [
+        ("instances", instances),
+        ("pool_size", pool_size),
+        ("max_client_connections", max_client_connections),
+    ] {
+        if value < 1 {
+            return Err(Error::Other(format!(
+                "components.db.pooler.{} must be at least 1, got {}",
+                field, value
+            )));
+        }
+    }
+    if max_client_connections < pool_size {
+        return Err(Error::Other(format!(
+            "components.db.pooler.max_client_connections ({}) must be at least pool_size ({})",
+            max_client_connections, pool_size
+        )));
+    }
+
+    let pool_mode = match config.mode.unwrap_or_default() {
+        PoolMode::Transaction => "transaction",
+        PoolMode::Session => "session",
+    };
+    let mut parameters = BTreeMap::new();
+    parameters.insert("default_pool_size".to_string(), pool_size.to_string());
+    parameters.insert(
+        "max_client_conn".to_string(),
+        max_client_connections.to_string(),
+    );
+
+    Ok(PoolerSpec {
+        cluster: ClusterReference {
+            name: cluster_name.to_string(),
+        },
+        instances,
+        type_: "rw".to_string(),
+        pgbouncer: PgBouncerSpec {
+            pool_mode: pool_mode.to_string(),
+            parameters,
+        },
+    })
+}
+
+/// Runs PgBouncer in front of the app cluster's primary, or removes it when
+/// `components.db.pooler` is unset.
+pub async fn deploy(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    config: Option<&DbPoolerConfig>,
+) -> Result<(), Error> {
+    let pooler_api: Api<Pooler> = Api::namespaced(client.clone(), namespace);
+    let name = pooler_name(app_name);
+
+    let Some(config) = config else {
+        return delete(client, namespace, app_name).await;
+    };
+
+    let cluster_name = database::active_cluster_name(client, namespace, app_name).await?;
+    let spec = pooler_spec(&cluster_name, config)?;
+    let pooler = json!({
+        "apiVersion": "postgresql.cnpg.io/v1",
+        "kind": "Pooler",
+        "metadata": {
+            "name": name,
+            "namespace": namespace
+        },
+        "spec": spec
+    });
+    match pooler_api
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(pooler),
+        )
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(err)) if err.code == 404 => {
+            Err(Error::DependencyMissing(database::CNPG_INSTALL_HINT))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub async fn delete(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    let api: Api<Pooler> = Api::namespaced(client, namespace);
+    let name = pooler_name(app_name);
+    if api.get_opt(&name).await?.is_some() {
+        api.delete(&name, &DeleteParams::default()).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,292 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::error::Error;
+use crate::operator::crd::{DatabaseStatus, DbSeedConfig};
+use crate::services::content_hash::content_hash;
+use crate::services::database::DATABASE_URLS_SECRET_NAME;
+use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::{ConfigMap, Secret};
+use kube::api::{DeleteParams, Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::json;
+
+const SEED_IMAGE: &str = "postgres:16-alpine";
+const SEED_HASH_ANNOTATION: &str = "stack-cli.dev/seed-hash";
+/// Set on `database-urls` once a seed has loaded, so each seed content runs only once
+/// even when its Job is cleaned up.
+const SEEDED_HASH_ANNOTATION: &str = "stack-cli.dev/seeded-hash";
+const SEED_SQL_KEY: &str = "seed.sql";
+const SEED_MOUNT_PATH: &str = "/seed";
+
+/// Name of the seed Job, and of the ConfigMap holding inline `sql`.
+pub fn job_name(app_name: &str) -> String {
+    format!("{app_name}-db-seed")
+}
+
+/// Problems with `components.db.seed`.
+pub fn problems(config: &DbSeedConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+    let sources = [
+        config.sql.is_some(),
+        config.config_map.is_some(),
+        config.image.is_some(),
+    ];
+    if sources.iter().filter(|set| **set).count() != 1 {
+        problems.push("set exactly one of sql, config_map or image".to_string());
+    }
+    if config.command.is_some() && config.image.is_none() {
+        problems.push("command only applies together with image".to_string());
+    }
+    problems
+}
+
+/// Loads `components.db.seed` into the application database through a Job, once the
+/// database is ready and the web Deployment, and with it its migrations, has rolled out.
+/// Each seed content is loaded once; changing the SQL, the ConfigMap or the image runs
+/// the new seed.
+pub async fn deploy(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    config: Option<&DbSeedConfig>,
+    status: &mut DatabaseStatus,
+) -> Result<(), Error> {
+    let Some(config) = config else {
+        return delete(client, namespace, app_name).await;
+    };
+    if let Some(problem) = problems(config).into_iter().next() {
+        return Err(Error::Other(format!("components.db.seed: {}", problem)));
+    }
+    if status.ready_instances < 1 {
+        return Ok(());
+    }
+
+    let name = job_name(app_name);
+    let config_map_api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    // What was hashed before seed hashes moved to content_hash, so seeds recorded then
+    // are still recognised and do not run again.
+    let mut legacy_hasher = DefaultHasher::new();
+    let content;
+    let config_map_name = if let Some(sql) = &config.sql {
+        sql.hash(&mut legacy_hasher);
+        content = json!({ "sql": sql });
+        let config_map = json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {
+                "name": name,
+                "namespace": namespace
+            },
+            "data": {
+                SEED_SQL_KEY: sql
+            }
+        });
+        config_map_api
+            .patch(
+                &name,
+                &PatchParams::apply(crate::MANAGER).force(),
+                &Patch::Apply(config_map),
+            )
+            .await?;
+        Some(name.clone())
+    } else if config_map_api.get_opt(&name).await?.is_some() {
+        // Left over from inline sql.
+        config_map_api
+            .delete(&name, &DeleteParams::default())
+            .await?;
+        return Ok(());
+    } else if let Some(config_map_name) = &config.config_map {
+        let Some(config_map) = config_map_api.get_opt(config_map_name).await? else {
+            status.message.get_or_insert_with(|| {
+                format!("Seed ConfigMap {} does not exist", config_map_name)
+            });
+            return Ok(());
+        };
+        config_map.data.hash(&mut legacy_hasher);
+        content = json!({ "config_map": config_map.data });
+        Some(config_map_name.clone())
+    } else {
+        config.image.hash(&mut legacy_hasher);
+        config.command.hash(&mut legacy_hasher);
+        content = json!({ "image": config.image, "command": config.command });
+        None
+    };
+    let seed_hash = content_hash(&content);
+    let legacy_hash = format!("{:x}", legacy_hasher.finish());
+
+    let secret_api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let Some(urls) = secret_api.get_opt(DATABASE_URLS_SECRET_NAME).await? else {
+        return Ok(());
+    };
+    match urls.annotations().get(SEEDED_HASH_ANNOTATION) {
+        Some(seeded) if *seeded == seed_hash => return Ok(()),
+        Some(seeded) if *seeded == legacy_hash => {
+            let patch = json!({
+                "metadata": {
+                    "annotations": {
+                        SEEDED_HASH_ANNOTATION: seed_hash
+                    }
+                }
+            });
+            secret_api
+                .patch(
+                    DATABASE_URLS_SECRET_NAME,
+                    &PatchParams::default(),
+                    &Patch::Merge(&patch),
+                )
+                .await?;
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let job_api: Api<Job> = Api::namespaced(client.clone(), namespace);
+    if let Some(job) = job_api.get_opt(&name).await? {
+        let job_hash = job.annotations().get(SEED_HASH_ANNOTATION).cloned();
+        if job_hash.as_deref() != Some(seed_hash.as_str()) {
+            // The replacement is created on the next reconcile, once this one is gone.
+            job_api.delete(&name, &DeleteParams::background()).await?;
+            return Ok(());
+        }
+
+        let job_status = job.status.unwrap_or_default();
+        if job_status.succeeded.unwrap_or(0) > 0 {
+            let patch = json!({
+                "metadata": {
+                    "annotations": {
+                        SEEDED_HASH_ANNOTATION: seed_hash
+                    }
+                }
+            });
+            secret_api
+                .patch(
+                    DATABASE_URLS_SECRET_NAME,
+                    &PatchParams::default(),
+                    &Patch::Merge(&patch),
+                )
+                .await?;
+        } else if job_status
+            .conditions
+            .iter()
+            .flatten()
+            .any(|condition| condition.type_ == "Failed" && condition.status == "True")
+        {
+            status.message.get_or_insert_with(|| {
+                format!(
+                    "Seeding the database failed; see `kubectl -n {} logs job/{}`",
+                    namespace, name
+                )
+            });
+        }
+        return Ok(());
+    }
+
+    if !web_rolled_out(client.clone(), namespace, app_name).await? {
+        return Ok(());
+    }
+
+    let database_url_env = json!({
+        "name": "DATABASE_URL",
+        "valueFrom": {
+            "secretKeyRef": {
+                "name": DATABASE_URLS_SECRET_NAME,
+                "key": "migrations-url"
+            }
+        }
+    });
+    let mut container = json!({
+        "name": "seed",
+        "imagePullPolicy": "IfNotPresent",
+        "env": [database_url_env]
+    });
+    let mut volumes = Vec::new();
+    if let Some(config_map_name) = config_map_name {
+        container["image"] = json!(SEED_IMAGE);
+        container["command"] = json!(["/bin/sh", "-c"]);
+        container["args"] = json!([format!(
+            "set -e\nfor file in {}/*; do\n  echo \"Loading $file\"\n  psql \"$DATABASE_URL\" -v ON_ERROR_STOP=1 --single-transaction -f \"$file\"\ndone",
+            SEED_MOUNT_PATH
+        )]);
+        container["volumeMounts"] = json!([{
+            "name": "seed",
+            "mountPath": SEED_MOUNT_PATH,
+            "readOnly": true
+        }]);
+        volumes.push(json!({
+            "name": "seed",
+            "configMap": { "name": config_map_name }
+        }));
+    } else {
+        container["image"] = json!(config.image);
+        if let Some(command) = &config.command {
+            container["command"] = json!(command);
+        }
+    }
+
+    let job = json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+            "annotations": {
+                SEED_HASH_ANNOTATION: seed_hash
+            }
+        },
+        "spec": {
+            "backoffLimit": 2,
+            "template": {
+                "spec": {
+                    "restartPolicy": "Never",
+                    "containers": [container],
+                    "volumes": volumes
+                }
+            }
+        }
+    });
+    job_api
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(job),
+        )
+        .await?;
+    status
+        .message
+        .get_or_insert_with(|| "Seeding the database".to_string());
+    Ok(())
+}
+
+/// Whether the current revision of the web Deployment, named after the app, has a pod
+/// available. Its init container runs the migrations the seed data depends on.
+pub async fn web_rolled_out(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+) -> Result<bool, Error> {
+    let deployment_api: Api<Deployment> = Api::namespaced(client, namespace);
+    let Some(deployment) = deployment_api.get_opt(app_name).await? else {
+        return Ok(false);
+    };
+    let status = deployment.status.unwrap_or_default();
+    Ok(status.observed_generation >= deployment.metadata.generation
+        && status.updated_replicas.unwrap_or(0) > 0
+        && status.available_replicas.unwrap_or(0) > 0)
+}
+
+pub async fn delete(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    let name = job_name(app_name);
+    let job_api: Api<Job> = Api::namespaced(client.clone(), namespace);
+    if job_api.get_opt(&name).await?.is_some() {
+        job_api.delete(&name, &DeleteParams::background()).await?;
+    }
+    let config_map_api: Api<ConfigMap> = Api::namespaced(client, namespace);
+    if config_map_api.get_opt(&name).await?.is_some() {
+        config_map_api
+            .delete(&name, &DeleteParams::default())
+            .await?;
+    }
+    Ok(())
+}
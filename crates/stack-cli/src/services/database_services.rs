@@ -0,0 +1,451 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::Error;
+use crate::operator::crd::{DatabaseStatus, ServiceDatabaseConfig, StackAppSpec};
+use crate::services::content_hash::content_hash;
+use crate::services::credentials;
+use crate::services::database::{
+    self, DATABASE_HOST_KEY, DATABASE_PORT_KEY, DATABASE_SSLMODE_KEY, DATABASE_URLS_SECRET_NAME,
+    DEFAULT_DB_PORT,
+};
+use crate::services::deployment;
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::Secret;
+use kube::api::{DeleteParams, ListParams, ObjectMeta, Patch, PatchParams, PostParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::json;
+
+const SERVICES_IMAGE: &str = "postgres:16-alpine";
+const SQL_HASH_ANNOTATION: &str = "stack-cli.dev/sql-hash";
+/// Marks the per-service secrets, so ones whose service no longer asks for a database
+/// can be found and removed.
+pub const SERVICE_LABEL: &str = "stack-cli.dev/service-database";
+const SQL_KEY: &str = "services.sql";
+const SQL_MOUNT_PATH: &str = "/sql";
+pub const DEFAULT_URL_ENV: &str = "DATABASE_URL";
+/// Schemas used by the platform components or Postgres itself.
+const RESERVED_SCHEMAS: [&str; 8] = [
+    "public",
+    "realtime",
+    "auth",
+    "storage",
+    "stack_migrations",
+    "stack_webhooks",
+    "information_schema",
+    "extensions",
+];
+const RESERVED_DATABASES: [&str; 3] = ["postgres", "template0", "template1"];
+
+/// Name of the Job creating the roles, schemas and databases, and of the Secret holding
+/// its SQL.
+pub fn job_name(app_name: &str) -> String {
+    format!("{app_name}-db-services")
+}
+
+/// Secret with `username`, `password` and `url` of a service's role.
+pub fn secret_name(service: &str) -> String {
+    format!("{service}-db")
+}
+
+/// Login role of a service.
+pub fn role_name(service: &str) -> String {
+    format!("{}_service", service.replace('-', "_"))
+}
+
+pub fn url_env(config: &ServiceDatabaseConfig) -> &str {
+    config.url_env.as_deref().unwrap_or(DEFAULT_URL_ENV)
+}
+
+/// Services asking for a database of their own; `services.web` is named after the app.
+pub fn service_databases<'a>(
+    spec: &'a StackAppSpec,
+    app_name: &str,
+) -> Vec<(String, &'a ServiceDatabaseConfig)> {
+    std::iter::once((app_name.to_string(), &spec.services.web))
+        .chain(
+            spec.services
+                .extra
+                .iter()
+                .map(|(name, service)| (name.clone(), service)),
+        )
+        .filter_map(|(name, service)| service.database.as_ref().map(|config| (name, config)))
+        .collect()
+}
+
+/// Problems with the `database` of each service, paired with the service name.
+pub fn problems(
+    app_name: &str,
+    services: &[(String, &ServiceDatabaseConfig)],
+) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    let mut claimed: HashMap<(bool, &str), &str> = HashMap::new();
+    for (service, config) in services {
+        let mut problem = |message: String| problems.push((service.clone(), message));
+        let target = match (&config.schema, &config.name) {
+            (Some(schema), None) => {
+                if RESERVED_SCHEMAS.contains(&schema.as_str()) {
+                    problem(format!("schema '{}' is used by the platform", schema));
+                }
+                (false, schema.as_str())
+            }
+            (None, Some(name)) => {
+                if RESERVED_DATABASES.contains(&name.as_str())
+                    || name == &database::database_name(app_name)
+                {
+                    problem(format!("database '{}' already exists", name));
+                }
+                (true, name.as_str())
+            }
+            _ => {
+                problem("set exactly one of schema or name".to_string());
+                continue;
+            }
+        };
+        if !is_identifier(target.1) {
+            problem(format!(
+                "'{}' should be lowercase letters, digits and `_`, starting with a letter",
+                target.1
+            ));
+        }
+        if let Some(other) = claimed.insert(target, service.as_str()) {
+            problem(format!(
+                "'{}' is already used by service {}",
+                target.1, other
+            ));
+        }
+    }
+    problems
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.len() <= 63
+        && !name.starts_with("pg_")
+        && name.starts_with(|ch: char| ch.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_')
+}
+
+/// Gives each service that asks for it a login role owning its schema or database, and a
+/// `{service}-db` secret with its URL. Roles, schemas and databases are kept when a
+/// service stops asking for them, so their data is not lost; only the secret goes.
+pub async fn deploy(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    spec: &StackAppSpec,
+    status: &mut DatabaseStatus,
+) -> Result<(), Error> {
+    let services = service_databases(spec, app_name);
+    if let Some((service, problem)) = problems(app_name, &services).into_iter().next() {
+        return Err(Error::Other(format!(
+            "services.{}.database: {}",
+            service, problem
+        )));
+    }
+
+    let secret_api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let stale = secret_api
+        .list(&ListParams::default().labels(SERVICE_LABEL))
+        .await?
+        .items
+        .into_iter()
+        .filter(|secret| {
+            let service = secret.labels().get(SERVICE_LABEL);
+            !services.iter().any(|(name, _)| Some(name) == service)
+        });
+    for secret in stale {
+        secret_api
+            .delete(&secret.name_any(), &DeleteParams::default())
+            .await?;
+    }
+
+    if services.is_empty() {
+        return delete_job(client, namespace, app_name).await;
+    }
+    let Some(urls) = secret_api.get_opt(DATABASE_URLS_SECRET_NAME).await? else {
+        return Ok(());
+    };
+    let host = read_secret_field(&urls, DATABASE_HOST_KEY)
+        .unwrap_or_else(|| database::cluster_rw_service_name(app_name));
+    let port =
+        read_secret_field(&urls, DATABASE_PORT_KEY).unwrap_or_else(|| DEFAULT_DB_PORT.to_string());
+    let sslmode =
+        read_secret_field(&urls, DATABASE_SSLMODE_KEY).unwrap_or_else(|| "disable".to_string());
+
+    let mut sql = String::new();
+    let mut changed = Vec::new();
+    for (service, config) in &services {
+        let name = secret_name(service);
+        let role = role_name(service);
+        let password = secret_api
+            .get_opt(&name)
+            .await?
+            .and_then(|secret| read_secret_field(&secret, "password"))
+            .unwrap_or_else(credentials::password);
+        let db_name = config
+            .name
+            .clone()
+            .unwrap_or_else(|| database::database_name(app_name));
+
+        let mut data = BTreeMap::new();
+        data.insert("username".to_string(), role.clone());
+        data.insert("password".to_string(), password.clone());
+        data.insert(
+            "url".to_string(),
+            format!(
+                "postgres://{}:{}@{}:{}/{}?sslmode={}",
+                role, password, host, port, db_name, sslmode
+            ),
+        );
+        if upsert_secret(&secret_api, namespace, &name, service, data).await? {
+            changed.push(name);
+        }
+        sql.push_str(&service_sql(&role, &password, config));
+    }
+    if !changed.is_empty() {
+        // Pods only read the secret at start, e.g. the host after a switch to a restored
+        // cluster.
+        let changed: Vec<&str> = changed.iter().map(String::as_str).collect();
+        deployment::restart_secret_consumers(client.clone(), namespace, &changed).await?;
+    }
+
+    if status.ready_instances < 1 {
+        return Ok(());
+    }
+    run_sql(client, namespace, app_name, &sql, status).await
+}
+
+/// Idempotent statements creating `role` and the schema or database it owns. The
+/// role's password is set on every run, so the secret stays the source of truth.
+fn service_sql(role: &str, password: &str, config: &ServiceDatabaseConfig) -> String {
+    let mut sql = format!(
+        "DO $$ BEGIN\n\
+         IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = '{role}') THEN CREATE ROLE \"{role}\" LOGIN; END IF;\n\
+         END $$;\n\
+         ALTER ROLE \"{role}\" WITH LOGIN PASSWORD '{password}';\n",
+        role = role,
+        password = password.replace('\'', "''")
+    );
+    if let Some(schema) = &config.schema {
+        sql.push_str(&format!(
+            "CREATE SCHEMA IF NOT EXISTS \"{schema}\" AUTHORIZATION \"{role}\";\n\
+             ALTER SCHEMA \"{schema}\" OWNER TO \"{role}\";\n\
+             REVOKE ALL ON SCHEMA \"{schema}\" FROM PUBLIC;\n\
+             ALTER ROLE \"{role}\" SET search_path = \"{schema}\";\n",
+            schema = schema,
+            role = role
+        ));
+    } else if let Some(name) = &config.name {
+        // CREATE DATABASE cannot run inside a DO block; \gexec runs the generated statement.
+        sql.push_str(&format!(
+            "SELECT 'CREATE DATABASE \"{name}\" OWNER \"{role}\"' WHERE NOT EXISTS (SELECT 1 FROM pg_database WHERE datname = '{name}')\\gexec\n\
+             ALTER DATABASE \"{name}\" OWNER TO \"{role}\";\n\
+             REVOKE ALL ON DATABASE \"{name}\" FROM PUBLIC;\n\
+             GRANT CONNECT ON DATABASE \"{name}\" TO \"{role}\";\n",
+            name = name,
+            role = role
+        ));
+    }
+    sql
+}
+
+/// Runs `sql` as `db-owner` through a Job, again whenever it changes.
+async fn run_sql(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    sql: &str,
+    status: &mut DatabaseStatus,
+) -> Result<(), Error> {
+    let name = job_name(app_name);
+    let sql_hash = content_hash(sql);
+
+    let job_api: Api<Job> = Api::namespaced(client.clone(), namespace);
+    if let Some(job) = job_api.get_opt(&name).await? {
+        let job_hash = job.annotations().get(SQL_HASH_ANNOTATION).cloned();
+        if job_hash.as_deref() != Some(sql_hash.as_str()) {
+            // The replacement is created on the next reconcile, once this one is gone.
+            job_api.delete(&name, &DeleteParams::background()).await?;
+            return Ok(());
+        }
+        let failed = job
+            .status
+            .unwrap_or_default()
+            .conditions
+            .iter()
+            .flatten()
+            .any(|condition| condition.type_ == "Failed" && condition.status == "True");
+        if failed {
+            status.message.get_or_insert_with(|| {
+                format!(
+                    "Creating the service databases failed; see `kubectl -n {} logs job/{}`",
+                    namespace, name
+                )
+            });
+        }
+        return Ok(());
+    }
+
+    let mut data = BTreeMap::new();
+    data.insert(SQL_KEY.to_string(), sql.to_string());
+    let secret_api: Api<Secret> = Api::namespaced(client, namespace);
+    let patch = json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": {
+            "name": name,
+            "namespace": namespace
+        },
+        "stringData": data
+    });
+    secret_api
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(patch),
+        )
+        .await?;
+
+    let job = json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+            "annotations": {
+                SQL_HASH_ANNOTATION: sql_hash
+            }
+        },
+        "spec": {
+            "backoffLimit": 4,
+            "template": {
+                "spec": {
+                    "restartPolicy": "Never",
+                    "containers": [{
+                        "name": "services",
+                        "image": SERVICES_IMAGE,
+                        "imagePullPolicy": "IfNotPresent",
+                        "command": ["/bin/sh", "-c"],
+                        "args": [format!(
+                            "psql \"$DATABASE_URL\" -v ON_ERROR_STOP=1 -f {}/{}",
+                            SQL_MOUNT_PATH, SQL_KEY
+                        )],
+                        "env": [{
+                            "name": "DATABASE_URL",
+                            "valueFrom": {
+                                "secretKeyRef": {
+                                    "name": DATABASE_URLS_SECRET_NAME,
+                                    "key": "migrations-url"
+                                }
+                            }
+                        }],
+                        "volumeMounts": [{
+                            "name": "sql",
+                            "mountPath": SQL_MOUNT_PATH,
+                            "readOnly": true
+                        }]
+                    }],
+                    "volumes": [{
+                        "name": "sql",
+                        "secret": { "secretName": name }
+                    }]
+                }
+            }
+        }
+    });
+    job_api
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(job),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Creates or updates a service's secret, returning whether an existing one changed.
+async fn upsert_secret(
+    secret_api: &Api<Secret>,
+    namespace: &str,
+    name: &str,
+    service: &str,
+    data: BTreeMap<String, String>,
+) -> Result<bool, Error> {
+    match secret_api.get_opt(name).await? {
+        Some(existing) => {
+            if data
+                .iter()
+                .any(|(key, value)| read_secret_field(&existing, key).as_ref() != Some(value))
+            {
+                let patch = json!({ "stringData": data });
+                secret_api
+                    .patch(name, &PatchParams::default(), &Patch::Merge(&patch))
+                    .await?;
+                return Ok(true);
+            }
+        }
+        None => {
+            let secret = Secret {
+                metadata: ObjectMeta {
+                    name: Some(name.to_string()),
+                    namespace: Some(namespace.to_string()),
+                    labels: Some(BTreeMap::from([(
+                        SERVICE_LABEL.to_string(),
+                        service.to_string(),
+                    )])),
+                    ..ObjectMeta::default()
+                },
+                string_data: Some(data),
+                ..Default::default()
+            };
+            secret_api.create(&PostParams::default(), &secret).await?;
+        }
+    }
+    Ok(false)
+}
+
+/// Removes the Job and the secret holding its SQL.
+pub async fn delete_job(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    let name = job_name(app_name);
+    let job_api: Api<Job> = Api::namespaced(client.clone(), namespace);
+    if job_api.get_opt(&name).await?.is_some() {
+        job_api.delete(&name, &DeleteParams::background()).await?;
+    }
+    let secret_api: Api<Secret> = Api::namespaced(client, namespace);
+    if secret_api.get_opt(&name).await?.is_some() {
+        secret_api.delete(&name, &DeleteParams::default()).await?;
+    }
+    Ok(())
+}
+
+/// Removes the Job and every per-service secret. Roles, schemas and databases go with
+/// the cluster.
+pub async fn delete(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    delete_job(client.clone(), namespace, app_name).await?;
+    let secret_api: Api<Secret> = Api::namespaced(client, namespace);
+    let secrets = secret_api
+        .list(&ListParams::default().labels(SERVICE_LABEL))
+        .await?;
+    for secret in secrets.items {
+        secret_api
+            .delete(&secret.name_any(), &DeleteParams::default())
+            .await?;
+    }
+    Ok(())
+}
+
+fn read_secret_field(secret: &Secret, key: &str) -> Option<String> {
+    if let Some(data) = &secret.data {
+        if let Some(value) = data.get(key) {
+            if let Ok(val) = String::from_utf8(value.0.clone()) {
+                return Some(val);
+            }
+        }
+    }
+
+    secret
+        .string_data
+        .as_ref()
+        .and_then(|map| map.get(key).cloned())
+}
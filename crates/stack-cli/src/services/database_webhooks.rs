@@ -0,0 +1,653 @@
+use std::collections::BTreeMap;
+
+use crate::error::Error;
+use crate::operator::crd::{DatabaseStatus, DbWebhookConfig, WebhookEvent};
+use crate::services::content_hash::content_hash;
+use crate::services::database::DATABASE_URLS_SECRET_NAME;
+use crate::services::{database_seed, deployment};
+use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::Secret;
+use kube::api::{DeleteParams, Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::json;
+
+const WEBHOOKS_IMAGE: &str = "postgres:16-alpine";
+const SQL_HASH_ANNOTATION: &str = "stack-cli.dev/sql-hash";
+const SQL_KEY: &str = "webhooks.sql";
+const SCRIPT_KEY: &str = "dispatch.sh";
+const MOUNT_PATH: &str = "/webhooks";
+/// Table the triggers queue changes in and the dispatcher records its attempts in.
+const DELIVERIES_TABLE: &str = "stack_webhooks.deliveries";
+const TRIGGER_PREFIX: &str = "stack_webhook_";
+/// Postgres truncates identifiers to 63 bytes, which trigger names must fit in.
+const MAX_NAME_LENGTH: usize = 63 - TRIGGER_PREFIX.len();
+/// Attempts before a delivery is marked failed. The wait doubles after each, from 10s.
+const MAX_ATTEMPTS: u32 = 5;
+const REQUEST_TIMEOUT_SECS: u32 = 10;
+/// Delivered rows are pruned after this long; failed ones stay until removed by hand.
+const DELIVERED_RETENTION: &str = "7 days";
+
+/// Name of the Job installing the triggers, and of the Secret holding its SQL.
+pub fn job_name(app_name: &str) -> String {
+    format!("{app_name}-db-webhooks")
+}
+
+/// Name of the dispatcher Deployment, and of the Secret holding its script.
+pub fn dispatcher_name(app_name: &str) -> String {
+    format!("{app_name}-webhook-dispatcher")
+}
+
+/// Problems with `components.db.webhooks`, as (webhook, problem).
+pub fn problems(webhooks: &BTreeMap<String, DbWebhookConfig>) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    for (name, webhook) in webhooks {
+        let mut problem = |message: String| problems.push((name.clone(), message));
+        if name.is_empty()
+            || name.len() > MAX_NAME_LENGTH
+            || !name
+                .chars()
+                .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_')
+        {
+            problem(format!(
+                "name it with up to {} lowercase letters, digits and `_`",
+                MAX_NAME_LENGTH
+            ));
+        }
+        let parts: Vec<&str> = webhook.table.split('.').collect();
+        if parts.len() > 2 || !parts.iter().all(|part| is_identifier(part)) {
+            problem(format!(
+                "table {} should be a table name, optionally with its schema, e.g. billing.invoices",
+                webhook.table
+            ));
+        }
+        if !(webhook.url.starts_with("http://") || webhook.url.starts_with("https://"))
+            || webhook.url.contains(char::is_whitespace)
+        {
+            problem(format!(
+                "url {} should be an http:// or https:// URL",
+                webhook.url
+            ));
+        }
+        for (header, value) in &webhook.headers {
+            if header.is_empty()
+                || !header
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+            {
+                problem(format!("header {} is not a valid header name", header));
+            }
+            if value.contains(['\r', '\n']) {
+                problem(format!("header {} cannot span lines", header));
+            }
+        }
+    }
+    problems
+}
+
+fn is_identifier(value: &str) -> bool {
+    value.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_')
+        && value
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// Installs a trigger per webhook queueing row changes in `stack_webhooks.deliveries`,
+/// and runs a dispatcher POSTing them. The triggers are installed once the migrations
+/// creating their tables have run, so after `spec.migrations` and the web rollout.
+/// Removed webhooks lose their trigger; queued deliveries for them are marked failed.
+pub async fn deploy(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    webhooks: Option<&BTreeMap<String, DbWebhookConfig>>,
+    migrated: bool,
+    status: &mut DatabaseStatus,
+) -> Result<(), Error> {
+    let webhooks = webhooks.cloned().unwrap_or_default();
+    if let Some((webhook, problem)) = problems(&webhooks).into_iter().next() {
+        return Err(Error::Other(format!(
+            "components.db.webhooks.{}: {}",
+            webhook, problem
+        )));
+    }
+
+    let job_api: Api<Job> = Api::namespaced(client.clone(), namespace);
+    let name = job_name(app_name);
+    if webhooks.is_empty() {
+        delete_dispatcher(client.clone(), namespace, app_name).await?;
+        // Triggers were only ever installed if the Job's Secret exists; then the Job
+        // runs once more to drop them.
+        let secret_api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+        if secret_api.get_opt(&name).await?.is_none() {
+            return Ok(());
+        }
+    } else {
+        deploy_dispatcher(client.clone(), namespace, app_name, &webhooks).await?;
+    }
+    if status.ready_instances < 1 {
+        return Ok(());
+    }
+
+    let sql = install_sql(&webhooks);
+    let sql_hash = content_hash(&sql);
+
+    if let Some(job) = job_api.get_opt(&name).await? {
+        let job_hash = job.annotations().get(SQL_HASH_ANNOTATION).cloned();
+        if job_hash.as_deref() != Some(sql_hash.as_str()) {
+            // The replacement is created on the next reconcile, once this one is gone.
+            job_api.delete(&name, &DeleteParams::background()).await?;
+            return Ok(());
+        }
+        let failed = job
+            .status
+            .unwrap_or_default()
+            .conditions
+            .iter()
+            .flatten()
+            .any(|condition| condition.type_ == "Failed" && condition.status == "True");
+        if failed {
+            status.message.get_or_insert_with(|| {
+                format!(
+                    "Installing the webhook triggers failed; see `kubectl -n {} logs job/{}`",
+                    namespace, name
+                )
+            });
+        }
+        return Ok(());
+    }
+    if !migrated || !database_seed::web_rolled_out(client.clone(), namespace, app_name).await? {
+        return Ok(());
+    }
+
+    apply_secret(client.clone(), namespace, &name, SQL_KEY, sql).await?;
+    let job = json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+            "annotations": {
+                SQL_HASH_ANNOTATION: sql_hash
+            }
+        },
+        "spec": {
+            "backoffLimit": 4,
+            "template": {
+                "spec": {
+                    "restartPolicy": "Never",
+                    "containers": [{
+                        "name": "webhooks",
+                        "image": WEBHOOKS_IMAGE,
+                        "imagePullPolicy": "IfNotPresent",
+                        "command": ["/bin/sh", "-c"],
+                        "args": [format!(
+                            "psql \"$DATABASE_URL\" -v ON_ERROR_STOP=1 --single-transaction -f {}/{}",
+                            MOUNT_PATH, SQL_KEY
+                        )],
+                        "env": [database_url_env()],
+                        "volumeMounts": [{
+                            "name": "webhooks",
+                            "mountPath": MOUNT_PATH,
+                            "readOnly": true
+                        }]
+                    }],
+                    "volumes": [{
+                        "name": "webhooks",
+                        "secret": { "secretName": name }
+                    }]
+                }
+            }
+        }
+    });
+    job_api
+        .patch(
+            &name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(job),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Creates the queue and its trigger function, drops triggers of removed webhooks and
+/// (re)creates the others. The function is `SECURITY DEFINER`, so roles writing to the
+/// tables need no grant on the queue.
+fn install_sql(webhooks: &BTreeMap<String, DbWebhookConfig>) -> String {
+    let mut sql = format!(
+        "CREATE SCHEMA IF NOT EXISTS stack_webhooks;\n\
+         CREATE TABLE IF NOT EXISTS {table} (\n\
+           id bigserial PRIMARY KEY,\n\
+           webhook text NOT NULL,\n\
+           payload jsonb NOT NULL,\n\
+           status text NOT NULL DEFAULT 'pending',\n\
+           attempts integer NOT NULL DEFAULT 0,\n\
+           last_error text,\n\
+           created_at timestamptz NOT NULL DEFAULT now(),\n\
+           next_attempt_at timestamptz NOT NULL DEFAULT now(),\n\
+           last_attempt_at timestamptz,\n\
+           delivered_at timestamptz\n\
+         );\n\
+         CREATE INDEX IF NOT EXISTS deliveries_pending_idx ON {table} (next_attempt_at) WHERE status = 'pending';\n\
+         CREATE OR REPLACE FUNCTION stack_webhooks.enqueue() RETURNS trigger\n\
+         LANGUAGE plpgsql SECURITY DEFINER SET search_path = pg_catalog AS $$\n\
+         BEGIN\n\
+           INSERT INTO {table} (webhook, payload) VALUES (TG_ARGV[0], jsonb_build_object(\n\
+             'type', TG_OP,\n\
+             'schema', TG_TABLE_SCHEMA,\n\
+             'table', TG_TABLE_NAME,\n\
+             'record', CASE WHEN TG_OP = 'DELETE' THEN NULL ELSE to_jsonb(NEW) END,\n\
+             'old_record', CASE WHEN TG_OP = 'INSERT' THEN NULL ELSE to_jsonb(OLD) END\n\
+           ));\n\
+           RETURN NULL;\n\
+         END\n\
+         $$;\n",
+        table = DELIVERIES_TABLE
+    );
+
+    let wanted: Vec<String> = webhooks
+        .iter()
+        .map(|(name, webhook)| {
+            format!(
+                "('{}{}', '{}'::regclass)",
+                TRIGGER_PREFIX,
+                name,
+                quoted_table(&webhook.table)
+            )
+        })
+        .collect();
+    let keep = if wanted.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " AND (tgname::text, tgrelid) NOT IN (VALUES {})",
+            wanted.join(", ")
+        )
+    };
+    sql.push_str(&format!(
+        "SELECT format('DROP TRIGGER %I ON %s', tgname, tgrelid::regclass) FROM pg_trigger \
+         WHERE NOT tgisinternal AND tgname LIKE '{}%'{}\\gexec\n",
+        TRIGGER_PREFIX.replace('_', "\\_"),
+        keep
+    ));
+    let names: Vec<String> = webhooks.keys().map(|name| format!("'{}'", name)).collect();
+    sql.push_str(&format!(
+        "UPDATE {} SET status = 'failed', last_error = 'webhook removed' WHERE status = 'pending'{};\n",
+        DELIVERIES_TABLE,
+        if names.is_empty() {
+            String::new()
+        } else {
+            format!(" AND webhook NOT IN ({})", names.join(", "))
+        }
+    ));
+
+    for (name, webhook) in webhooks {
+        let events = if webhook.events.is_empty() {
+            vec![
+                WebhookEvent::Insert,
+                WebhookEvent::Update,
+                WebhookEvent::Delete,
+            ]
+        } else {
+            webhook.events.clone()
+        };
+        let mut events: Vec<&str> = events
+            .iter()
+            .map(|event| match event {
+                WebhookEvent::Insert => "INSERT",
+                WebhookEvent::Update => "UPDATE",
+                WebhookEvent::Delete => "DELETE",
+            })
+            .collect();
+        events.sort();
+        events.dedup();
+        sql.push_str(&format!(
+            "DROP TRIGGER IF EXISTS \"{prefix}{name}\" ON {table};\n\
+             CREATE TRIGGER \"{prefix}{name}\" AFTER {events} ON {table} \
+             FOR EACH ROW EXECUTE FUNCTION stack_webhooks.enqueue('{name}');\n",
+            prefix = TRIGGER_PREFIX,
+            name = name,
+            table = quoted_table(&webhook.table),
+            events = events.join(" OR ")
+        ));
+    }
+    sql
+}
+
+fn quoted_table(table: &str) -> String {
+    table
+        .split('.')
+        .map(|part| format!("\"{}\"", part))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Polls the queue and POSTs each pending delivery with the URL and headers of its
+/// webhook, recording every attempt. Failed attempts are retried with a doubling wait.
+fn dispatcher_script(webhooks: &BTreeMap<String, DbWebhookConfig>) -> String {
+    let mut cases = String::new();
+    for (name, webhook) in webhooks {
+        let mut args = vec![shell_quote(&webhook.url)];
+        for (header, value) in &webhook.headers {
+            args.push("--header".to_string());
+            args.push(shell_quote(&format!("{}: {}", header, value)));
+        }
+        cases.push_str(&format!(
+            "      {}) set -- {} ;;\n",
+            shell_quote(name),
+            args.join(" ")
+        ));
+    }
+
+    format!(
+        "set -u\n\
+         tab=$(printf '\\t')\n\
+         idle=0\n\
+         while true; do\n\
+           if ! psql \"$DATABASE_URL\" -X -q -At -F \"$tab\" -v ON_ERROR_STOP=1 \
+             -c \"SELECT id, webhook, payload FROM {table} WHERE status = 'pending' AND next_attempt_at <= now() ORDER BY id LIMIT 100\" > /tmp/batch; then\n\
+             sleep 5\n\
+             continue\n\
+           fi\n\
+           if [ ! -s /tmp/batch ]; then\n\
+             idle=$((idle + 1))\n\
+             if [ $((idle % 1800)) -eq 0 ]; then\n\
+               psql \"$DATABASE_URL\" -X -q -c \"DELETE FROM {table} WHERE status = 'delivered' AND delivered_at < now() - interval '{retention}'\"\n\
+             fi\n\
+             sleep 2\n\
+             continue\n\
+           fi\n\
+           while IFS=\"$tab\" read -r id webhook payload; do\n\
+             printf '%s' \"$payload\" > /tmp/payload\n\
+             case \"$webhook\" in\n\
+         {cases}\
+               *) set -- '' ;;\n\
+             esac\n\
+             url=$1\n\
+             shift\n\
+             if [ -z \"$url\" ]; then\n\
+               error='webhook removed'\n\
+             elif error=$(wget -q -O /dev/null -T {timeout} --header 'Content-Type: application/json' \"$@\" --post-file /tmp/payload \"$url\" 2>&1); then\n\
+               psql \"$DATABASE_URL\" -X -q -v id=\"$id\" <<'SQL'\n\
+         UPDATE {table} SET status = 'delivered', attempts = attempts + 1, last_attempt_at = now(), delivered_at = now(), last_error = NULL WHERE id = :id;\n\
+         SQL\n\
+               continue\n\
+             fi\n\
+             psql \"$DATABASE_URL\" -X -q -v id=\"$id\" -v error=\"$error\" <<'SQL'\n\
+         UPDATE {table} SET attempts = attempts + 1, last_attempt_at = now(), last_error = left(:'error', 1000),\n\
+           status = CASE WHEN attempts + 1 >= {max_attempts} OR :'error' = 'webhook removed' THEN 'failed' ELSE 'pending' END,\n\
+           next_attempt_at = now() + interval '10 seconds' * power(2, attempts)\n\
+         WHERE id = :id;\n\
+         SQL\n\
+           done < /tmp/batch\n\
+         done\n",
+        table = DELIVERIES_TABLE,
+        retention = DELIVERED_RETENTION,
+        cases = cases,
+        timeout = REQUEST_TIMEOUT_SECS,
+        max_attempts = MAX_ATTEMPTS
+    )
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+async fn deploy_dispatcher(
+    client: Client,
+    namespace: &str,
+    app_name: &str,
+    webhooks: &BTreeMap<String, DbWebhookConfig>,
+) -> Result<(), Error> {
+    let name = dispatcher_name(app_name);
+    let script = dispatcher_script(webhooks);
+    let script_hash = content_hash(&script);
+    apply_secret(client.clone(), namespace, &name, SCRIPT_KEY, script).await?;
+
+    deployment::deployment(
+        client,
+        deployment::ServiceDeployment {
+            name: name.clone(),
+            replicas: 1,
+            image_name: WEBHOOKS_IMAGE.to_string(),
+            port: None,
+            env: vec![
+                database_url_env(),
+                // Rolls the dispatcher when its script changes.
+                json!({ "name": "WEBHOOKS_HASH", "value": script_hash }),
+            ],
+            init_containers: vec![],
+            command: Some(deployment::Command {
+                command: vec!["/bin/sh".to_string()],
+                args: vec![format!("{}/{}", MOUNT_PATH, SCRIPT_KEY)],
+            }),
+            volume_mounts: vec![json!({
+                "name": "webhooks",
+                "mountPath": MOUNT_PATH,
+                "readOnly": true
+            })],
+            volumes: vec![json!({
+                "name": "webhooks",
+                "secret": { "secretName": name }
+            })],
+        },
+        namespace,
+    )
+    .await
+}
+
+fn database_url_env() -> serde_json::Value {
+    json!({
+        "name": "DATABASE_URL",
+        "valueFrom": {
+            "secretKeyRef": {
+                "name": DATABASE_URLS_SECRET_NAME,
+                "key": "migrations-url"
+            }
+        }
+    })
+}
+
+/// Secrets rather than ConfigMaps, as webhook headers often carry credentials.
+async fn apply_secret(
+    client: Client,
+    namespace: &str,
+    name: &str,
+    key: &str,
+    value: String,
+) -> Result<(), Error> {
+    let secret = json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": {
+            "name": name,
+            "namespace": namespace
+        },
+        "stringData": { key: value }
+    });
+    let secret_api: Api<Secret> = Api::namespaced(client, namespace);
+    secret_api
+        .patch(
+            name,
+            &PatchParams::apply(crate::MANAGER).force(),
+            &Patch::Apply(secret),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn delete_dispatcher(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    let name = dispatcher_name(app_name);
+    let deployment_api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+    if deployment_api.get_opt(&name).await?.is_some() {
+        deployment_api
+            .delete(&name, &DeleteParams::default())
+            .await?;
+    }
+    let secret_api: Api<Secret> = Api::namespaced(client, namespace);
+    if secret_api.get_opt(&name).await?.is_some() {
+        secret_api.delete(&name, &DeleteParams::default()).await?;
+    }
+    Ok(())
+}
+
+/// Removes the dispatcher and the Job. The triggers and the queue stay with the data.
+pub async fn delete(client: Client, namespace: &str, app_name: &str) -> Result<(), Error> {
+    delete_dispatcher(client.clone(), namespace, app_name).await?;
+    let name = job_name(app_name);
+    let job_api: Api<Job> = Api::namespaced(client.clone(), namespace);
+    if job_api.get_opt(&name).await?.is_some() {
+        job_api.delete(&name, &DeleteParams::background()).await?;
+    }
+    let secret_api: Api<Secret> = Api::namespaced(client, namespace);
+    if secret_api.get_opt(&name).await?.is_some() {
+        secret_api.delete(&name, &DeleteParams::default()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(table: &str, url: &str) -> DbWebhookConfig {
+        DbWebhookConfig {
+            table: table.to_string(),
+            events: vec![],
+            url: url.to_string(),
+            headers: BTreeMap::new(),
+        }
+    }
+
+    fn problems_of(name: &str, config: DbWebhookConfig) -> Vec<String> {
+        problems(&BTreeMap::from([(name.to_string(), config)]))
+            .into_iter()
+            .map(|(webhook, problem)| {
+                assert_eq!(webhook, name);
+                problem
+            })
+            .collect()
+    }
+
+    #[test]
+    fn valid_webhooks() {
+        for (table, url) in [
+            ("orders", "https://hooks.example.com/orders"),
+            ("billing.invoices", "http://web:3000/hooks"),
+            ("_private.order_items2", "https://example.com"),
+        ] {
+            assert!(
+                problems_of("orders", webhook(table, url)).is_empty(),
+                "{}",
+                table
+            );
+        }
+        let mut config = webhook("orders", "https://example.com");
+        config.headers = BTreeMap::from([
+            ("Authorization".to_string(), "Bearer abc".to_string()),
+            ("X-Api_Key".to_string(), "1".to_string()),
+        ]);
+        assert!(problems_of("orders", config).is_empty());
+    }
+
+    #[test]
+    fn names() {
+        let long = "a".repeat(MAX_NAME_LENGTH + 1);
+        for name in ["", "Orders", "new-orders", "orders!", long.as_str()] {
+            assert_eq!(
+                problems_of(name, webhook("orders", "https://example.com")).len(),
+                1,
+                "{}",
+                name
+            );
+        }
+        let longest = "a".repeat(MAX_NAME_LENGTH);
+        assert!(problems_of(&longest, webhook("orders", "https://example.com")).is_empty());
+    }
+
+    #[test]
+    fn tables_and_urls() {
+        for table in [
+            "",
+            "a.b.c",
+            "1orders",
+            "orders;drop",
+            "\"orders\"",
+            "billing.",
+        ] {
+            assert_eq!(
+                problems_of("orders", webhook(table, "https://example.com")).len(),
+                1,
+                "{}",
+                table
+            );
+        }
+        for url in [
+            "",
+            "example.com",
+            "ftp://example.com",
+            "https://example.com/a b",
+        ] {
+            assert_eq!(
+                problems_of("orders", webhook("orders", url)).len(),
+                1,
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn headers() {
+        for (header, value) in [
+            ("", "1"),
+            ("X Key", "1"),
+            ("X:Key", "1"),
+            ("X-Key", "a\r\nX-Other: b"),
+        ] {
+            let mut config = webhook("orders", "https://example.com");
+            config.headers = BTreeMap::from([(header.to_string(), value.to_string())]);
+            assert_eq!(problems_of("orders", config).len(), 1, "{}", header);
+        }
+    }
+
+    #[test]
+    fn install_sql_triggers() {
+        let mut invoices = webhook("billing.invoices", "https://example.com");
+        invoices.events = vec![
+            WebhookEvent::Update,
+            WebhookEvent::Insert,
+            WebhookEvent::Update,
+        ];
+        let sql = install_sql(&BTreeMap::from([
+            (
+                "orders".to_string(),
+                webhook("orders", "https://example.com"),
+            ),
+            ("invoices".to_string(), invoices),
+        ]));
+        assert!(sql.contains(
+            "CREATE TRIGGER \"stack_webhook_orders\" AFTER DELETE OR INSERT OR UPDATE ON \"orders\" \
+             FOR EACH ROW EXECUTE FUNCTION stack_webhooks.enqueue('orders');"
+        ));
+        assert!(sql.contains(
+            "CREATE TRIGGER \"stack_webhook_invoices\" AFTER INSERT OR UPDATE ON \"billing\".\"invoices\" \
+             FOR EACH ROW EXECUTE FUNCTION stack_webhooks.enqueue('invoices');"
+        ));
+        assert!(sql.contains(
+            "NOT IN (VALUES ('stack_webhook_invoices', '\"billing\".\"invoices\"'::regclass), \
+             ('stack_webhook_orders', '\"orders\"'::regclass))\\gexec"
+        ));
+        assert!(sql.contains("AND webhook NOT IN ('invoices', 'orders');"));
+    }
+
+    #[test]
+    fn install_sql_without_webhooks() {
+        let sql = install_sql(&BTreeMap::new());
+        assert!(sql.contains("WHERE NOT tgisinternal AND tgname LIKE 'stack\\_webhook\\_%'\\gexec"));
+        assert!(sql.contains(
+            "UPDATE stack_webhooks.deliveries SET status = 'failed', last_error = 'webhook removed' WHERE status = 'pending';"
+        ));
+        assert!(!sql.contains("CREATE TRIGGER"));
+    }
+}
@@ -1,9 +1,10 @@
 use crate::error::Error;
 use k8s_openapi::api::apps::v1::Deployment;
 use k8s_openapi::api::core::v1::Service;
-use kube::api::{Patch, PatchParams};
+use kube::api::{ListParams, Patch, PatchParams};
 use kube::{api::Api, Client};
 use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub struct Command {
     pub command: Vec<String>,
@@ -166,3 +167,62 @@ pub async fn service(
         .await?;
     Ok(service)
 }
+
+/// Rolls every Deployment whose pods read one of `secret_names` through an env var, so
+/// they pick up new values. Returns the names of the restarted Deployments.
+pub async fn restart_secret_consumers(
+    client: Client,
+    namespace: &str,
+    secret_names: &[&str],
+) -> Result<Vec<String>, Error> {
+    let deployment_api: Api<Deployment> = Api::namespaced(client, namespace);
+    let restarted_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let restart = json!({
+        "spec": {
+            "template": {
+                "metadata": {
+                    "annotations": {
+                        "kubectl.kubernetes.io/restartedAt": restarted_at.to_string()
+                    }
+                }
+            }
+        }
+    });
+    let mut restarted = Vec::new();
+    for deployment in deployment_api.list(&ListParams::default()).await?.items {
+        if !secret_names
+            .iter()
+            .any(|secret_name| reads_secret(&deployment, secret_name))
+        {
+            continue;
+        }
+        let name = deployment.metadata.name.unwrap_or_default();
+        deployment_api
+            .patch(&name, &PatchParams::default(), &Patch::Merge(&restart))
+            .await?;
+        restarted.push(name);
+    }
+
+    Ok(restarted)
+}
+
+fn reads_secret(deployment: &Deployment, secret_name: &str) -> bool {
+    let Some(pod_spec) = deployment
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.template.spec.as_ref())
+    else {
+        return false;
+    };
+    pod_spec
+        .containers
+        .iter()
+        .chain(pod_spec.init_containers.iter().flatten())
+        .flat_map(|container| container.env.iter().flatten())
+        .filter_map(|var| var.value_from.as_ref())
+        .filter_map(|source| source.secret_key_ref.as_ref())
+        .any(|selector| selector.name == secret_name)
+}
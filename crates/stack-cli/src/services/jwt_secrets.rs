@@ -1,10 +1,11 @@
 use crate::error::Error;
+use crate::services::credentials;
 use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
 use k8s_openapi::api::core::v1::Secret;
 use kube::api::{DeleteParams, Patch, PatchParams};
 use kube::{Api, Client};
-use rand::{distr::Alphanumeric, Rng};
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub const JWT_AUTH_SECRET_NAME: &str = "jwt-auth";
@@ -25,26 +26,11 @@ pub async fn ensure_secret(client: Client, namespace: &str) -> Result<(), Error>
     let secret_api: Api<Secret> = Api::namespaced(client, namespace);
     let existing = secret_api.get(JWT_AUTH_SECRET_NAME).await.ok();
 
-    let jwt_secret = existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, JWT_SECRET_KEY))
-        .unwrap_or_else(random_token);
-
-    let anon_jwt = match existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, JWT_ANON_TOKEN_KEY))
-    {
-        Some(value) => value,
-        None => build_jwt(&jwt_secret, "anon")?,
-    };
-
-    let service_role_jwt = match existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, JWT_SERVICE_ROLE_TOKEN_KEY))
-    {
-        Some(value) => value,
-        None => build_jwt(&jwt_secret, "service_role")?,
-    };
+    let string_data = secret_data(|key| {
+        existing
+            .as_ref()
+            .and_then(|secret| read_secret_field(secret, key))
+    })?;
 
     let secret_manifest = serde_json::json!({
         "apiVersion": "v1",
@@ -53,11 +39,7 @@ pub async fn ensure_secret(client: Client, namespace: &str) -> Result<(), Error>
             "name": JWT_AUTH_SECRET_NAME,
             "namespace": namespace
         },
-        "stringData": {
-            JWT_SECRET_KEY: jwt_secret,
-            JWT_ANON_TOKEN_KEY: anon_jwt,
-            JWT_SERVICE_ROLE_TOKEN_KEY: service_role_jwt
-        }
+        "stringData": string_data
     });
 
     secret_api
@@ -71,6 +53,29 @@ pub async fn ensure_secret(client: Client, namespace: &str) -> Result<(), Error>
     Ok(())
 }
 
+/// Contents of the `jwt-auth` secret, keeping any values `existing` already has.
+pub fn secret_data(
+    existing: impl Fn(&str) -> Option<String>,
+) -> Result<BTreeMap<&'static str, String>, Error> {
+    let jwt_secret = existing(JWT_SECRET_KEY).unwrap_or_else(credentials::password);
+
+    let anon_jwt = match existing(JWT_ANON_TOKEN_KEY) {
+        Some(value) => value,
+        None => build_jwt(&jwt_secret, "anon")?,
+    };
+
+    let service_role_jwt = match existing(JWT_SERVICE_ROLE_TOKEN_KEY) {
+        Some(value) => value,
+        None => build_jwt(&jwt_secret, "service_role")?,
+    };
+
+    Ok(BTreeMap::from([
+        (JWT_SECRET_KEY, jwt_secret),
+        (JWT_ANON_TOKEN_KEY, anon_jwt),
+        (JWT_SERVICE_ROLE_TOKEN_KEY, service_role_jwt),
+    ]))
+}
+
 pub async fn delete(client: Client, namespace: &str) -> Result<(), Error> {
     let secret_api: Api<Secret> = Api::namespaced(client, namespace);
     if secret_api.get(JWT_AUTH_SECRET_NAME).await.is_ok() {
@@ -114,14 +119,6 @@ fn build_jwt(secret: &str, role: &str) -> Result<String, Error> {
     .map_err(|err: jsonwebtoken::errors::Error| Error::Other(err.to_string()))
 }
 
-fn random_token() -> String {
-    rand::rng()
-        .sample_iter(Alphanumeric)
-        .take(32)
-        .map(char::from)
-        .collect()
-}
-
 fn read_secret_field(secret: &Secret, key: &str) -> Option<String> {
     if let Some(data) = &secret.data {
         if let Some(value) = data.get(key) {
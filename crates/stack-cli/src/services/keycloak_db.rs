@@ -1,10 +1,10 @@
 use std::collections::BTreeMap;
 
 use super::database::{
-    rand_hex, BootstrapSpec, Cluster, ClusterSpec, InitDBSpec, SecretSpec, StorageSpec,
-    CNPG_INSTALL_HINT,
+    BootstrapSpec, Cluster, ClusterSpec, InitDBSpec, SecretSpec, StorageSpec, CNPG_INSTALL_HINT,
 };
 use crate::error::Error;
+use crate::services::credentials;
 use k8s_openapi::api::core::v1::Secret;
 use kube::api::{DeleteParams, ObjectMeta};
 use kube::{
@@ -24,7 +24,7 @@ pub async fn deploy(
         return Ok(None);
     }
 
-    let database_password: String = rand_hex();
+    let database_password = credentials::password();
 
     let cluster = Cluster {
         metadata: ObjectMeta {
@@ -36,7 +36,7 @@ pub async fn deploy(
             image_name: None, // Just take the default
             instances: 1,
             bootstrap: BootstrapSpec {
-                initdb: InitDBSpec {
+                initdb: Some(InitDBSpec {
                     database: "keycloak".to_string(),
                     owner: "keycloak-db-owner".to_string(),
                     secret: SecretSpec {
@@ -44,12 +44,19 @@ pub async fn deploy(
                     },
                     post_init_sql: None,
                     post_init_application_sql: None,
-                },
+                }),
+                recovery: None,
             },
             storage: StorageSpec {
                 size: format!("{}Gi", disk_size),
+                storage_class: None,
             },
+            wal_storage: None,
+            backup: None,
+            external_clusters: None,
+            postgresql: None,
         },
+        status: None,
     };
     match cluster_api.create(&PostParams::default(), &cluster).await {
         Ok(_) => {}
@@ -1,6 +1,22 @@
 pub mod auth;
 pub mod cloudflare;
+pub mod content_hash;
+pub mod credentials;
 pub mod database;
+pub mod database_backup;
+pub mod database_cdc;
+pub mod database_clone;
+pub mod database_deletion;
+pub mod database_extensions;
+pub mod database_external;
+pub mod database_migrations;
+pub mod database_monitoring;
+pub mod database_parameters;
+pub mod database_pooler;
+pub mod database_rotation;
+pub mod database_seed;
+pub mod database_services;
+pub mod database_webhooks;
 pub mod deployment;
 pub mod document_engine;
 pub mod jwt_secrets;
@@ -12,6 +12,7 @@ use super::deployment;
 
 pub const NGINX_NAME: &str = "nginx";
 pub const NGINX_PORT: u16 = 80;
+pub const NGINX_IMAGE: &str = "nginx:1.27.2";
 
 pub enum NginxMode {
     Oidc,
@@ -178,10 +179,74 @@ pub async fn deploy_nginx(
     include_realtime: bool,
     include_document_engine: bool,
 ) -> Result<(), Error> {
-    let env = vec![];
+    let config_body = nginx_config(
+        &mode,
+        upstream_port,
+        app_name,
+        include_auth,
+        include_storage,
+        storage_max_upload_size_bytes,
+        include_rest,
+        include_realtime,
+        include_document_engine,
+    );
 
-    let image_name = "nginx:1.27.2".to_string();
+    // Put the nginx config into a ConfigMap
+    let config_map = serde_json::json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": {
+            "name": NGINX_NAME,
+            "namespace": namespace
+        },
+        "data": {
+            "default.conf": config_body,
+        }
+    });
 
+    apply::apply(client, &config_map.to_string(), Some(namespace))
+        .await
+        .map_err(Error::from)?;
+
+    // Application with the migrations as a sidecar
+    deployment::deployment(client.clone(), service_deployment(), namespace).await?;
+
+    Ok(())
+}
+
+/// nginx deployment serving `default.conf` from the `nginx` ConfigMap.
+pub fn service_deployment() -> deployment::ServiceDeployment {
+    deployment::ServiceDeployment {
+        name: NGINX_NAME.to_string(),
+        image_name: NGINX_IMAGE.to_string(),
+        replicas: 1,
+        port: Some(NGINX_PORT),
+        env: vec![],
+        command: None,
+        init_containers: vec![],
+        volume_mounts: vec![json!({"name": NGINX_NAME, "mountPath": "/etc/nginx/conf.d"})],
+        volumes: vec![json!({"name": NGINX_NAME,
+            "configMap": {
+                "name": NGINX_NAME
+            }
+        })],
+    }
+}
+
+/// Renders `default.conf`, routing each enabled component under its path prefix and
+/// everything else to the web app.
+#[allow(clippy::too_many_arguments)]
+pub fn nginx_config(
+    mode: &NginxMode,
+    upstream_port: u16,
+    app_name: &str,
+    include_auth: bool,
+    include_storage: bool,
+    storage_max_upload_size_bytes: u64,
+    include_rest: bool,
+    include_realtime: bool,
+    include_document_engine: bool,
+) -> String {
     let storage_block = if include_storage {
         storage_proxy_block("$forwarded_proto", storage_max_upload_size_bytes)
     } else {
@@ -245,7 +310,7 @@ pub async fn deploy_nginx(
         String::new()
     };
 
-    let config_body = match mode {
+    match mode {
         NginxMode::Oidc => {
             format!(
                 r#"
@@ -375,48 +440,7 @@ server {{
                 document_engine_block = document_engine_block
             )
         }
-    };
-
-    // Put the nginx config into a ConfigMap
-    let config_map = serde_json::json!({
-        "apiVersion": "v1",
-        "kind": "ConfigMap",
-        "metadata": {
-            "name": NGINX_NAME,
-            "namespace": namespace
-        },
-        "data": {
-            "default.conf": config_body,
-        }
-    });
-
-    apply::apply(client, &config_map.to_string(), Some(namespace))
-        .await
-        .map_err(Error::from)?;
-
-    // Application with the migrations as a sidecar
-    deployment::deployment(
-        client.clone(),
-        deployment::ServiceDeployment {
-            name: NGINX_NAME.to_string(),
-            image_name,
-            replicas: 1,
-            port: Some(NGINX_PORT),
-            env,
-            command: None,
-            init_containers: vec![],
-            volume_mounts: vec![json!({"name": NGINX_NAME, "mountPath": "/etc/nginx/conf.d"})],
-            volumes: vec![json!({"name": NGINX_NAME,
-                "configMap": {
-                    "name": NGINX_NAME
-                }
-            })],
-        },
-        namespace,
-    )
-    .await?;
-
-    Ok(())
+    }
 }
 
 pub async fn delete_nginx(client: Client, namespace: &str) -> Result<(), Error> {
@@ -1,5 +1,6 @@
 use super::deployment;
 use crate::error::Error;
+use crate::services::credentials;
 use crate::services::keycloak::{RealmConfig, KEYCLOAK_INTERNAL_URL, KEYCLOAK_REALM_BASE_PATH};
 use k8s_openapi::api::apps::v1::Deployment;
 use k8s_openapi::api::core::v1::{Secret, Service};
@@ -151,11 +152,12 @@ pub async fn ensure_secret(
     let client_secret = existing_secret
         .as_ref()
         .and_then(|secret| read_secret_field(secret, "client-secret"))
-        .unwrap_or_else(rand_base64);
+        .unwrap_or_else(credentials::password);
+    // 32 characters, which oauth2-proxy reads as a 24-byte AES key.
     let cookie_secret = existing_secret
         .as_ref()
         .and_then(|secret| read_secret_field(secret, "cookie-secret"))
-        .unwrap_or_else(rand_base64);
+        .unwrap_or_else(credentials::password);
 
     let redirect_uri = redirect_uri_value(hostname_url);
     let issuer_url = format!(
@@ -201,11 +203,6 @@ pub async fn ensure_secret(
     })
 }
 
-pub fn rand_base64() -> String {
-    let random_bytes: [u8; 32] = rand::random();
-    base64::encode_config(random_bytes, base64::URL_SAFE_NO_PAD)
-}
-
 pub async fn delete(client: Client, namespace: &str) -> Result<(), Error> {
     // Remove deployments
     let api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
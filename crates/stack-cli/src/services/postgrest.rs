@@ -20,7 +20,11 @@ pub async fn deploy(
     config: Option<&RestConfig>,
 ) -> Result<(), Error> {
     jwt_secrets::ensure_secret(client.clone(), namespace).await?;
+    deployment::deployment(client, service_deployment(config), namespace).await
+}
 
+/// PostgREST deployment connecting as the `authenticator` role.
+pub fn service_deployment(config: Option<&RestConfig>) -> deployment::ServiceDeployment {
     let db_schemas = config
         .and_then(|c| c.db_schemas.clone())
         .unwrap_or_else(|| DEFAULT_DB_SCHEMAS.to_string());
@@ -70,22 +74,17 @@ pub async fn deploy(
         args: vec![],
     };
 
-    deployment::deployment(
-        client,
-        deployment::ServiceDeployment {
-            name: REST_NAME.to_string(),
-            image_name: DEFAULT_REST_IMAGE.to_string(),
-            replicas: 1,
-            port: Some(DEFAULT_REST_PORT),
-            env,
-            init_containers: vec![],
-            command: Some(command),
-            volume_mounts: vec![],
-            volumes: vec![],
-        },
-        namespace,
-    )
-    .await
+    deployment::ServiceDeployment {
+        name: REST_NAME.to_string(),
+        image_name: DEFAULT_REST_IMAGE.to_string(),
+        replicas: 1,
+        port: Some(DEFAULT_REST_PORT),
+        env,
+        init_containers: vec![],
+        command: Some(command),
+        volume_mounts: vec![],
+        volumes: vec![],
+    }
 }
 
 pub async fn delete(client: Client, namespace: &str) -> Result<(), Error> {
@@ -1,18 +1,19 @@
 use crate::error::Error;
 use crate::operator::crd::RealtimeConfig;
+use crate::services::credentials::{self, CredentialSpec};
 use crate::services::jwt_secrets;
 use crate::services::{database, deployment};
 use k8s_openapi::api::apps::v1::Deployment as KubeDeployment;
 use k8s_openapi::api::core::v1::Secret;
 use kube::api::{DeleteParams, Patch, PatchParams};
 use kube::{Api, Client};
-use rand::{distr::Alphanumeric, Rng};
 use serde_json::json;
+use std::collections::BTreeMap;
 
 pub const REALTIME_NAME: &str = "realtime";
 pub const REALTIME_IMAGE: &str = "supabase/realtime:v2.69.2";
 pub const REALTIME_PORT: u16 = 4000;
-const REALTIME_SECRET_NAME: &str = "realtime-secrets";
+pub const REALTIME_SECRET_NAME: &str = "realtime-secrets";
 const REALTIME_SECRET_KEY_BASE_KEY: &str = "secret-key-base";
 const REALTIME_DB_ENC_KEY: &str = "db-enc-key";
 const DB_ENC_KEY_LEN: usize = 16;
@@ -27,14 +28,17 @@ pub async fn deploy(
 ) -> Result<(), Error> {
     jwt_secrets::ensure_secret(client.clone(), namespace).await?;
     ensure_secret(client.clone(), namespace).await?;
+    deployment::deployment(client, service_deployment(app_name), namespace).await
+}
 
-    let cluster_rw_service = database::cluster_rw_service_name(app_name);
+/// Realtime deployment, with an init container that creates its schema and publication.
+pub fn service_deployment(app_name: &str) -> deployment::ServiceDeployment {
     let db_name = database::database_name(app_name);
 
     let env = vec![
         json!({"name": "PORT", "value": REALTIME_PORT.to_string()}),
-        json!({"name": "DB_HOST", "value": cluster_rw_service}),
-        json!({"name": "DB_PORT", "value": "5432"}),
+        database::host_env("DB_HOST"),
+        database::port_env("DB_PORT"),
         json!({"name": "DB_NAME", "value": db_name}),
         json!({
             "name": "DB_USER",
@@ -95,8 +99,8 @@ pub async fn deploy(
     let init_container = deployment::InitContainer {
         image_name: REALTIME_INIT_IMAGE.to_string(),
         env: vec![
-            json!({"name": "PGHOST", "value": database::cluster_rw_service_name(app_name)}),
-            json!({"name": "PGPORT", "value": "5432"}),
+            database::host_env("PGHOST"),
+            database::port_env("PGPORT"),
             json!({"name": "PGDATABASE", "value": database::database_name(app_name)}),
             json!({
                 "name": "PGUSER",
@@ -126,22 +130,17 @@ pub async fn deploy(
         }),
     };
 
-    deployment::deployment(
-        client,
-        deployment::ServiceDeployment {
-            name: REALTIME_NAME.to_string(),
-            image_name: REALTIME_IMAGE.to_string(),
-            replicas: 1,
-            port: Some(REALTIME_PORT),
-            env,
-            init_containers: vec![init_container],
-            command: None,
-            volume_mounts: vec![],
-            volumes: vec![],
-        },
-        namespace,
-    )
-    .await
+    deployment::ServiceDeployment {
+        name: REALTIME_NAME.to_string(),
+        image_name: REALTIME_IMAGE.to_string(),
+        replicas: 1,
+        port: Some(REALTIME_PORT),
+        env,
+        init_containers: vec![init_container],
+        command: None,
+        volume_mounts: vec![],
+        volumes: vec![],
+    }
 }
 
 pub async fn delete(client: Client, namespace: &str) -> Result<(), Error> {
@@ -174,16 +173,11 @@ async fn ensure_secret(client: Client, namespace: &str) -> Result<(), Error> {
     let secret_api: Api<Secret> = Api::namespaced(client, namespace);
     let existing = secret_api.get(REALTIME_SECRET_NAME).await.ok();
 
-    let secret_key_base = existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, REALTIME_SECRET_KEY_BASE_KEY))
-        .filter(|value| value.len() >= REALTIME_SECRET_KEY_BASE_LEN)
-        .unwrap_or_else(|| random_token_len(REALTIME_SECRET_KEY_BASE_LEN));
-    let db_enc_key = existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, REALTIME_DB_ENC_KEY))
-        .filter(|value| value.len() == DB_ENC_KEY_LEN)
-        .unwrap_or_else(|| random_token_len(DB_ENC_KEY_LEN));
+    let string_data = secret_data(|key| {
+        existing
+            .as_ref()
+            .and_then(|secret| read_secret_field(secret, key))
+    });
 
     let secret_manifest = json!({
         "apiVersion": "v1",
@@ -192,10 +186,7 @@ async fn ensure_secret(client: Client, namespace: &str) -> Result<(), Error> {
             "name": REALTIME_SECRET_NAME,
             "namespace": namespace
         },
-        "stringData": {
-            REALTIME_SECRET_KEY_BASE_KEY: secret_key_base,
-            REALTIME_DB_ENC_KEY: db_enc_key
-        }
+        "stringData": string_data
     });
 
     secret_api
@@ -209,12 +200,26 @@ async fn ensure_secret(client: Client, namespace: &str) -> Result<(), Error> {
     Ok(())
 }
 
-fn random_token_len(len: usize) -> String {
-    rand::rng()
-        .sample_iter(Alphanumeric)
-        .take(len)
-        .map(char::from)
-        .collect()
+/// Contents of the `realtime-secrets` secret, keeping valid values `existing` already has.
+pub fn secret_data(existing: impl Fn(&str) -> Option<String>) -> BTreeMap<&'static str, String> {
+    let secret_key_base = existing(REALTIME_SECRET_KEY_BASE_KEY)
+        .filter(|value| value.len() >= REALTIME_SECRET_KEY_BASE_LEN)
+        .unwrap_or_else(|| random_token_len(REALTIME_SECRET_KEY_BASE_LEN));
+    let db_enc_key = existing(REALTIME_DB_ENC_KEY)
+        .filter(|value| value.len() == DB_ENC_KEY_LEN)
+        .unwrap_or_else(|| random_token_len(DB_ENC_KEY_LEN));
+
+    BTreeMap::from([
+        (REALTIME_SECRET_KEY_BASE_KEY, secret_key_base),
+        (REALTIME_DB_ENC_KEY, db_enc_key),
+    ])
+}
+
+fn random_token_len(length: usize) -> String {
+    credentials::generate(CredentialSpec {
+        length,
+        ..credentials::PASSWORD
+    })
 }
 
 fn read_secret_field(secret: &Secret, key: &str) -> Option<String> {
@@ -1,24 +1,24 @@
 use crate::error::Error;
 use crate::operator::crd::StorageConfig;
 use crate::services::jwt_secrets;
-use crate::services::{database, deployment};
+use crate::services::{credentials, database, deployment};
 use k8s_openapi::api::apps::v1::Deployment as KubeDeployment;
 use k8s_openapi::api::core::v1::{Secret, Service};
 use kube::api::{DeleteParams, Patch, PatchParams};
 use kube::{Api, Client};
-use rand::{distr::Alphanumeric, Rng};
 use serde_json::json;
+use std::collections::BTreeMap;
 
 pub const STORAGE_NAME: &str = "storage";
 pub const DEFAULT_STORAGE_IMAGE: &str = "supabase/storage-api:v1.33.0";
 pub const DEFAULT_STORAGE_PORT: u16 = 5000;
-const STORAGE_S3_SECRET_NAME: &str = "storage-s3";
-const STORAGE_S3_BUCKET_KEY: &str = "STORAGE_S3_BUCKET";
-const STORAGE_S3_ENDPOINT_KEY: &str = "STORAGE_S3_ENDPOINT";
-const STORAGE_S3_REGION_KEY: &str = "STORAGE_S3_REGION";
+pub const STORAGE_S3_SECRET_NAME: &str = "storage-s3";
+pub const STORAGE_S3_BUCKET_KEY: &str = "STORAGE_S3_BUCKET";
+pub const STORAGE_S3_ENDPOINT_KEY: &str = "STORAGE_S3_ENDPOINT";
+pub const STORAGE_S3_REGION_KEY: &str = "STORAGE_S3_REGION";
 const STORAGE_S3_FORCE_PATH_STYLE_KEY: &str = "STORAGE_S3_FORCE_PATH_STYLE";
-const AWS_ACCESS_KEY_ID_KEY: &str = "AWS_ACCESS_KEY_ID";
-const AWS_SECRET_ACCESS_KEY_KEY: &str = "AWS_SECRET_ACCESS_KEY";
+pub const AWS_ACCESS_KEY_ID_KEY: &str = "AWS_ACCESS_KEY_ID";
+pub const AWS_SECRET_ACCESS_KEY_KEY: &str = "AWS_SECRET_ACCESS_KEY";
 const S3_PROTOCOL_ACCESS_KEY_ID_KEY: &str = "S3_PROTOCOL_ACCESS_KEY_ID";
 const S3_PROTOCOL_ACCESS_KEY_SECRET_KEY: &str = "S3_PROTOCOL_ACCESS_KEY_SECRET";
 const DEFAULT_S3_BUCKET: &str = "supa-storage-bucket";
@@ -69,25 +69,45 @@ pub async fn deploy(
     app_name: &str,
     config: Option<&StorageConfig>,
 ) -> Result<(), Error> {
-    let upload_size_limit = storage_upload_size_limit_bytes(config);
-    let secret_name = config
-        .and_then(|c| c.s3_secret_name.as_ref())
-        .map(String::from)
-        .unwrap_or_else(|| STORAGE_S3_SECRET_NAME.to_string());
-    let secret_name_env = secret_name.clone();
-    let install_minio = config.map_or(true, |c| {
-        c.install_minio.unwrap_or(c.s3_secret_name.is_none())
-    });
+    let secret_name = s3_secret_name(config);
 
     jwt_secrets::ensure_secret(client.clone(), namespace).await?;
-    if config.is_none() || config.and_then(|c| c.s3_secret_name.as_ref()).is_none() {
+    if config.and_then(|c| c.s3_secret_name.as_ref()).is_none() {
         ensure_s3_secret(client.clone(), namespace, &secret_name).await?;
     }
 
-    if install_minio {
-        deploy_minio(client.clone(), namespace, &secret_name).await?;
+    if installs_minio(config) {
+        deployment::deployment(client.clone(), minio_deployment(&secret_name), namespace).await?;
     }
 
+    deployment::deployment(client, service_deployment(app_name, config), namespace).await
+}
+
+/// Name of the secret Storage reads its S3 settings from.
+pub fn s3_secret_name(config: Option<&StorageConfig>) -> String {
+    config
+        .and_then(|c| c.s3_secret_name.as_ref())
+        .map(String::from)
+        .unwrap_or_else(|| STORAGE_S3_SECRET_NAME.to_string())
+}
+
+/// Whether the bundled MinIO is deployed alongside Storage.
+pub fn installs_minio(config: Option<&StorageConfig>) -> bool {
+    config.map_or(true, |c| {
+        c.install_minio.unwrap_or(c.s3_secret_name.is_none())
+    })
+}
+
+/// Storage API deployment, with init containers that prepare its schema and, when MinIO
+/// is bundled, create the buckets.
+pub fn service_deployment(
+    app_name: &str,
+    config: Option<&StorageConfig>,
+) -> deployment::ServiceDeployment {
+    let upload_size_limit = storage_upload_size_limit_bytes(config);
+    let secret_name_env = s3_secret_name(config);
+    let install_minio = installs_minio(config);
+
     let minio_init = if install_minio {
         Some(deployment::InitContainer {
             image_name: MINIO_MC_IMAGE.to_string(),
@@ -147,11 +167,8 @@ mc anonymous set download supa-minio/warehouse--table-s3 || true
     let storage_db_init = deployment::InitContainer {
         image_name: STORAGE_DB_INIT_IMAGE.to_string(),
         env: vec![
-            json!({
-                "name": "PGHOST",
-                "value": database::cluster_rw_service_name(app_name)
-            }),
-            json!({"name": "PGPORT", "value": "5432"}),
+            database::host_env("PGHOST"),
+            database::port_env("PGPORT"),
             json!({
                 "name": "PGDATABASE",
                 "value": database::database_name(app_name)
@@ -349,22 +366,17 @@ SQL"#
         "emptyDir": {}
     })];
 
-    deployment::deployment(
-        client,
-        deployment::ServiceDeployment {
-            name: STORAGE_NAME.to_string(),
-            image_name: DEFAULT_STORAGE_IMAGE.to_string(),
-            replicas: 1,
-            port: Some(DEFAULT_STORAGE_PORT),
-            env,
-            init_containers,
-            command: None,
-            volume_mounts,
-            volumes,
-        },
-        namespace,
-    )
-    .await
+    deployment::ServiceDeployment {
+        name: STORAGE_NAME.to_string(),
+        image_name: DEFAULT_STORAGE_IMAGE.to_string(),
+        replicas: 1,
+        port: Some(DEFAULT_STORAGE_PORT),
+        env,
+        init_containers,
+        command: None,
+        volume_mounts,
+        volumes,
+    }
 }
 
 pub async fn delete(client: Client, namespace: &str) -> Result<(), Error> {
@@ -406,38 +418,11 @@ async fn ensure_s3_secret(client: Client, namespace: &str, secret_name: &str) ->
     let secret_api: Api<Secret> = Api::namespaced(client, namespace);
     let existing = secret_api.get(secret_name).await.ok();
 
-    let bucket = existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, STORAGE_S3_BUCKET_KEY))
-        .unwrap_or_else(|| DEFAULT_S3_BUCKET.to_string());
-    let endpoint = existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, STORAGE_S3_ENDPOINT_KEY))
-        .unwrap_or_else(|| DEFAULT_S3_ENDPOINT.to_string());
-    let region = existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, STORAGE_S3_REGION_KEY))
-        .unwrap_or_else(|| DEFAULT_S3_REGION.to_string());
-    let force_path_style = existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, STORAGE_S3_FORCE_PATH_STYLE_KEY))
-        .unwrap_or_else(|| DEFAULT_S3_FORCE_PATH_STYLE.to_string());
-    let access_key_id = existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, AWS_ACCESS_KEY_ID_KEY))
-        .unwrap_or_else(random_token);
-    let secret_access_key = existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, AWS_SECRET_ACCESS_KEY_KEY))
-        .unwrap_or_else(random_token);
-    let protocol_access_key_id = existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, S3_PROTOCOL_ACCESS_KEY_ID_KEY))
-        .unwrap_or_else(random_token);
-    let protocol_access_key_secret = existing
-        .as_ref()
-        .and_then(|secret| read_secret_field(secret, S3_PROTOCOL_ACCESS_KEY_SECRET_KEY))
-        .unwrap_or_else(random_token);
+    let string_data = s3_secret_data(|key| {
+        existing
+            .as_ref()
+            .and_then(|secret| read_secret_field(secret, key))
+    });
 
     let secret_manifest = json!({
         "apiVersion": "v1",
@@ -446,16 +431,7 @@ async fn ensure_s3_secret(client: Client, namespace: &str, secret_name: &str) ->
             "name": secret_name,
             "namespace": namespace
         },
-        "stringData": {
-            STORAGE_S3_BUCKET_KEY: bucket,
-            STORAGE_S3_ENDPOINT_KEY: endpoint,
-            STORAGE_S3_REGION_KEY: region,
-            STORAGE_S3_FORCE_PATH_STYLE_KEY: force_path_style,
-            AWS_ACCESS_KEY_ID_KEY: access_key_id,
-            AWS_SECRET_ACCESS_KEY_KEY: secret_access_key,
-            S3_PROTOCOL_ACCESS_KEY_ID_KEY: protocol_access_key_id,
-            S3_PROTOCOL_ACCESS_KEY_SECRET_KEY: protocol_access_key_secret
-        }
+        "stringData": string_data
     });
 
     secret_api
@@ -469,7 +445,37 @@ async fn ensure_s3_secret(client: Client, namespace: &str, secret_name: &str) ->
     Ok(())
 }
 
-async fn deploy_minio(client: Client, namespace: &str, secret_name: &str) -> Result<(), Error> {
+/// Contents of the generated S3 secret, pointing at the bundled MinIO and keeping any
+/// values `existing` already has.
+pub fn s3_secret_data(existing: impl Fn(&str) -> Option<String>) -> BTreeMap<&'static str, String> {
+    let defaults = [
+        (STORAGE_S3_BUCKET_KEY, Some(DEFAULT_S3_BUCKET)),
+        (STORAGE_S3_ENDPOINT_KEY, Some(DEFAULT_S3_ENDPOINT)),
+        (STORAGE_S3_REGION_KEY, Some(DEFAULT_S3_REGION)),
+        (
+            STORAGE_S3_FORCE_PATH_STYLE_KEY,
+            Some(DEFAULT_S3_FORCE_PATH_STYLE),
+        ),
+        (AWS_ACCESS_KEY_ID_KEY, None),
+        (AWS_SECRET_ACCESS_KEY_KEY, None),
+        (S3_PROTOCOL_ACCESS_KEY_ID_KEY, None),
+        (S3_PROTOCOL_ACCESS_KEY_SECRET_KEY, None),
+    ];
+
+    defaults
+        .into_iter()
+        .map(|(key, default)| {
+            let value = existing(key).unwrap_or_else(|| match default {
+                Some(default) => default.to_string(),
+                None => credentials::password(),
+            });
+            (key, value)
+        })
+        .collect()
+}
+
+/// MinIO deployment using the root credentials from the S3 secret.
+pub fn minio_deployment(secret_name: &str) -> deployment::ServiceDeployment {
     let env = vec![
         json!({
             "name": "MINIO_ROOT_USER",
@@ -512,30 +518,17 @@ async fn deploy_minio(client: Client, namespace: &str, secret_name: &str) -> Res
         "emptyDir": {}
     })];
 
-    deployment::deployment(
-        client.clone(),
-        deployment::ServiceDeployment {
-            name: MINIO_NAME.to_string(),
-            image_name: MINIO_IMAGE.to_string(),
-            replicas: 1,
-            port: Some(MINIO_PORT),
-            env,
-            init_containers: vec![],
-            command: Some(command),
-            volume_mounts,
-            volumes,
-        },
-        namespace,
-    )
-    .await
-}
-
-fn random_token() -> String {
-    rand::rng()
-        .sample_iter(Alphanumeric)
-        .take(32)
-        .map(char::from)
-        .collect()
+    deployment::ServiceDeployment {
+        name: MINIO_NAME.to_string(),
+        image_name: MINIO_IMAGE.to_string(),
+        replicas: 1,
+        port: Some(MINIO_PORT),
+        env,
+        init_containers: vec![],
+        command: Some(command),
+        volume_mounts,
+        volumes,
+    }
 }
 
 fn read_secret_field(secret: &Secret, key: &str) -> Option<String> {